@@ -0,0 +1,62 @@
+//! How much of an image's alpha channel is actually used.
+
+use png::{BitDepth, ColorType};
+
+use crate::Png;
+
+/// Pixel counts by alpha value, from `alpha_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlphaStats {
+    /// Alpha at its maximum: 255, or 65535 for 16-bit samples
+    pub opaque: u64,
+    /// Alpha of zero
+    pub transparent: u64,
+    /// Everything in between
+    pub partial: u64,
+}
+
+impl AlphaStats {
+    pub fn total(&self) -> u64 {
+        self.opaque + self.transparent + self.partial
+    }
+
+    /// Whether every pixel is fully opaque, so the alpha channel can be
+    /// dropped without changing the picture.
+    pub fn is_unused(&self) -> bool {
+        self.opaque == self.total()
+    }
+}
+
+/// Count the opaque, transparent and partially transparent pixels of an
+/// RGBA or grayscale+alpha image. Returns `None` for color types without an
+/// alpha channel, or if the pixels were not decoded.
+pub fn alpha_stats(png: &Png) -> Option<AlphaStats> {
+    let channels = match png.color_type {
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgba => 4,
+        _ => return None,
+    };
+    let sample_bytes = if png.bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+    let pixel_bytes = channels * sample_bytes;
+    let pixels = png.pixels();
+    if pixels.is_empty() {
+        return None;
+    }
+
+    let max = if sample_bytes == 2 { u16::MAX } else { u8::MAX as u16 };
+    let mut stats = AlphaStats::default();
+    // Alpha is the last sample; 16-bit samples are big-endian
+    let alpha_at = pixel_bytes - sample_bytes;
+    for pixel in pixels.chunks_exact(pixel_bytes) {
+        let alpha = match sample_bytes {
+            2 => u16::from_be_bytes([pixel[alpha_at], pixel[alpha_at + 1]]),
+            _ => pixel[alpha_at] as u16,
+        };
+        match alpha {
+            0 => stats.transparent += 1,
+            alpha if alpha == max => stats.opaque += 1,
+            _ => stats.partial += 1,
+        }
+    }
+    Some(stats)
+}
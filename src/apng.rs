@@ -0,0 +1,248 @@
+//! Compositing APNG frames onto the full canvas, for extracting one frame
+//! as a still image, and assembling still images into an APNG.
+
+use png::{BitDepth, BlendOp, ColorType, Decoder, DisposeOp, Encoder, FrameControl, Limits, Transformations};
+
+use crate::transform::{convert_bit_depth, convert_color_type};
+use crate::UnpeelError;
+
+/// One fully composited frame, expanded to 8 or 16 bits per sample with any
+/// palette or tRNS chunk turned into RGB(A) or gray-alpha pixels.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: BitDepth,
+    pub pixels: Vec<u8>,
+}
+
+/// Composite frame `index` of the APNG in `bytes` the way a viewer would show
+/// it, applying each earlier frame's blend and dispose operations.
+///
+/// A static PNG has a single frame, the whole image. A default image that is
+/// not part of the animation (IDAT without an fcTL) is not counted as a frame.
+/// Out-of-range indices fail with `FrameOutOfRange`, which carries the count.
+pub fn extract_frame(bytes: &[u8], index: usize, max_bytes: usize) -> Result<Frame, UnpeelError> {
+    let mut decoder = Decoder::new(bytes);
+    decoder.set_limits(Limits { bytes: max_bytes });
+    decoder.ignore_checksums(true);
+    decoder.set_ignore_text_chunk(true);
+    decoder.set_transformations(Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+
+    let (width, height) = (reader.info().width, reader.info().height);
+    let frames = reader.info().animation_control.map_or(1, |actl| actl.num_frames as usize);
+    if index >= frames {
+        return Err(UnpeelError::FrameOutOfRange { index, frames });
+    }
+    let size = reader.output_buffer_size();
+    if size > max_bytes {
+        return Err(UnpeelError::ImageTooLarge { width, height, bytes: Some(size), limit: max_bytes });
+    }
+    let (color_type, bit_depth) = reader.output_color_type();
+    let mut buffer = vec![0; size];
+    let mut canvas = vec![0; size];
+
+    let animated = reader.info().animation_control.is_some();
+    if animated && reader.info().frame_control.is_none() {
+        // Skip the default image shown by viewers that don't support APNG
+        reader.next_frame(&mut buffer)?;
+    }
+
+    let layout = Layout::new(width, color_type, bit_depth);
+    for current in 0..=index {
+        reader.next_frame(&mut buffer)?;
+        let Some(control) = reader.info().frame_control else {
+            // A static image: the frame is the whole canvas
+            canvas.copy_from_slice(&buffer);
+            break;
+        };
+        let mut dispose = control.dispose_op;
+        if current == 0 && dispose == DisposeOp::Previous {
+            // There is no previous canvas to restore before the first frame
+            dispose = DisposeOp::Background;
+        }
+        let saved = (dispose == DisposeOp::Previous).then(|| layout.copy_region(&canvas, &control));
+        layout.blend(&mut canvas, &buffer, &control);
+        if current == index {
+            break;
+        }
+        match (dispose, saved) {
+            (DisposeOp::Background, _) => layout.clear_region(&mut canvas, &control),
+            (DisposeOp::Previous, Some(saved)) => layout.paste_region(&mut canvas, &saved, &control),
+            _ => {}
+        }
+    }
+
+    Ok(Frame { width, height, color_type, bit_depth, pixels: canvas })
+}
+
+/// Encode `frames` as an APNG that loops forever, showing each frame for its
+/// paired delay in milliseconds. Every frame replaces the whole canvas.
+///
+/// All frames must be the size of the first. Frames that share a color type
+/// and bit depth are written as they are; otherwise every frame is converted
+/// to RGBA, at 16 bits if any frame has 16-bit samples. An empty list fails
+/// with `FrameOutOfRange`.
+pub fn assemble_apng(frames: Vec<(Frame, u16)>) -> Result<Vec<u8>, UnpeelError> {
+    let Some((first, _)) = frames.first() else {
+        return Err(UnpeelError::FrameOutOfRange { index: 0, frames: 0 });
+    };
+    let expected = (first.width, first.height);
+    if let Some(index) = frames.iter().position(|(frame, _)| (frame.width, frame.height) != expected) {
+        let size = (frames[index].0.width, frames[index].0.height);
+        return Err(UnpeelError::FrameSizeMismatch { index, size, expected });
+    }
+    let format = (first.color_type, first.bit_depth);
+    let frames = if frames.iter().all(|(frame, _)| (frame.color_type, frame.bit_depth) == format) {
+        frames
+    } else {
+        let sixteen = frames.iter().any(|(frame, _)| frame.bit_depth == BitDepth::Sixteen);
+        let converted = frames.into_iter().map(|(frame, delay)| to_rgba_frame(frame, sixteen).map(|rgba| (rgba, delay)));
+        converted.collect::<Result<_, _>>()?
+    };
+
+    let (width, height) = expected;
+    let (color_type, bit_depth) = (frames[0].0.color_type, frames[0].0.bit_depth);
+    let mut encoded = Vec::new();
+    let mut encoder = Encoder::new(&mut encoded, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+    // Zero plays loops forever
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let mut writer = encoder.write_header()?;
+    for (frame, delay) in &frames {
+        writer.set_frame_delay(*delay, 1000)?;
+        writer.write_image_data(&frame.pixels)?;
+    }
+    writer.finish()?;
+    Ok(encoded)
+}
+
+/// Convert an expanded frame to RGBA, at 16 bits when `sixteen` is set.
+fn to_rgba_frame(frame: Frame, sixteen: bool) -> Result<Frame, UnpeelError> {
+    let (width, height) = (frame.width, frame.height);
+    let rgba = convert_color_type(
+        &frame.pixels,
+        width,
+        height,
+        frame.color_type,
+        frame.bit_depth,
+        &[],
+        None,
+        ColorType::Rgba,
+        false,
+    )?;
+    let (pixels, bit_depth) = match (rgba.bit_depth, sixteen) {
+        (BitDepth::Eight, true) => {
+            let widened = convert_bit_depth(&rgba.pixels, BitDepth::Eight, BitDepth::Sixteen);
+            (widened.ok_or(UnpeelError::UnsupportedColorType(frame.color_type))?, BitDepth::Sixteen)
+        }
+        (bit_depth, _) => (rgba.pixels, bit_depth),
+    };
+    Ok(Frame { width, height, color_type: ColorType::Rgba, bit_depth, pixels })
+}
+
+/// How pixels are laid out in the expanded canvas and frame buffers.
+struct Layout {
+    stride: usize,
+    samples: usize,
+    sample_bytes: usize,
+    has_alpha: bool,
+}
+
+impl Layout {
+    fn new(width: u32, color_type: ColorType, bit_depth: BitDepth) -> Layout {
+        let samples = color_type.samples();
+        let sample_bytes = if bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+        Layout {
+            stride: width as usize * samples * sample_bytes,
+            samples,
+            sample_bytes,
+            has_alpha: matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba),
+        }
+    }
+
+    fn pixel_bytes(&self) -> usize {
+        self.samples * self.sample_bytes
+    }
+
+    /// Byte range of each canvas row covered by the frame region.
+    fn rows(&self, control: &FrameControl) -> impl Iterator<Item = std::ops::Range<usize>> + '_ {
+        let (x, y) = (control.x_offset as usize, control.y_offset as usize);
+        let (width, height) = (control.width as usize, control.height as usize);
+        let start = x * self.pixel_bytes();
+        let len = width * self.pixel_bytes();
+        (y..y + height).map(move |row| row * self.stride + start..row * self.stride + start + len)
+    }
+
+    fn copy_region(&self, canvas: &[u8], control: &FrameControl) -> Vec<u8> {
+        self.rows(control).flat_map(|range| canvas[range].iter().copied()).collect()
+    }
+
+    fn paste_region(&self, canvas: &mut [u8], saved: &[u8], control: &FrameControl) {
+        let len = control.width as usize * self.pixel_bytes();
+        for (range, row) in self.rows(control).zip(saved.chunks_exact(len)) {
+            canvas[range].copy_from_slice(row);
+        }
+    }
+
+    /// Dispose to the background: fully transparent black.
+    fn clear_region(&self, canvas: &mut [u8], control: &FrameControl) {
+        for range in self.rows(control) {
+            canvas[range].fill(0);
+        }
+    }
+
+    /// Draw the subframe in `frame` (rows packed at the subframe's width) onto
+    /// the canvas with the frame's blend operation.
+    fn blend(&self, canvas: &mut [u8], frame: &[u8], control: &FrameControl) {
+        let len = control.width as usize * self.pixel_bytes();
+        let over = control.blend_op == BlendOp::Over && self.has_alpha;
+        for (range, row) in self.rows(control).zip(frame.chunks_exact(len)) {
+            let dst = &mut canvas[range];
+            if !over {
+                dst.copy_from_slice(row);
+                continue;
+            }
+            for (dst, src) in dst.chunks_exact_mut(self.pixel_bytes()).zip(row.chunks_exact(self.pixel_bytes())) {
+                self.blend_over(dst, src);
+            }
+        }
+    }
+
+    /// Composite one source pixel over one canvas pixel, per the APNG spec.
+    fn blend_over(&self, dst: &mut [u8], src: &[u8]) {
+        let read = |pixel: &[u8], i: usize| -> u64 {
+            match self.sample_bytes {
+                2 => u16::from_be_bytes([pixel[2 * i], pixel[2 * i + 1]]) as u64,
+                _ => pixel[i] as u64,
+            }
+        };
+        let alpha = self.samples - 1;
+        let max = if self.sample_bytes == 2 { 0xFFFF } else { 0xFF };
+        let (src_a, dst_a) = (read(src, alpha), read(dst, alpha));
+        if src_a == max {
+            dst.copy_from_slice(src);
+            return;
+        }
+        if src_a == 0 {
+            return;
+        }
+        // Destination alpha weighted by how much of it shows through the source
+        let dst_weight = dst_a * (max - src_a) / max;
+        let out_a = src_a + dst_weight;
+        let mut out = vec![0u64; self.samples];
+        for (i, value) in out.iter_mut().enumerate().take(alpha) {
+            *value = (read(src, i) * src_a + read(dst, i) * dst_weight) / out_a;
+        }
+        out[alpha] = out_a;
+        for (i, value) in out.into_iter().enumerate() {
+            match self.sample_bytes {
+                2 => dst[2 * i..2 * i + 2].copy_from_slice(&(value as u16).to_be_bytes()),
+                _ => dst[i] = value as u8,
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! `--assemble`: build an APNG from a sequence of still images.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use unpeel::{assemble_apng, extract_frame, write_output_file, UnpeelError};
+
+use crate::batch;
+use crate::cli::{parse_delay, Args};
+use crate::style;
+use crate::{write_error, Failure, EXIT_NOT_FOUND, EXIT_WRITE};
+
+/// Frame delay when neither --delay nor --delays is given.
+const DEFAULT_DELAY_MS: u16 = 100;
+
+/// The frames to assemble, in order: the --from-list entries, the PNGs in a
+/// directory, the matches of a glob, or a single file.
+pub fn input_files(args: &Args) -> Result<Vec<PathBuf>, Failure> {
+    let input = args.from_list.as_deref().unwrap_or_else(|| args.input());
+    let files = if args.from_list.is_some() {
+        batch::read_list(input).map_err(|e| format!("Error: {}", e))?
+    } else if Path::new(input).is_dir() {
+        batch::collect_pngs(Path::new(input), args.recursive).map_err(|e| format!("Error reading directory: {}", e))?
+    } else if !Path::new(input).exists() && batch::is_glob_pattern(input) {
+        batch::expand_glob(input).map_err(|e| format!("Error: {}", e))?
+    } else {
+        vec![PathBuf::from(input)]
+    };
+    if files.is_empty() {
+        let message = format!("Error: no PNG files to assemble in '{}'", input);
+        return Err(Failure { message, code: EXIT_NOT_FOUND });
+    }
+    Ok(files)
+}
+
+/// Each frame's delay in milliseconds: one per line of --delays, which must
+/// have a line per frame, or --delay for every frame.
+fn frame_delays(args: &Args, frames: usize) -> Result<Vec<u16>, String> {
+    let Some(path) = &args.delays else {
+        return Ok(vec![args.delay.unwrap_or(DEFAULT_DELAY_MS); frames]);
+    };
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let delays: Vec<u16> = lines.map(parse_delay).collect::<Result<_, _>>().map_err(|e| format!("{}: {}", path.display(), e))?;
+    if delays.len() != frames {
+        return Err(format!("{} lists {} delay(s) for {} frame(s)", path.display(), delays.len(), frames));
+    }
+    Ok(delays)
+}
+
+/// Read every file in `files` as a frame, using the first frame of any that
+/// are animated, and write them to --output as one APNG.
+pub fn run(files: &[PathBuf], args: &Args) -> Result<(), Failure> {
+    let delays = frame_delays(args, files.len()).map_err(|e| format!("Error: --delays: {}", e))?;
+    let mut frames = Vec::with_capacity(files.len());
+    for (file, delay) in files.iter().zip(delays) {
+        let bytes = fs::read(file).map_err(UnpeelError::from);
+        let frame = bytes.and_then(|bytes| extract_frame(&bytes, 0, args.max_bytes));
+        let frame = frame.map_err(|e| Failure::reading(format!("Error reading {}: {}", file.display(), e), &e))?;
+        frames.push((frame, delay));
+    }
+    let Some((width, height)) = frames.first().map(|(frame, _)| (frame.width, frame.height)) else {
+        return Err("Error: no frames to assemble".to_string().into());
+    };
+    let encoded = assemble_apng(frames).map_err(|e| {
+        let message = match &e {
+            UnpeelError::FrameSizeMismatch { index, .. } => format!("Error: {}: {}", files[*index].display(), e),
+            _ => format!("Error: {}", e),
+        };
+        Failure::reading(message, &e)
+    })?;
+
+    // --assemble requires --output
+    let output = args.output.as_deref().unwrap_or(Path::new(""));
+    if !args.quiet {
+        println!("{} {} frames, {}x{}", style::key("Animation"), files.len(), width, height);
+    }
+    if args.dry_run {
+        if !args.quiet {
+            println!("Dry run: {} not written", output.display());
+        }
+        return Ok(());
+    }
+    write_output_file(output, &encoded, args.force)
+        .map_err(|e| Failure { message: format!("Error: {}", write_error(output, &e)), code: EXIT_WRITE })?;
+    println!("Successfully wrote image to: {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn empty_list_is_not_found() {
+        let list = std::env::temp_dir().join(format!("unpeel-test-{}-empty-list.txt", std::process::id()));
+        fs::write(&list, "# nothing yet\n").unwrap();
+        let list_arg = list.to_string_lossy().into_owned();
+        let args = Args::parse_from(["unpeel", "--assemble", "--from-list", &list_arg, "--output", "out.png"]);
+
+        let failure = input_files(&args).unwrap_err();
+        fs::remove_file(&list).unwrap();
+        assert_eq!(failure.code, EXIT_NOT_FOUND);
+        assert!(failure.message.contains("no PNG files to assemble"), "{}", failure.message);
+    }
+}
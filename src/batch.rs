@@ -0,0 +1,307 @@
+//! Processing every PNG in a directory or matched by a glob pattern.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde_json::Value;
+use unpeel::{OutputTemplate, StripRules};
+
+use crate::cli::Args;
+use crate::{process_file, Processed};
+use crate::progress;
+use crate::report;
+use crate::style;
+use crate::summary;
+use crate::timing::{self, Timings};
+use crate::verify;
+
+/// Process every `.png` under `dir`. See `run_files`.
+pub fn run(dir: &Path, args: &Args, strip_rules: &StripRules) -> bool {
+    let files = match collect_pngs(dir, args.recursive) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Error reading directory '{}': {}", dir.display(), e);
+            return false;
+        }
+    };
+    run_files(&files, dir, args, strip_rules)
+}
+
+/// Process `files`, printing one line per file and a final tally. Returns
+/// whether every file succeeded.
+///
+/// Files are processed on a thread pool of `--jobs` threads, one per CPU by
+/// default; each file's result is printed in input order once all are done.
+///
+/// With `--output`, results go into that directory, mirroring the layout of
+/// subdirectories below `root`.
+pub fn run_files(files: &[PathBuf], root: &Path, args: &Args, strip_rules: &StripRules) -> bool {
+    if args.verify_only {
+        return verify::run_files(files, !args.quiet);
+    }
+    if args.summary_only {
+        return summary::run_files(files, !args.quiet);
+    }
+    let single_file_only = args.dump_icc.is_some()
+        || args.dump_text.is_some()
+        || args.dump_trailing.is_some()
+        || args.extract_chunk.is_some()
+        || args.extract_frame.is_some()
+        || args.to_ppm.is_some()
+        || args.fix_crc;
+    if single_file_only {
+        eprintln!(
+            "Error: --dump-icc, --dump-text, --dump-trailing, --extract-chunk, --extract-frame, --to-ppm and --fix-crc \
+             need a single input file"
+        );
+        return false;
+    }
+    if let Some(output) = &args.output {
+        if output.exists() && !output.is_dir() {
+            eprintln!("Error: --output must be a directory when processing multiple files");
+            return false;
+        }
+    }
+
+    if let Some(template) = &args.output_template {
+        // Catch clashes before anything is written, rather than overwriting outputs part way through
+        let mut seen = HashMap::new();
+        for (index, file) in files.iter().enumerate() {
+            if let Some(earlier) = seen.insert(template.render(file, index + 1), file) {
+                eprintln!(
+                    "Error: --output-template gives {} and {} the same output name; add {{index}} or {{parent}}",
+                    earlier.display(),
+                    file.display()
+                );
+                return false;
+            }
+        }
+    }
+
+    // Zero threads lets rayon pick one per CPU
+    let pool = match ThreadPoolBuilder::new().num_threads(args.jobs.map_or(0, usize::from)).build() {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Error starting worker threads: {}", e);
+            return false;
+        }
+    };
+    debug!("processing {} files on {} worker threads", files.len(), pool.current_num_threads());
+    let bar = progress::batch_bar(files.len(), !args.quiet);
+    // Files are processed concurrently, but the results are collected and
+    // printed in input order so the output doesn't depend on scheduling
+    let results: Vec<(Option<Value>, Result<Processed, String>)> = pool.install(|| {
+        files
+            .par_iter()
+            .enumerate()
+            .map(|(index, file)| {
+                let result = process_one(file, index + 1, root, args, strip_rules);
+                bar.inc(1);
+                result
+            })
+            .collect()
+    });
+    bar.finish_and_clear();
+
+    let mut failures = Vec::new();
+    let mut timings = Timings::default();
+    for (file, (json_report, result)) in files.iter().zip(results) {
+        let file_path = file.to_string_lossy();
+        if let Some(report) = json_report {
+            println!("{}", report);
+        }
+        match result {
+            Ok(done) => {
+                timings.add(&done.timings);
+                if !args.json {
+                    print_processed(&file_path, &done);
+                }
+            }
+            Err(message) => {
+                eprintln!("{}  {}", style::fail("FAIL"), file_path);
+                failures.push((file_path.to_string(), message));
+            }
+        }
+    }
+
+    // The JSON stream stays one report per line, so the tally goes to stderr there
+    let summary = format!("\nProcessed {} file(s): {} succeeded, {} failed", files.len(), files.len() - failures.len(), failures.len());
+    if args.json {
+        eprintln!("{}", summary);
+    } else {
+        println!("{}", summary);
+    }
+    if args.timing {
+        timing::print_batch(&timings, files.len() - failures.len());
+    }
+    if !failures.is_empty() {
+        eprintln!("Failed files:");
+        for (file, e) in &failures {
+            eprintln!("  {}: {}", file, e);
+        }
+    }
+    failures.is_empty()
+}
+
+/// Process the `index`th of the batch's files, returning its JSON report, if
+/// any, and the outcome.
+fn process_one(
+    file: &Path,
+    index: usize,
+    root: &Path,
+    args: &Args,
+    strip_rules: &StripRules,
+) -> (Option<Value>, Result<Processed, String>) {
+    // A dry run writes nothing, so it has no output directories to create
+    let output = if args.dry_run { None } else { args.output.as_deref() };
+    // An --output-template names the file itself; --output only picks its directory
+    let target = match &args.output_template {
+        Some(template) => templated_output(template, file, index, !args.dry_run).map(Some),
+        None => output_dir_for(root, file, output),
+    };
+    let target = match target {
+        Ok(target) => target,
+        Err(e) => return (None, Err(format!("Error creating output directory: {}", e))),
+    };
+    let mut json_report = None;
+    let processed = panic::catch_unwind(AssertUnwindSafe(|| {
+        process_file(&file.to_string_lossy(), target.as_deref(), args, strip_rules, false, &mut json_report)
+    }));
+    let result = match processed {
+        Ok(result) => result.map_err(|e| e.message),
+        // A bug one crafted file trips shouldn't take the rest of the batch down with it
+        Err(payload) => {
+            let message = payload.downcast_ref::<String>().map(String::as_str);
+            let reason = payload.downcast_ref::<&str>().copied().or(message);
+            Err(format!("Internal error: {}", reason.unwrap_or("panicked")))
+        }
+    };
+    (json_report, result)
+}
+
+/// The `OK` line for a file that succeeded, with any hashes, stego verdict
+/// and polyglot findings.
+fn print_processed(file_path: &str, done: &Processed) {
+    match (&done.output_path, done.cropped) {
+        (Some(output_path), Some((width, height))) => println!(
+            "{}    {} -> {} ({}x{} -> {}x{})",
+            style::ok("OK"),
+            file_path,
+            output_path.display(),
+            done.original.0,
+            done.original.1,
+            width,
+            height
+        ),
+        _ => println!("{}    {} ({}x{})", style::ok("OK"), file_path, done.original.0, done.original.1),
+    }
+    if let Some(hash) = &done.pixel_hash {
+        println!("      pixel sha256 {}", hash);
+    }
+    if let Some(hash) = done.perceptual_hash {
+        println!("      dhash {:016x}", hash);
+    }
+    if let Some(scores) = &done.stego {
+        println!("      {}", report::stego_check_line(scores));
+    }
+    for signature in done.polyglot.iter().flatten() {
+        println!("      {}", report::polyglot_line(signature));
+    }
+}
+
+/// Find the PNG files in `dir` (and its subdirectories if `recursive`), sorted.
+///
+/// Previous `-unpeeled` outputs are skipped so re-running over a folder does
+/// not process its own results again.
+pub fn collect_pngs(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if is_png_path(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_png_path(path: &Path) -> bool {
+    let is_png = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    let is_output = path.file_stem().is_some_and(|stem| stem.to_string_lossy().ends_with("-unpeeled"));
+    is_png && !is_output
+}
+
+/// The `--output` directory for `file`, with the file's subdirectory under
+/// `root` recreated inside it.
+fn output_dir_for(root: &Path, file: &Path, output: Option<&Path>) -> io::Result<Option<PathBuf>> {
+    let output = match output {
+        Some(output) => output,
+        None => return Ok(None),
+    };
+    let relative_parent = file.parent().and_then(|parent| parent.strip_prefix(root).ok()).unwrap_or(Path::new(""));
+    let target = output.join(relative_parent);
+    fs::create_dir_all(&target)?;
+    Ok(Some(target))
+}
+
+/// The `--output-template` path for the `index`th `file`, creating its
+/// directory when `create` is set.
+pub fn templated_output(template: &OutputTemplate, file: &Path, index: usize, create: bool) -> io::Result<PathBuf> {
+    let target = template.render(file, index);
+    if let Some(parent) = target.parent().filter(|parent| create && !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(target)
+}
+
+/// Read a `--from-list` file, or stdin for `-`: one path per line, skipping
+/// blank lines and `#` comments.
+pub fn read_list(list: &str) -> Result<Vec<PathBuf>, String> {
+    let text = if list == "-" {
+        io::read_to_string(io::stdin().lock())
+    } else {
+        fs::read_to_string(list)
+    };
+    let text = text.map_err(|e| format!("could not read file list '{}': {}", list, e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Whether `arg` contains glob metacharacters.
+pub fn is_glob_pattern(arg: &str) -> bool {
+    arg.contains(['*', '?', '['])
+}
+
+/// Expand a glob pattern such as `photos/**/*.png` into the matching files, sorted.
+pub fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let paths = glob::glob(pattern).map_err(|e| format!("invalid glob pattern '{}': {}", pattern, e))?;
+    let mut files: Vec<PathBuf> = paths.filter_map(Result::ok).filter(|path| path.is_file()).collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The leading directories of a glob pattern before the first wildcard, used
+/// as the root when mirroring matches into `--output`.
+pub fn glob_root(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| !is_glob_pattern(&component.as_os_str().to_string_lossy()))
+        .collect()
+}
@@ -0,0 +1,428 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::crc::chunk_crc;
+use crate::error::UnpeelError;
+
+/// The 8-byte signature every PNG file starts with.
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Other image formats recognized by their leading bytes, so a mistaken input
+/// gets a clearer error than a bad PNG signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileFormat::Jpeg => "JPEG",
+            FileFormat::Gif => "GIF",
+            FileFormat::Webp => "WebP",
+            FileFormat::Bmp => "BMP",
+        })
+    }
+}
+
+/// Identify a non-PNG image from the start of the file. WebP needs 12 bytes;
+/// the other formats are recognized from the first 8.
+pub fn detect_format(header: &[u8]) -> Option<FileFormat> {
+    if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(FileFormat::Jpeg)
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        Some(FileFormat::Gif)
+    } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+        Some(FileFormat::Webp)
+    } else if header.starts_with(b"BM") {
+        Some(FileFormat::Bmp)
+    } else {
+        None
+    }
+}
+
+/// Check that `header` starts with the PNG signature, naming the format if
+/// it is another kind of image we recognize.
+pub fn check_signature(header: &[u8]) -> Result<(), UnpeelError> {
+    if header.starts_with(&PNG_SIGNATURE) {
+        return Ok(());
+    }
+    Err(UnpeelError::NotPng(detect_format(header)))
+}
+
+/// A single raw chunk as stored in the file: type code, payload, and stored CRC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub type_code: [u8; 4],
+    pub data: Vec<u8>,
+    pub crc: u32,
+    /// Byte offset of the chunk's length field from the start of the file
+    pub offset: usize,
+}
+
+impl Chunk {
+    /// Build a new chunk with a freshly computed CRC.
+    pub fn new(type_code: [u8; 4], data: Vec<u8>) -> Chunk {
+        let crc = chunk_crc(&type_code, &data);
+        Chunk { type_code, data, crc, offset: 0 }
+    }
+
+    /// Chunk type as a printable string (e.g. "IHDR", "tEXt").
+    pub fn type_str(&self) -> String {
+        String::from_utf8_lossy(&self.type_code).into_owned()
+    }
+
+    /// CRC-32 computed over the type code and data.
+    pub fn computed_crc(&self) -> u32 {
+        chunk_crc(&self.type_code, &self.data)
+    }
+
+    /// Whether the stored CRC matches the chunk contents.
+    pub fn crc_is_valid(&self) -> bool {
+        self.crc == self.computed_crc()
+    }
+
+    /// Critical chunks have an uppercase first letter (ancillary bit clear).
+    pub fn is_critical(&self) -> bool {
+        self.type_code[0] & 0x20 == 0
+    }
+
+    /// Safe-to-copy chunks have a lowercase fourth letter.
+    pub fn is_safe_to_copy(&self) -> bool {
+        self.type_code[3] & 0x20 != 0
+    }
+
+    /// Serialize the chunk exactly as stored: length, type, data, and stored CRC.
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        w.write_all(&(self.data.len() as u32).to_be_bytes())?;
+        w.write_all(&self.type_code)?;
+        w.write_all(&self.data)?;
+        w.write_all(&self.crc.to_be_bytes())
+    }
+}
+
+/// Walk every chunk in a PNG stream, starting from the file signature and
+/// stopping after IEND.
+///
+/// A stream that ends cleanly between chunks without an IEND is returned as
+/// is, so the missing IEND can be reported rather than failing the read.
+pub fn parse_chunks(reader: impl Read) -> Result<Vec<Chunk>, UnpeelError> {
+    ChunkReader::new(reader).collect()
+}
+
+/// Recompute every chunk's CRC, returning the corrected file and the chunks
+/// whose stored CRC was wrong. Only the CRC fields change: the signature,
+/// lengths, types, data and anything after IEND are copied byte for byte.
+pub fn repair_crcs(bytes: &[u8]) -> Result<(Vec<u8>, Vec<Chunk>), UnpeelError> {
+    let mut repaired = bytes.to_vec();
+    let mut fixed = Vec::new();
+    for chunk in ChunkReader::new(bytes) {
+        let chunk = chunk?;
+        if !chunk.crc_is_valid() {
+            // The CRC follows the length, type and data fields
+            let at = chunk.offset + 8 + chunk.data.len();
+            repaired[at..at + 4].copy_from_slice(&chunk.computed_crc().to_be_bytes());
+            fixed.push(chunk);
+        }
+    }
+    Ok((repaired, fixed))
+}
+
+/// Streams the chunks of a PNG one at a time, checking the file signature
+/// before the first chunk and stopping after IEND.
+///
+/// Reading ends after the first error, and also if the stream ends cleanly
+/// between chunks, which is how a missing IEND shows up.
+pub struct ChunkReader<R> {
+    reader: R,
+    /// Byte offset of the next chunk, or `None` before the signature is read
+    offset: Option<usize>,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R) -> ChunkReader<R> {
+        ChunkReader { reader, offset: None, done: false }
+    }
+
+    fn read_signature(&mut self) -> Result<(), UnpeelError> {
+        let mut signature = [0u8; 8];
+        self.reader.read_exact(&mut signature)?;
+        if signature != PNG_SIGNATURE {
+            // Read a little further so WebP's RIFF header can be recognized
+            let mut header = signature.to_vec();
+            (&mut self.reader).take(4).read_to_end(&mut header)?;
+            check_signature(&header)?;
+        }
+        Ok(())
+    }
+
+    /// Read the next chunk, or `None` at a clean end of stream.
+    fn read_chunk(&mut self, offset: usize) -> Result<Option<Chunk>, UnpeelError> {
+        // Each chunk: 4-byte length, 4-byte type, data, 4-byte CRC
+        let mut length_bytes = [0u8; 4];
+        if !read_exact_or_eof(&mut self.reader, &mut length_bytes)? {
+            return Ok(None);
+        }
+        let length = u32::from_be_bytes(length_bytes) as usize;
+
+        let mut type_code = [0u8; 4];
+        self.reader.read_exact(&mut type_code)?;
+
+        // Read through `take` so a bogus length can't force a huge allocation up front
+        let mut data = Vec::new();
+        (&mut self.reader).take(length as u64).read_to_end(&mut data)?;
+        if data.len() != length {
+            return Err(UnpeelError::MalformedChunk(format!(
+                "{} chunk declares {} bytes but only {} remain",
+                String::from_utf8_lossy(&type_code),
+                length,
+                data.len()
+            )));
+        }
+
+        let mut crc_bytes = [0u8; 4];
+        self.reader.read_exact(&mut crc_bytes)?;
+        let crc = u32::from_be_bytes(crc_bytes);
+
+        Ok(Some(Chunk { type_code, data, crc, offset }))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk, UnpeelError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => match self.read_signature() {
+                Ok(()) => PNG_SIGNATURE.len(),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            },
+        };
+        match self.read_chunk(offset) {
+            Ok(Some(chunk)) => {
+                // length + type + data + CRC
+                self.offset = Some(offset + 12 + chunk.data.len());
+                self.done = &chunk.type_code == b"IEND";
+                Some(Ok(chunk))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Fill `buf`, returning `false` if the stream was already at EOF.
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+
+    /// Encode a 3x2 RGB image with a tEXt and a pHYs chunk.
+    fn sample_png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 3, 2);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: 2835, yppu: 2835, unit: png::Unit::Meter }));
+        encoder.add_text_chunk("Author".to_string(), "unpeel tests".to_string()).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0x40; 3 * 2 * 3]).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    fn types(chunks: &[Chunk]) -> Vec<String> {
+        chunks.iter().map(Chunk::type_str).collect()
+    }
+
+    #[test]
+    fn parses_every_chunk_in_order() {
+        let bytes = sample_png();
+        let chunks = parse_chunks(bytes.as_slice()).unwrap();
+
+        assert_eq!(types(&chunks).first().map(String::as_str), Some("IHDR"));
+        assert_eq!(types(&chunks).last().map(String::as_str), Some("IEND"));
+        assert!(types(&chunks).contains(&"tEXt".to_string()));
+        assert!(types(&chunks).contains(&"pHYs".to_string()));
+        assert!(chunks.iter().all(Chunk::crc_is_valid));
+    }
+
+    #[test]
+    fn records_offsets_that_round_trip() {
+        let bytes = sample_png();
+        let chunks = parse_chunks(bytes.as_slice()).unwrap();
+
+        assert_eq!(chunks[0].offset, PNG_SIGNATURE.len());
+        let mut rewritten = PNG_SIGNATURE.to_vec();
+        for chunk in &chunks {
+            assert_eq!(chunk.offset, rewritten.len());
+            chunk.write_to(&mut rewritten).unwrap();
+        }
+        assert_eq!(rewritten, bytes);
+    }
+
+    #[test]
+    fn decodes_text_and_physical_dimensions() {
+        let chunks = parse_chunks(sample_png().as_slice()).unwrap();
+        let find = |code: &[u8; 4]| chunks.iter().find(|c| &c.type_code == code).unwrap();
+
+        let text = decode::parse_text(&find(b"tEXt").data).unwrap();
+        assert_eq!(text.keyword, "Author");
+        assert_eq!(text.text, "unpeel tests");
+
+        let phys = decode::parse_phys(&find(b"pHYs").data).unwrap();
+        assert_eq!((phys.x_ppu, phys.y_ppu, phys.unit_name()), (2835, 2835, "meter"));
+    }
+
+    #[test]
+    fn decodes_text_as_latin1_and_itxt_as_utf8() {
+        // 0xE9 is é in Latin-1, but not valid UTF-8 on its own
+        let text = decode::parse_text(b"Caf\xe9\0Caf\xe9 cr\xe8me").unwrap();
+        assert_eq!(text.keyword, "Café");
+        assert_eq!(text.text, "Café crème");
+
+        let itxt = decode::parse_itxt("Title\0\0\0fr\0Café\0Café crème".as_bytes()).unwrap();
+        assert_eq!(itxt.translated_keyword, "Café");
+        assert_eq!(itxt.text, Ok("Café crème".to_string()));
+    }
+
+    #[test]
+    fn invalid_utf8_in_itxt_is_an_error_not_a_panic() {
+        let data = b"Title\0\0\0\0\0bad \xff\xfe text".to_vec();
+        let itxt = decode::parse_itxt(&data).unwrap();
+        assert_eq!(itxt.keyword, "Title");
+        assert_eq!(itxt.text, Err("text is not valid UTF-8".to_string()));
+        assert_eq!(decode::text_entry(b"iTXt", &data), None);
+
+        // The rest of the file still reads
+        let mut chunks = parse_chunks(sample_png().as_slice()).unwrap();
+        chunks.insert(1, Chunk::new(*b"iTXt", data));
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        for chunk in &chunks {
+            chunk.write_to(&mut bytes).unwrap();
+        }
+        let png = crate::Png::from_bytes(&bytes).unwrap();
+        assert_eq!(png.pixels, vec![0x40; 3 * 2 * 3]);
+        assert!(png.chunks.iter().any(|c| &c.type_code == b"iTXt"));
+    }
+
+    #[test]
+    fn decodes_the_image_header_and_pixels() {
+        let png = crate::Png::from_bytes(&sample_png()).unwrap();
+
+        assert_eq!((png.width, png.height), (3, 2));
+        assert_eq!(png.color_type, png::ColorType::Rgb);
+        assert_eq!(png.pixels, vec![0x40; 3 * 2 * 3]);
+    }
+
+    #[test]
+    fn missing_iend_is_not_an_error() {
+        let bytes = sample_png();
+        // IEND is the final 12 bytes: empty payload plus length, type and CRC
+        let chunks = parse_chunks(&bytes[..bytes.len() - 12]).unwrap();
+
+        assert_eq!(types(&chunks).last().map(String::as_str), Some("IDAT"));
+    }
+
+    #[test]
+    fn truncated_chunk_data_is_malformed() {
+        let bytes = sample_png();
+        let idat = parse_chunks(bytes.as_slice()).unwrap().into_iter().find(|c| &c.type_code == b"IDAT").unwrap();
+        // Cut the file two bytes into the IDAT payload
+        let truncated = &bytes[..idat.offset + 8 + 2];
+
+        match parse_chunks(truncated) {
+            Err(UnpeelError::MalformedChunk(msg)) => assert!(msg.starts_with("IDAT chunk declares"), "{}", msg),
+            other => panic!("expected MalformedChunk, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_crc_is_an_io_error() {
+        let bytes = sample_png();
+        let truncated = &bytes[..bytes.len() - 2];
+
+        assert!(matches!(parse_chunks(truncated), Err(UnpeelError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn chunk_reader_yields_chunks_lazily_and_stops_after_iend() {
+        let mut bytes = sample_png();
+        bytes.extend_from_slice(b"trailing bytes are never read");
+        let mut reader = ChunkReader::new(bytes.as_slice());
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(&first.type_code, b"IHDR");
+        assert_eq!(first.offset, PNG_SIGNATURE.len());
+        let rest: Vec<Chunk> = reader.by_ref().map(Result::unwrap).collect();
+        assert_eq!(rest.last().map(Chunk::type_str).as_deref(), Some("IEND"));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn repairing_crcs_changes_only_the_bad_crc() {
+        let mut bytes = sample_png();
+        bytes.extend_from_slice(b"trailer");
+        let text = parse_chunks(bytes.as_slice()).unwrap().into_iter().find(|c| &c.type_code == b"tEXt").unwrap();
+        let crc_at = text.offset + 8 + text.data.len();
+        let mut broken = bytes.clone();
+        broken[crc_at] ^= 0xFF;
+
+        let (repaired, fixed) = repair_crcs(&broken).unwrap();
+        assert_eq!(repaired, bytes);
+        assert_eq!(types(&fixed), vec!["tEXt"]);
+        assert_eq!(repair_crcs(&bytes).unwrap(), (bytes, Vec::new()));
+    }
+
+    #[test]
+    fn names_other_image_formats() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00];
+        let webp = *b"RIFF\x24\0\0\0WEBPVP8 ";
+
+        assert!(matches!(parse_chunks(jpeg.as_slice()), Err(UnpeelError::NotPng(Some(FileFormat::Jpeg)))));
+        assert!(matches!(parse_chunks(webp.as_slice()), Err(UnpeelError::NotPng(Some(FileFormat::Webp)))));
+        assert_eq!(detect_format(b"GIF89a\x01\0"), Some(FileFormat::Gif));
+        assert_eq!(detect_format(b"BM\x36\0\0\0\0\0"), Some(FileFormat::Bmp));
+    }
+
+    #[test]
+    fn rejects_an_unknown_signature() {
+        assert!(matches!(parse_chunks(b"not an image".as_slice()), Err(UnpeelError::NotPng(None))));
+    }
+
+    #[test]
+    fn rejects_input_shorter_than_the_signature() {
+        assert!(matches!(parse_chunks(&PNG_SIGNATURE[..4]), Err(UnpeelError::IoError(_))));
+    }
+}
@@ -0,0 +1,843 @@
+//! Command-line arguments.
+
+use std::path::{Path, PathBuf};
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use log::LevelFilter;
+use png::{BitDepth, ColorType, Compression};
+
+use unpeel::decode::parse_hex_color;
+use unpeel::{
+    parse_chunk_list, CropRect, EncodeOptions, Filter, GammaTarget, MetadataMode, Orientation, OutputFormat,
+    OutputTemplate, ResizeFilter, ResizeTarget, StripRules,
+};
+
+use crate::config::{self, Config, StripSetting};
+
+/// The exit statuses, shown at the end of `--help`.
+const EXIT_STATUS_HELP: &str = "\
+Exit status:
+  0  success
+  1  any other failure, or some files failed in a batch run
+  2  input file not found (also invalid arguments)
+  3  input is not a PNG
+  4  PNG could not be decoded
+  5  a --strict check failed
+  6  output image could not be written";
+
+/// Inspect a PNG's metadata and, when asked, write a re-encoded copy.
+#[derive(Parser, Debug)]
+#[command(version, override_usage = "unpeel [OPTIONS] <PATH>\n       unpeel [OPTIONS] --from-list <LIST>", after_help = EXIT_STATUS_HELP)]
+pub struct Args {
+    /// PNG file to inspect, `-` to read from stdin, a directory, or a glob pattern such as "photos/**/*.png"
+    #[arg(value_name = "PATH")]
+    pub input: Option<String>,
+
+    /// Output file, or a directory to write `<stem>-unpeeled.<ext>` into
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Name each output from a template instead, e.g. `out/{index:04}.png`.
+    /// Placeholders: {stem}, {ext}, {parent} (the input's directory name) and
+    /// {index} (1-based position in the run, zero-padded with {index:04})
+    #[arg(long, value_name = "TEMPLATE", value_parser = OutputTemplate::parse, conflicts_with = "output")]
+    pub output_template: Option<OutputTemplate>,
+
+    /// Overwrite the input file instead of writing a copy
+    #[arg(long, conflicts_with_all = ["output", "output_template"])]
+    pub in_place: bool,
+
+    /// Replace an output file that already exists; without it unpeel stops
+    /// rather than overwrite an earlier run's output
+    #[arg(long, conflicts_with = "in_place")]
+    pub force: bool,
+
+    /// Write a re-encoded copy next to the input. Only needed when no other
+    /// output, strip or conversion option is given; without any, unpeel just inspects
+    #[arg(short, long, conflicts_with = "metadata_only")]
+    pub write: bool,
+
+    /// Print the report as a single JSON object
+    #[arg(long)]
+    pub json: bool,
+
+    /// Also process PNGs in subdirectories when the input is a directory
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Process up to N files at once in batch mode (default: one per CPU)
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: Option<u16>,
+
+    /// Drop all ancillary chunks, or only the listed types (e.g. `--strip tEXt,eXIf`)
+    #[arg(long, value_name = "TYPES", num_args = 0..=1)]
+    pub strip: Option<Option<String>>,
+
+    /// Drop all ancillary chunks except the listed types (e.g. `--keep iCCP,pHYs`)
+    #[arg(long, value_name = "TYPES")]
+    pub keep: Option<String>,
+
+    /// Keep only IHDR, PLTE, IDAT, IEND and tRNS, removing all other metadata,
+    /// and report what was removed
+    #[arg(long, conflicts_with_all = ["strip", "keep"])]
+    pub strip_all_but_critical: bool,
+
+    /// Rewrite the eXIf chunk without its GPS location, keeping camera settings
+    /// and the other tags
+    #[arg(long)]
+    pub remove_gps: bool,
+
+    /// Drop the gAMA, cHRM and sRGB color space chunks, which are otherwise kept
+    #[arg(long)]
+    pub strip_color: bool,
+
+    /// Add a tEXt chunk to the output, replacing any text with the same keyword.
+    /// Repeatable; survives --strip
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_text_entry)]
+    pub set_text: Vec<(String, String)>,
+
+    /// Write a pHYs chunk giving the print resolution in dots per inch, either
+    /// one value for both axes or XxY; replaces any pHYs and survives --strip
+    #[arg(long, value_name = "DPI|XxY", value_parser = parse_dpi)]
+    pub set_phys: Option<(u32, u32)>,
+
+    /// Only print errors, warnings, and the files written
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log to stderr at this level and above: trace, debug, info, warn or
+    /// error (default warn, or RUST_LOG when set)
+    #[arg(long, value_name = "LEVEL", value_parser = parse_log_level)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Composite transparency over a background color (default white) and write RGB
+    #[arg(
+        long,
+        value_name = "background=#RRGGBB",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "#FFFFFF",
+        value_parser = parse_background
+    )]
+    pub to_rgb: Option<[u8; 3]>,
+
+    /// Convert RGB and RGBA images to grayscale (alpha is dropped)
+    #[arg(long)]
+    pub grayscale: bool,
+
+    /// Convert to this color type whatever the input, warning when that loses
+    /// information; indexed builds a palette of up to 256 colors by median cut
+    #[arg(
+        long,
+        value_name = "gray|gray-alpha|rgb|rgba|indexed",
+        value_parser = parse_color,
+        conflicts_with_all = ["to_rgb", "grayscale"]
+    )]
+    pub color: Option<ColorType>,
+
+    /// Reduce the image to an indexed PNG of at most N colors (1-256), built
+    /// by median cut; translucent colors are kept in a tRNS chunk
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..=256),
+        conflicts_with_all = ["color", "to_rgb", "grayscale"]
+    )]
+    pub quantize: Option<u16>,
+
+    /// Dither with Floyd-Steinberg error diffusion when mapping to a palette
+    /// (--quantize or --color indexed), so gradients don't band
+    #[arg(long)]
+    pub dither: bool,
+
+    /// Apply the gAMA chunk's gamma and re-encode for a linear or sRGB target
+    #[arg(long, value_name = "linear|srgb", value_parser = parse_gamma_target)]
+    pub apply_gamma: Option<GammaTarget>,
+
+    /// Crop to a rectangle of pixels instead of the default 88% crop: the
+    /// top-left corner, then width and height
+    #[arg(long, value_name = "x,y,w,h", value_parser = parse_crop)]
+    pub crop: Option<CropRect>,
+
+    /// Rotate the image clockwise; may be repeated and combined with --flip,
+    /// applied in the order given
+    #[arg(long, value_name = "90|180|270", value_parser = parse_rotation)]
+    pub rotate: Vec<Orientation>,
+
+    /// Mirror the image horizontally (h) or vertically (v); may be repeated
+    /// and combined with --rotate, applied in the order given
+    #[arg(long, value_name = "h|v", value_parser = parse_flip)]
+    pub flip: Vec<Orientation>,
+
+    /// Every --rotate and --flip in command-line order
+    #[arg(skip)]
+    pub orientations: Vec<Orientation>,
+
+    /// Resample to WxH pixels; give only one side (`256x` or `x256`) to keep
+    /// the aspect ratio
+    #[arg(long, value_name = "WxH", value_parser = parse_resize, conflicts_with = "scale")]
+    pub resize: Option<ResizeTarget>,
+
+    /// Resample both sides by a factor, e.g. 0.5 for half size
+    #[arg(long, value_name = "FACTOR", value_parser = parse_scale)]
+    pub scale: Option<ResizeTarget>,
+
+    /// Resampling filter for --resize and --scale (default bilinear)
+    #[arg(long, value_name = "nearest|bilinear", value_parser = parse_resize_filter)]
+    pub filter_resize: Option<ResizeFilter>,
+
+    /// Convert samples to 8 or 16 bits per channel
+    #[arg(long, value_name = "8|16", value_parser = parse_depth)]
+    pub depth: Option<BitDepth>,
+
+    /// Print a table of every chunk's offset, length, type and CRC
+    #[arg(long)]
+    pub chunk_map: bool,
+
+    /// Refuse images whose decoded pixels would exceed this many bytes (suffixes K, M, G allowed)
+    #[arg(long, value_name = "SIZE", default_value = "512M", value_parser = parse_size)]
+    pub max_bytes: usize,
+
+    /// Re-encode with better filtering (adaptive unless --filter is given)
+    #[arg(long)]
+    pub recompress: bool,
+
+    /// Row filter for recompression; implies --recompress
+    #[arg(long, value_name = "none|sub|up|avg|paeth|adaptive", value_parser = parse_filter)]
+    pub filter: Option<Filter>,
+
+    /// zlib compression level for recompression; implies --recompress
+    #[arg(long, value_name = "fast|default|best", value_parser = parse_compression)]
+    pub compression: Option<Compression>,
+
+    /// Write byte-identical output for the same pixels and metadata, however
+    /// the input was laid out: noise seeded from the source pixels, chunks in a fixed order (IHDR, color space,
+    /// PLTE, tRNS, other metadata, text by keyword, IDAT, IEND), adaptive
+    /// filtering at default compression unless --filter or --compression say
+    /// otherwise, one IDAT chunk, and no interlacing unless --interlace adam7
+    #[arg(long)]
+    pub canonical: bool,
+
+    /// Interlace the output with Adam7 or write it progressive-free; by
+    /// default the input's interlacing is kept
+    #[arg(long, value_name = "adam7|none", value_parser = parse_interlace)]
+    pub interlace: Option<bool>,
+
+    /// Output image format; WebP needs the `webp` build feature and drops
+    /// all metadata
+    #[arg(long, value_name = "png|webp", value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    /// Encode WebP output lossily at this quality (0-100) instead of losslessly
+    #[arg(long, value_name = "0-100", value_parser = parse_quality)]
+    pub quality: Option<f32>,
+
+    /// Print a SHA-256 of the decoded pixels, for finding duplicate images
+    /// regardless of their metadata or compression
+    #[arg(long, conflicts_with = "metadata_only")]
+    pub hash: bool,
+
+    /// Print a 64-bit perceptual difference hash (dHash) of the decoded pixels.
+    /// The Hamming distance between two hashes estimates visual similarity:
+    /// 0 for resaved copies, more than about 10 for different pictures
+    #[arg(long, conflicts_with = "metadata_only")]
+    pub phash: bool,
+
+    /// Draw a small thumbnail of the image in the terminal: 24-bit color
+    /// half blocks, or an ASCII ramp when color is off (e.g. `NO_COLOR`)
+    #[arg(long, conflicts_with = "metadata_only")]
+    pub preview: bool,
+
+    /// Score each channel's least-significant bit plane for the randomness left
+    /// by hidden data. A heuristic: noisy photos can score high too
+    #[arg(long, conflicts_with = "metadata_only")]
+    pub stego_check: bool,
+
+    /// Scan the whole file, including anything after IEND, for embedded ZIP
+    /// and PDF signatures that would make it a polyglot, and report their offsets
+    #[arg(long)]
+    pub polyglot_check: bool,
+
+    /// Only report on the input; never decode pixels or write an output image,
+    /// even when output options are given. This is the default without --write
+    /// or any output, strip or conversion option
+    #[arg(long)]
+    pub metadata_only: bool,
+
+    /// Change nothing but metadata: write the source pixels untouched (no
+    /// noise or crop), then decode the output and check its pixels still
+    /// match the source. Unlike --metadata-only, an image is written
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "metadata_only", "to_rgb", "grayscale", "color", "quantize", "apply_gamma", "crop", "rotate", "flip",
+            "resize", "scale", "depth", "format"
+        ]
+    )]
+    pub only_metadata: bool,
+
+    /// Decode, convert and report as usual, but never write the output image
+    #[arg(long, visible_alias = "no-write")]
+    pub dry_run: bool,
+
+    /// List every palette entry and APNG frame chunk
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Cut text chunk values longer than this many characters in the report;
+    /// 0 or --verbose shows them in full, as does --dump-text
+    #[arg(long, value_name = "N", default_value_t = 200)]
+    pub max_text_len: usize,
+
+    /// Print how many milliseconds each phase took (read, decode, chunk parse,
+    /// convert, re-encode) to stderr; batch runs print totals and averages
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Exit with an error if any chunk fails CRC validation or breaks chunk ordering rules
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Only check every chunk's CRC and that the IDAT stream fully
+    /// decompresses, printing OK or FAILED: <reason>; nothing is written
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "output", "in_place", "json", "compare", "dry_run", "dump_icc", "dump_text", "dump_trailing",
+            "extract_chunk", "to_ppm", "extract_frame"
+        ]
+    )]
+    pub verify_only: bool,
+
+    /// Print one tab-separated line per file instead of the report:
+    /// path, WxH, color type, bit depth, adam7 or none, and file size in bytes.
+    /// The columns are stable for sort and awk; nothing is written
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "verify_only", "output", "in_place", "json", "compare", "dry_run", "dump_icc", "dump_text",
+            "dump_trailing", "extract_chunk", "to_ppm", "extract_frame"
+        ]
+    )]
+    pub summary_only: bool,
+
+    /// Rewrite the file with every chunk's CRC recomputed, listing the chunks
+    /// repaired. Chunk data is left untouched and pixels are never decoded
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "verify_only", "summary_only", "output_template", "json", "compare", "metadata_only", "only_metadata",
+            "strip", "keep", "strip_all_but_critical", "strip_color", "remove_gps", "set_text", "set_phys",
+            "to_rgb", "grayscale", "color", "quantize", "apply_gamma", "crop", "rotate", "flip", "resize", "scale",
+            "depth", "recompress", "filter", "compression", "canonical", "interlace", "format", "quality"
+        ]
+    )]
+    pub fix_crc: bool,
+
+    /// Write the embedded ICC profile to this path
+    #[arg(long, value_name = "PATH")]
+    pub dump_icc: Option<PathBuf>,
+
+    /// Write any data found after the IEND chunk to this path
+    #[arg(long, value_name = "PATH")]
+    pub dump_trailing: Option<PathBuf>,
+
+    /// Write every tEXt, zTXt and iTXt entry to this path as JSON, grouped by keyword
+    #[arg(long, value_name = "PATH")]
+    pub dump_text: Option<PathBuf>,
+
+    /// Write the raw data of the chunk of this type to PATH, e.g.
+    /// `--extract-chunk iTXt out.bin`; several are written as out-0.bin,
+    /// out-1.bin and so on, in file order
+    #[arg(long, num_args = 2, value_names = ["TYPE", "PATH"])]
+    pub extract_chunk: Option<Vec<String>>,
+
+    /// Compare the input with another PNG and print only what differs:
+    /// header fields, chunks, and whether the decoded pixels match
+    #[arg(long, value_name = "OTHER", conflicts_with_all = ["output", "in_place", "json"])]
+    pub compare: Option<String>,
+
+    /// Write the decoded pixels to this path as binary PPM (P6), or PGM (P5)
+    /// for grayscale, without re-encoding a PNG; alpha is dropped
+    #[arg(long, value_name = "PATH", conflicts_with = "metadata_only")]
+    pub to_ppm: Option<PathBuf>,
+
+    /// Write APNG frame N (from 0), composited as a viewer shows it, to
+    /// `<stem>-frame<N>.png` or --output. Frame 0 of a static PNG is the image
+    #[arg(long, value_name = "N", conflicts_with_all = ["in_place", "metadata_only", "dry_run"])]
+    pub extract_frame: Option<usize>,
+
+    /// Build an APNG at --output from the PNGs in a directory, glob or
+    /// --from-list, in that order. Frames must share their size; each shows
+    /// for --delay, or its line of --delays
+    #[arg(
+        long,
+        requires = "output",
+        conflicts_with_all = [
+            "in_place", "output_template", "json", "compare", "verify_only", "summary_only", "fix_crc",
+            "metadata_only", "only_metadata", "extract_frame", "strip", "keep", "strip_all_but_critical",
+            "to_rgb", "grayscale", "color", "quantize", "apply_gamma", "crop", "rotate", "flip", "resize", "scale",
+            "depth", "format"
+        ]
+    )]
+    pub assemble: bool,
+
+    /// How long each --assemble frame shows: milliseconds, or with an ms or s
+    /// suffix such as 100ms or 0.5s (default 100ms)
+    #[arg(long, value_name = "DURATION", value_parser = parse_delay, requires = "assemble")]
+    pub delay: Option<u16>,
+
+    /// File of per-frame --assemble delays, one DURATION per line in frame
+    /// order; blank lines and lines starting with # are skipped
+    #[arg(long, value_name = "FILE", requires = "assemble", conflicts_with = "delay")]
+    pub delays: Option<PathBuf>,
+
+    /// Process the files listed in this file, one path per line, like a
+    /// batch run; `-` reads the list from stdin. Blank lines and lines
+    /// starting with # are skipped
+    #[arg(long, value_name = "LIST", conflicts_with_all = ["input", "in_place"])]
+    pub from_list: Option<String>,
+
+    /// Ignore unpeel.toml in the current directory and $XDG_CONFIG_HOME/unpeel/
+    #[arg(long)]
+    pub no_config: bool,
+}
+
+impl Args {
+    /// Parse the process arguments, exiting with a usage message on error.
+    pub fn parse_args() -> Args {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        // Interleave --rotate and --flip by position so they compose in the order given
+        let positions = |id: &str| matches.indices_of(id).into_iter().flatten();
+        let mut orientations: Vec<(usize, Orientation)> = positions("rotate").zip(args.rotate.iter().copied()).collect();
+        orientations.extend(positions("flip").zip(args.flip.iter().copied()));
+        orientations.sort_by_key(|&(position, _)| position);
+        args.orientations = orientations.into_iter().map(|(_, orientation)| orientation).collect();
+
+        // `--strip <path>` greedily takes the path as its list; hand it back
+        // when it doesn't look like chunk types
+        if args.input.is_none() {
+            if let Some(Some(value)) = &args.strip {
+                if !looks_like_chunk_list(value) {
+                    args.input = args.strip.take().flatten();
+                    args.strip = Some(None);
+                }
+            }
+        }
+        if args.input.is_none() && args.from_list.is_none() {
+            Args::command()
+                .error(ErrorKind::MissingRequiredArgument, "the input PATH or --from-list is required")
+                .exit();
+        }
+        // The config file only holds output settings, so runs that write nothing skip it
+        if !args.no_config && args.writes_output() {
+            if let Err(e) = config::load().and_then(|config| args.apply_config(config.unwrap_or_default())) {
+                Args::command().error(ErrorKind::InvalidValue, e).exit();
+            }
+        }
+        if let Some([type_code, _]) = args.extract_chunk.as_deref() {
+            if let Err(e) = parse_chunk_type(type_code) {
+                Args::command().error(ErrorKind::InvalidValue, format!("--extract-chunk: {}", e)).exit();
+            }
+        }
+        if args.dither && args.quantize.is_none() && args.color != Some(ColorType::Indexed) {
+            Args::command()
+                .error(ErrorKind::MissingRequiredArgument, "--dither needs --quantize or --color indexed")
+                .exit();
+        }
+        if args.filter_resize.is_some() && args.resize_target().is_none() {
+            Args::command()
+                .error(ErrorKind::MissingRequiredArgument, "--filter-resize needs --resize or --scale")
+                .exit();
+        }
+        if args.output_format() == OutputFormat::Png {
+            if args.quality.is_some() {
+                Args::command().error(ErrorKind::ArgumentConflict, "--quality only applies to --format webp").exit();
+            }
+        } else if args.in_place {
+            Args::command()
+                .error(ErrorKind::ArgumentConflict, "--in-place can only write PNG; drop --format or --in-place")
+                .exit();
+        }
+        args
+    }
+
+    /// Fill in the settings the command line left unset from `config`.
+    fn apply_config(&mut self, config: Config) -> Result<(), String> {
+        if let (None, false, Some(output)) = (&self.output, self.in_place, config.output) {
+            if !output.is_dir() {
+                return Err(format!("the config output {} is not an existing directory", output.display()));
+            }
+            self.output = Some(output);
+        }
+        // --strip and --keep make up one rule set, so either replaces the config's
+        if self.strip.is_none() && self.keep.is_none() && !self.strip_all_but_critical {
+            self.strip = match config.strip {
+                Some(StripSetting::All(true)) => Some(None),
+                Some(StripSetting::List(list)) => Some(Some(list)),
+                Some(StripSetting::All(false)) | None => None,
+            };
+            self.keep = config.keep;
+        }
+        self.strip_color |= config.strip_color;
+        self.recompress |= config.recompress;
+        if self.filter.is_none() {
+            let filter = config.filter.as_deref().map(parse_filter).transpose();
+            self.filter = filter.map_err(|e| format!("config filter: {}", e))?;
+        }
+        if self.compression.is_none() {
+            let compression = config.compression.as_deref().map(parse_compression).transpose();
+            self.compression = compression.map_err(|e| format!("config compression: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// The `--max-text-len` cap on printed text values, if any.
+    pub fn text_limit(&self) -> Option<usize> {
+        Some(self.max_text_len).filter(|&limit| limit > 0 && !self.verbose)
+    }
+
+    /// The input path; always present after `parse_args` unless `--from-list`
+    /// was given.
+    pub fn input(&self) -> &str {
+        self.input.as_deref().unwrap_or_default()
+    }
+
+    /// The output size asked for with `--resize` or `--scale`, if either.
+    pub fn resize_target(&self) -> Option<ResizeTarget> {
+        self.resize.or(self.scale)
+    }
+
+    /// The `--format` to write, PNG unless given.
+    pub fn output_format(&self) -> OutputFormat {
+        self.format.unwrap_or_default()
+    }
+
+    /// Whether pixels need decoding: an output image is written or `--hash`,
+    /// `--phash`, `--stego-check`, `--preview` or `--to-ppm` was given.
+    pub fn needs_pixels(&self) -> bool {
+        self.hash || self.phash || self.stego_check || self.preview || self.to_ppm.is_some() || self.writes_output()
+    }
+
+    /// The chunk type and target path of `--extract-chunk`, checked by `parse_args`.
+    pub fn extract_chunk(&self) -> Option<([u8; 4], &Path)> {
+        let [type_code, path] = self.extract_chunk.as_deref()? else {
+            return None;
+        };
+        Some((parse_chunk_type(type_code).ok()?, Path::new(path)))
+    }
+
+    /// Whether to write an output image: `--write`, `--dry-run` or any option
+    /// that affects the output was given. Otherwise the input is only inspected.
+    pub fn writes_output(&self) -> bool {
+        // --extract-frame writes the frame in place of the usual output
+        if self.metadata_only || self.extract_frame.is_some() {
+            return false;
+        }
+        self.write
+            || self.dry_run
+            || self.output.is_some()
+            || self.output_template.is_some()
+            || self.in_place
+            || self.strip.is_some()
+            || self.keep.is_some()
+            || self.strip_all_but_critical
+            || self.strip_color
+            || self.remove_gps
+            || !self.set_text.is_empty()
+            || self.set_phys.is_some()
+            || self.to_rgb.is_some()
+            || self.grayscale
+            || self.color.is_some()
+            || self.quantize.is_some()
+            || self.only_metadata
+            || self.crop.is_some()
+            || self.resize_target().is_some()
+            || !self.orientations.is_empty()
+            || self.depth.is_some()
+            || self.apply_gamma.is_some()
+            || self.recompress
+            || self.filter.is_some()
+            || self.compression.is_some()
+            || self.canonical
+            || self.interlace.is_some()
+            || self.format.is_some()
+            || self.quality.is_some()
+    }
+
+    /// Encoder settings from `--recompress`, `--filter`, `--compression`,
+    /// `--canonical`, `--interlace` and `--set-text`. The output keeps the
+    /// source's interlacing unless `--interlace` or `--canonical` says otherwise.
+    pub fn encode_options(&self, source_interlaced: bool) -> EncodeOptions {
+        let interlaced = self.interlace.unwrap_or(source_interlaced && !self.canonical);
+        let text = self.set_text.clone();
+        let canonical = self.canonical;
+        // --in-place writes a temporary file, which a crashed run may have left behind
+        let overwrite = self.force || self.in_place;
+        if !(self.recompress || self.filter.is_some() || self.compression.is_some() || canonical) {
+            return EncodeOptions { interlaced, text, overwrite, ..EncodeOptions::default() };
+        }
+        let filter = Some(self.filter.unwrap_or(Filter::Adaptive));
+        let compression = self.compression;
+        EncodeOptions { filter, compression, interlaced, text, single_idat: true, canonical, overwrite }
+    }
+
+    /// Build the chunk selection rules from `--strip` and `--keep`.
+    pub fn strip_rules(&self) -> Result<StripRules, String> {
+        let mut rules = StripRules::new(MetadataMode::Preserve);
+        match &self.strip {
+            Some(Some(list)) => rules.strip = parse_chunk_list(list)?,
+            Some(None) => rules.mode = MetadataMode::Strip,
+            None => {}
+        }
+        // A keep list turns on stripping for everything not listed
+        if let Some(list) = &self.keep {
+            rules.mode = MetadataMode::Strip;
+            rules.keep = parse_chunk_list(list)?;
+        }
+        if self.strip_all_but_critical {
+            // tRNS is kept since dropping it would change how the pixels look
+            rules.mode = MetadataMode::Strip;
+            rules.keep = vec![*b"tRNS"];
+        }
+        if self.strip_color {
+            rules.strip.extend([*b"gAMA", *b"cHRM", *b"sRGB"]);
+        }
+        Ok(rules)
+    }
+}
+
+/// Parse the `--to-rgb` background, accepting `#RRGGBB` with or without a
+/// `background=` prefix.
+fn parse_background(value: &str) -> Result<[u8; 3], String> {
+    let color = value.strip_prefix("background=").unwrap_or(value);
+    parse_hex_color(color).ok_or_else(|| format!("expected #RRGGBB, got '{}'", color))
+}
+
+fn parse_gamma_target(value: &str) -> Result<GammaTarget, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "linear" => Ok(GammaTarget::Linear),
+        "srgb" => Ok(GammaTarget::Srgb),
+        _ => Err(format!("expected linear or srgb, got '{}'", value)),
+    }
+}
+
+/// Parse a byte count with an optional binary `K`, `M` or `G` suffix, e.g. `512M`.
+fn parse_size(value: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match value.as_bytes().last().map(|b| b.to_ascii_uppercase()) {
+        Some(b'K') => (&value[..value.len() - 1], 1 << 10),
+        Some(b'M') => (&value[..value.len() - 1], 1 << 20),
+        Some(b'G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("expected a byte count such as 536870912 or 512M, got '{}'", value))
+}
+
+fn parse_filter(value: &str) -> Result<Filter, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "none" => Ok(Filter::None),
+        "sub" => Ok(Filter::Sub),
+        "up" => Ok(Filter::Up),
+        "avg" => Ok(Filter::Avg),
+        "paeth" => Ok(Filter::Paeth),
+        "adaptive" => Ok(Filter::Adaptive),
+        _ => Err(format!("expected none, sub, up, avg, paeth or adaptive, got '{}'", value)),
+    }
+}
+
+fn parse_compression(value: &str) -> Result<Compression, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "fast" => Ok(Compression::Fast),
+        "default" => Ok(Compression::Default),
+        "best" => Ok(Compression::Best),
+        _ => Err(format!("expected fast, default or best, got '{}'", value)),
+    }
+}
+
+/// Parse a single chunk type code such as `iTXt`.
+fn parse_chunk_type(value: &str) -> Result<[u8; 4], String> {
+    match parse_chunk_list(value)?.as_slice() {
+        [type_code] => Ok(*type_code),
+        _ => Err(format!("expected one chunk type, got '{}'", value)),
+    }
+}
+
+fn parse_log_level(value: &str) -> Result<LevelFilter, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "trace" => Ok(LevelFilter::Trace),
+        "debug" => Ok(LevelFilter::Debug),
+        "info" => Ok(LevelFilter::Info),
+        "warn" => Ok(LevelFilter::Warn),
+        "error" => Ok(LevelFilter::Error),
+        _ => Err(format!("expected trace, debug, info, warn or error, got '{}'", value)),
+    }
+}
+
+fn parse_format(value: &str) -> Result<OutputFormat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "png" => Ok(OutputFormat::Png),
+        #[cfg(feature = "webp")]
+        "webp" => Ok(OutputFormat::Webp),
+        #[cfg(not(feature = "webp"))]
+        "webp" => Err("WebP output needs unpeel built with the `webp` feature".to_string()),
+        _ => Err(format!("expected png or webp, got '{}'", value)),
+    }
+}
+
+fn parse_quality(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(quality) if (0.0..=100.0).contains(&quality) => Ok(quality),
+        _ => Err(format!("expected a quality from 0 to 100, got '{}'", value)),
+    }
+}
+
+/// Parse `--crop x,y,w,h`; the rectangle is checked against the image size
+/// once it has been read.
+fn parse_crop(value: &str) -> Result<CropRect, String> {
+    let expected = || format!("expected x,y,width,height such as 0,0,640,480, got '{}'", value);
+    let numbers: Vec<u32> = value.split(',').map(|n| n.trim().parse()).collect::<Result<_, _>>().map_err(|_| expected())?;
+    let [x, y, width, height] = numbers[..] else {
+        return Err(expected());
+    };
+    if width == 0 || height == 0 {
+        return Err(format!("the crop width and height must be at least 1, got '{}'", value));
+    }
+    Ok(CropRect { x, y, width, height })
+}
+
+/// Parse `--resize WxH`, where either side may be left out.
+fn parse_resize(value: &str) -> Result<ResizeTarget, String> {
+    let expected = || format!("expected WxH, Wx or xH such as 640x480 or 256x, got '{}'", value);
+    let lower = value.to_ascii_lowercase();
+    let (width, height) = lower.split_once('x').ok_or_else(expected)?;
+    let side = |side: &str| match side {
+        "" => Ok(None),
+        digits => digits.parse::<u32>().ok().filter(|&n| n > 0).map(Some).ok_or_else(expected),
+    };
+    match (side(width)?, side(height)?) {
+        (None, None) => Err(expected()),
+        (width, height) => Ok(ResizeTarget::Size { width, height }),
+    }
+}
+
+fn parse_scale(value: &str) -> Result<ResizeTarget, String> {
+    match value.parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(ResizeTarget::Scale(factor)),
+        _ => Err(format!("expected a positive factor such as 0.5, got '{}'", value)),
+    }
+}
+
+fn parse_resize_filter(value: &str) -> Result<ResizeFilter, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "nearest" => Ok(ResizeFilter::Nearest),
+        "bilinear" => Ok(ResizeFilter::Bilinear),
+        _ => Err(format!("expected nearest or bilinear, got '{}'", value)),
+    }
+}
+
+fn parse_rotation(value: &str) -> Result<Orientation, String> {
+    match value {
+        "90" => Ok(Orientation::Rotate90),
+        "180" => Ok(Orientation::Rotate180),
+        "270" => Ok(Orientation::Rotate270),
+        _ => Err(format!("expected 90, 180 or 270, got '{}'", value)),
+    }
+}
+
+fn parse_flip(value: &str) -> Result<Orientation, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "h" | "horizontal" => Ok(Orientation::FlipHorizontal),
+        "v" | "vertical" => Ok(Orientation::FlipVertical),
+        _ => Err(format!("expected h or v, got '{}'", value)),
+    }
+}
+
+/// Parse a frame delay into milliseconds: `100`, `100ms` or `0.5s`. APNG
+/// stores delays as fractions of a second, here thousandths, so the longest
+/// is 65.535 s.
+pub fn parse_delay(value: &str) -> Result<u16, String> {
+    let expected = || format!("expected a delay such as 100, 100ms or 0.5s, up to 65535ms, got '{}'", value);
+    let value = value.trim().to_ascii_lowercase();
+    let (number, scale) = match value.strip_suffix("ms") {
+        Some(number) => (number, 1.0),
+        None => value.strip_suffix('s').map_or((value.as_str(), 1.0), |number| (number, 1000.0)),
+    };
+    let millis = number.trim().parse::<f64>().map_err(|_| expected())? * scale;
+    if !(0.0..=u16::MAX as f64).contains(&millis) {
+        return Err(expected());
+    }
+    Ok(millis.round() as u16)
+}
+
+/// Parse `--interlace` into whether the output is Adam7-interlaced.
+fn parse_interlace(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "adam7" => Ok(true),
+        "none" => Ok(false),
+        _ => Err(format!("expected adam7 or none, got '{}'", value)),
+    }
+}
+
+/// Parse `--set-phys`: `300` for the same resolution on both axes, or
+/// `300x150` for horizontal and vertical.
+fn parse_dpi(value: &str) -> Result<(u32, u32), String> {
+    let expected = || format!("expected a DPI from 1 to 1000000 such as 300, or XxY such as 300x150, got '{}'", value);
+    let dpi = |n: &str| n.trim().parse::<u32>().ok().filter(|n| (1..=1_000_000).contains(n)).ok_or_else(expected);
+    match value.to_ascii_lowercase().split_once('x') {
+        Some((x, y)) => Ok((dpi(x)?, dpi(y)?)),
+        None => dpi(value).map(|d| (d, d)),
+    }
+}
+
+/// Parse a `--set-text` entry, checking the keyword against the PNG spec:
+/// 1-79 printable Latin-1 characters with no leading, trailing or repeated
+/// spaces. The text must be Latin-1 too.
+fn parse_text_entry(value: &str) -> Result<(String, String), String> {
+    let (keyword, text) = value.split_once('=').ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))?;
+    let printable_latin1 = |c: char| matches!(c as u32, 0x20..=0x7E | 0xA1..=0xFF);
+    if keyword.is_empty() || keyword.chars().count() > 79 {
+        return Err(format!("keyword '{}' must be 1 to 79 characters long", keyword));
+    }
+    if !keyword.chars().all(printable_latin1) {
+        return Err(format!("keyword '{}' may only contain printable Latin-1 characters", keyword));
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(format!("keyword '{}' may not have leading, trailing or consecutive spaces", keyword));
+    }
+    if !text.chars().all(|c| matches!(c as u32, 0x0A | 0x20..=0x7E | 0xA0..=0xFF)) {
+        return Err(format!("text for '{}' may only contain Latin-1 characters and newlines", keyword));
+    }
+    Ok((keyword.to_string(), text.to_string()))
+}
+
+fn parse_color(value: &str) -> Result<ColorType, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "gray" => Ok(ColorType::Grayscale),
+        "gray-alpha" => Ok(ColorType::GrayscaleAlpha),
+        "rgb" => Ok(ColorType::Rgb),
+        "rgba" => Ok(ColorType::Rgba),
+        "indexed" => Ok(ColorType::Indexed),
+        _ => Err(format!("expected gray, gray-alpha, rgb, rgba or indexed, got '{}'", value)),
+    }
+}
+
+fn parse_depth(value: &str) -> Result<BitDepth, String> {
+    match value {
+        "8" => Ok(BitDepth::Eight),
+        "16" => Ok(BitDepth::Sixteen),
+        _ => Err(format!("expected 8 or 16, got '{}'", value)),
+    }
+}
+
+/// Whether the value after `--strip` is a chunk list rather than the input path:
+/// it contains a comma, or is a single bare four-letter code.
+fn looks_like_chunk_list(arg: &str) -> bool {
+    arg.contains(',') || (arg.len() == 4 && arg.bytes().all(|b| b.is_ascii_alphabetic()))
+}
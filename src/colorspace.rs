@@ -0,0 +1,148 @@
+//! A single best guess at the image's color space from its cICP, iCCP, sRGB,
+//! gAMA and cHRM chunks.
+
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::decode;
+
+/// The color space a PNG declares and the chunk(s) that declare it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclaredColorSpace {
+    /// e.g. "sRGB", "Display P3", "Linear" or "gamma 2.2"
+    pub label: String,
+    /// The chunk type(s) the label was taken from, e.g. "sRGB chunk" or "gAMA"
+    pub source: &'static str,
+}
+
+impl fmt::Display for DeclaredColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (from {})", self.label, self.source)
+    }
+}
+
+/// Work out the color space from the color chunks present, following the PNG
+/// precedence rules: cICP overrides iCCP, which overrides sRGB, which
+/// overrides gAMA and cHRM. Returns `None` when there are no usable color
+/// chunks, in which case viewers assume sRGB. Malformed chunks are skipped
+/// in favor of the next one down.
+pub fn declared_color_space(chunks: &[Chunk]) -> Option<DeclaredColorSpace> {
+    let find = |code: &[u8; 4]| chunks.iter().find(|c| &c.type_code == code).map(|c| c.data.as_slice());
+
+    if let Some(label) = find(b"cICP").and_then(cicp_label) {
+        return Some(DeclaredColorSpace { label, source: "cICP" });
+    }
+    if let Some(label) = find(b"iCCP").and_then(icc_label) {
+        return Some(DeclaredColorSpace { label, source: "iCCP" });
+    }
+    if find(b"sRGB").and_then(decode::parse_srgb).is_some() {
+        return Some(DeclaredColorSpace { label: "sRGB".to_string(), source: "sRGB chunk" });
+    }
+
+    let gamma = find(b"gAMA").and_then(decode::parse_gamma).filter(|&gamma| gamma > 0.0);
+    let primaries = find(b"cHRM").and_then(chrm_primaries);
+    let curve = gamma.map(|gamma| {
+        if (gamma - 1.0).abs() < 0.01 {
+            "Linear".to_string()
+        } else {
+            // gAMA stores the encoding exponent; the display gamma is its inverse
+            format!("gamma {:.1}", 1.0 / gamma)
+        }
+    });
+    match (curve, primaries) {
+        (Some(curve), Some(primaries)) => {
+            Some(DeclaredColorSpace { label: format!("{}, {} primaries", curve, primaries), source: "gAMA and cHRM" })
+        }
+        (Some(curve), None) => Some(DeclaredColorSpace { label: curve, source: "gAMA" }),
+        (None, Some(primaries)) => {
+            Some(DeclaredColorSpace { label: format!("{} primaries, gamma unknown", primaries), source: "cHRM" })
+        }
+        (None, None) => None,
+    }
+}
+
+/// Name the common combinations of cICP color primaries and transfer
+/// function (ITU-T H.273 code points), or list the codes otherwise.
+fn cicp_label(data: &[u8]) -> Option<String> {
+    let [primaries, transfer, ..] = *data else {
+        return None;
+    };
+    let primaries_name = match primaries {
+        1 => Some("sRGB"),
+        9 => Some("BT.2020"),
+        11 => Some("DCI-P3"),
+        12 => Some("Display P3"),
+        _ => None,
+    };
+    let label = match (primaries_name, transfer) {
+        (Some("BT.2020"), 16) => "BT.2100 PQ".to_string(),
+        (Some("BT.2020"), 18) => "BT.2100 HLG".to_string(),
+        (Some(name), 13) => name.to_string(),
+        (Some(name), 8) => format!("Linear {}", name),
+        (Some(name), 1 | 6 | 14 | 15) => format!("{} with the BT.709 curve", name),
+        _ => format!("primaries {}, transfer {}", primaries, transfer),
+    };
+    Some(label)
+}
+
+/// Name the profile in an iCCP chunk from its description, falling back to
+/// the profile name stored in the chunk.
+fn icc_label(data: &[u8]) -> Option<String> {
+    let name = decode::parse_iccp(data)?.name;
+    let description = decode::extract_icc_profile(data).ok().and_then(|profile| icc_description(&profile));
+    let text = description.unwrap_or(name);
+    let lower = text.to_lowercase();
+    let known = [
+        ("display p3", "Display P3"),
+        ("srgb", "sRGB"),
+        ("adobe rgb", "Adobe RGB (1998)"),
+        ("prophoto", "ProPhoto RGB"),
+        ("2020", "BT.2020"),
+    ];
+    Some(match known.iter().find(|(needle, _)| lower.contains(needle)) {
+        Some((_, label)) => label.to_string(),
+        None => format!("ICC profile '{}'", text),
+    })
+}
+
+/// The text of an ICC profile's `desc` tag, in either the v2 `desc` (ASCII)
+/// or the v4 `mluc` (UTF-16, first record) form.
+fn icc_description(profile: &[u8]) -> Option<String> {
+    let count = decode::be_u32(profile, 128)? as usize;
+    let tag = (0..count.min(1024)).map(|i| 132 + i * 12).find(|&entry| profile.get(entry..entry + 4) == Some(b"desc"))?;
+    let offset = decode::be_u32(profile, tag + 4)? as usize;
+    let size = decode::be_u32(profile, tag + 8)? as usize;
+    let data = profile.get(offset..offset.checked_add(size)?)?;
+    let text = match data.get(0..4)? {
+        b"desc" => {
+            let length = decode::be_u32(data, 8)? as usize;
+            let ascii = data.get(12..12 + length)?;
+            String::from_utf8_lossy(ascii.split(|&b| b == 0).next()?).into_owned()
+        }
+        b"mluc" => {
+            let length = decode::be_u32(data, 20)? as usize;
+            let start = decode::be_u32(data, 24)? as usize;
+            let units: Vec<u16> = data.get(start..start + length)?.chunks_exact(2).map(|p| u16::from_be_bytes([p[0], p[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// Name the primaries in a cHRM chunk when they match a standard set.
+fn chrm_primaries(data: &[u8]) -> Option<&'static str> {
+    if data.len() != 32 {
+        return None;
+    }
+    let values: Vec<f64> = (0..8).map(|i| decode::be_u32(data, i * 4).map(|v| v as f64 / 100000.0)).collect::<Option<_>>()?;
+    // White point then red, green, blue as (x, y)
+    let known: [(&str, [f64; 8]); 4] = [
+        ("sRGB", [0.3127, 0.3290, 0.64, 0.33, 0.30, 0.60, 0.15, 0.06]),
+        ("Display P3", [0.3127, 0.3290, 0.680, 0.320, 0.265, 0.690, 0.150, 0.060]),
+        ("Adobe RGB", [0.3127, 0.3290, 0.64, 0.33, 0.21, 0.71, 0.15, 0.06]),
+        ("BT.2020", [0.3127, 0.3290, 0.708, 0.292, 0.170, 0.797, 0.131, 0.046]),
+    ];
+    let matching = known.iter().find(|(_, expected)| expected.iter().zip(&values).all(|(e, v)| (e - v).abs() < 0.005));
+    Some(matching.map_or("custom", |(name, _)| name))
+}
@@ -0,0 +1,102 @@
+//! `--compare`: report how two PNGs differ in header, chunks and pixels.
+
+use std::path::Path;
+
+use unpeel::decode;
+use unpeel::{Chunk, Png};
+
+use crate::cli::Args;
+use crate::{style, Failure};
+
+/// Decode both files and print only what differs between them: header
+/// fields, chunk types present in just one, the values of chunks present in
+/// both, and whether the decoded pixels match.
+pub fn run(first: &str, second: &str, args: &Args) -> Result<(), Failure> {
+    let read = |file: &str| {
+        Png::from_path_with_limit(Path::new(file), args.max_bytes)
+            .map_err(|e| Failure::reading(format!("Error reading {}: {}", file, e), &e))
+    };
+    let (a, b) = (read(first)?, read(second)?);
+
+    println!("{}", style::header(&format!("Comparing {} and {}", first, second)));
+    let mut differences = 0;
+    let mut differ = |label: &str, a: String, b: String| {
+        if a != b {
+            println!("{} {} vs {}", style::key(label), a, b);
+            differences += 1;
+        }
+    };
+    differ("Dimensions", format!("{}x{}", a.width, a.height), format!("{}x{}", b.width, b.height));
+    differ("Color type", format!("{:?}", a.color_type), format!("{:?}", b.color_type));
+    differ("Bit depth", format!("{:?}", a.bit_depth), format!("{:?}", b.bit_depth));
+    differ("Interlaced", a.interlaced.to_string(), b.interlaced.to_string());
+
+    let (a_types, b_types) = (chunk_types(&a), chunk_types(&b));
+    for (file, types, other) in [(first, &a_types, &b_types), (second, &b_types, &a_types)] {
+        let only: Vec<&str> = types.iter().filter(|t| !other.contains(t)).map(String::as_str).collect();
+        if !only.is_empty() {
+            println!("{} {}", style::key(&format!("Only in {}", file)), only.join(", "));
+            differences += 1;
+        }
+    }
+    for chunk_type in a_types.iter().filter(|t| b_types.contains(t)) {
+        let (a_values, b_values) = (chunk_values(&a, chunk_type), chunk_values(&b, chunk_type));
+        if a_values == b_values {
+            continue;
+        }
+        differences += 1;
+        let (a_text, b_text) = (describe_all(&a, chunk_type), describe_all(&b, chunk_type));
+        if a_text != b_text {
+            println!("{} {} vs {}", style::key(chunk_type), a_text, b_text);
+        } else {
+            println!("{} contents differ", style::key(chunk_type));
+        }
+    }
+
+    // Hashing covers the dimensions and format as well, so a converted copy never matches
+    let identical = a.pixel_sha256() == b.pixel_sha256();
+    if differences == 0 {
+        println!("{} identical", style::key("Header and chunks"));
+    }
+    println!("{} {}", style::key("Pixel data"), if identical { "identical" } else { "differs" });
+    Ok(())
+}
+
+/// The distinct chunk types besides IHDR, IDAT and IEND, in file order.
+/// Those three are covered by the header and pixel comparisons.
+fn chunk_types(png: &Png) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    for chunk in png.chunks.iter().filter(|c| !matches!(&c.type_code, b"IHDR" | b"IDAT" | b"IEND")) {
+        if !types.contains(&chunk.type_str()) {
+            types.push(chunk.type_str());
+        }
+    }
+    types
+}
+
+fn chunks_of<'a>(png: &'a Png, chunk_type: &'a str) -> impl Iterator<Item = &'a Chunk> {
+    png.chunks.iter().filter(move |c| c.type_str() == chunk_type)
+}
+
+fn chunk_values<'a>(png: &'a Png, chunk_type: &'a str) -> Vec<&'a [u8]> {
+    chunks_of(png, chunk_type).map(|c| c.data.as_slice()).collect()
+}
+
+/// Every chunk of `chunk_type` in `png`, decoded for the types where a value
+/// reads better than "contents differ".
+fn describe_all(png: &Png, chunk_type: &str) -> String {
+    let values: Vec<String> = chunks_of(png, chunk_type).map(|chunk| describe(chunk).unwrap_or_default()).collect();
+    values.join("; ")
+}
+
+fn describe(chunk: &Chunk) -> Option<String> {
+    match &chunk.type_code {
+        b"gAMA" => decode::parse_gamma(&chunk.data).map(|gamma| format!("{:.5}", gamma)),
+        b"pHYs" => decode::parse_phys(&chunk.data)
+            .map(|phys| format!("{}x{} pixels per {}", phys.x_ppu, phys.y_ppu, phys.unit_name())),
+        b"sRGB" => decode::parse_srgb(&chunk.data).map(str::to_string),
+        b"tIME" => decode::parse_time(&chunk.data).map(|time| time.to_string()),
+        b"tEXt" => decode::parse_text(&chunk.data).map(|text| format!("{}={}", text.keyword, text.text)),
+        _ => None,
+    }
+}
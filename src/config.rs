@@ -0,0 +1,71 @@
+//! Default flags from an optional `unpeel.toml`.
+//!
+//! The first file found is used: `./unpeel.toml`, then
+//! `$XDG_CONFIG_HOME/unpeel/unpeel.toml` (`~/.config` when XDG_CONFIG_HOME is
+//! unset). Flags on the command line take precedence over the file, and
+//! `--no-config` ignores it.
+//!
+//! ```toml
+//! output = "cleaned"          # directory to write into
+//! strip = "tEXt,eXIf"         # or `true` to strip all ancillary chunks
+//! keep = "iCCP"
+//! strip-color = true
+//! recompress = true
+//! filter = "paeth"
+//! compression = "best"
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+const FILE_NAME: &str = "unpeel.toml";
+
+/// The settings a config file can provide, all optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Directory to write output images into, like `--output`
+    pub output: Option<PathBuf>,
+    pub strip: Option<StripSetting>,
+    pub keep: Option<String>,
+    pub strip_color: bool,
+    pub recompress: bool,
+    pub filter: Option<String>,
+    pub compression: Option<String>,
+}
+
+/// `strip = true` strips every ancillary chunk, like a bare `--strip`;
+/// `strip = "tEXt,eXIf"` strips only the listed types.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StripSetting {
+    All(bool),
+    List(String),
+}
+
+/// Where to look for the config file, in order.
+fn candidates() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(FILE_NAME)];
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("unpeel").join(FILE_NAME));
+    }
+    paths
+}
+
+/// Load the first config file found, or `None` if there is none. Errors name
+/// the file that failed to read or parse.
+pub fn load() -> Result<Option<Config>, String> {
+    let Some(path) = candidates().into_iter().find(|path| path.is_file()) else {
+        return Ok(None);
+    };
+    let text = fs::read_to_string(&path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let config = toml::from_str(&text).map_err(|e| format!("invalid {}: {}", path.display(), e))?;
+    Ok(Some(config))
+}
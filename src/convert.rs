@@ -0,0 +1,282 @@
+//! The pixel pipeline between decoding and writing: noise, crop, resize and the
+//! optional format conversions, plus the chunk fixups they require.
+
+use log::warn;
+use png::{BitDepth, ColorType};
+use sha2::{Digest, Sha256};
+
+use unpeel::{decode, exif};
+use unpeel::{
+    add_randomized_noise, add_seeded_noise, apply_gamma, apply_gamma_to_palette, convert_bit_depth, convert_color_type, crop_image,
+    crop_rect, drop_format_dependent, flatten_alpha, quantize, reorient, repack_samples, resize, select_chunks,
+    to_grayscale, unpack_samples, Chunk, GammaTarget, Png, ResizeFilter, StripRules,
+};
+
+use crate::cli::Args;
+
+/// The image as it will be written.
+pub struct Converted {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: BitDepth,
+    pub pixels: Vec<u8>,
+    /// The gAMA value and target, if `--apply-gamma` changed the colors
+    pub applied_gamma: Option<(f64, GammaTarget)>,
+    /// PLTE and tRNS data built by `--color indexed` or `--quantize`,
+    /// replacing the source's
+    pub palette: Option<(Vec<u8>, Option<Vec<u8>>)>,
+    /// How many distinct colors the palette was built from
+    pub source_colors: Option<usize>,
+}
+
+impl Converted {
+    /// Whether the color type or bit depth differs from the source.
+    pub fn format_changed(&self, png: &Png) -> bool {
+        (self.color_type, self.bit_depth) != (png.color_type, png.bit_depth)
+    }
+}
+
+/// Add noise, crop, resize, and apply the conversions requested in `args`.
+/// `--only-metadata` skips the noise and crop, leaving the pixels as decoded.
+pub fn convert_pixels(png: &mut Png, args: &Args) -> Result<Converted, String> {
+    let bytes_per_pixel = png.bytes_per_pixel();
+
+    // Add randomized noise to each pixel, unless only the metadata should change.
+    // --canonical seeds it from the source pixels so the same image always gets the same noise
+    if args.canonical && !args.only_metadata {
+        let seed = Sha256::digest(&png.pixels).into();
+        add_seeded_noise(&mut png.pixels, png.color_type, seed);
+    } else if !args.only_metadata {
+        add_randomized_noise(&mut png.pixels, png.color_type);
+    }
+
+    // Crop to --crop's rectangle, or by default to 88% of original size (keeping top-left portion)
+    let (width, height, pixels) = match args.crop {
+        None if args.only_metadata => (png.width, png.height, png.pixels().to_vec()),
+        Some(rect) => {
+            let pixels = crop_rect(&png.pixels, png.width, png.height, png.color_type, png.bit_depth, rect)
+                .map_err(|e| format!("Error: --crop: {}", e))?;
+            (rect.width, rect.height, pixels)
+        }
+        None if (png.bit_depth as u8) < 8 => {
+            // crop_image moves whole bytes, so packed samples are spread out to a byte each first
+            let samples = unpack_samples(png.pixels(), png.width, png.bit_depth, png.color_type);
+            let (width, height, cropped) = crop_image(&samples, png.width, png.height, png.color_type.samples());
+            (width, height, repack_samples(&cropped, width, png.bit_depth, png.color_type))
+        }
+        None => crop_image(&png.pixels, png.width, png.height, bytes_per_pixel),
+    };
+
+    let mut out = Converted {
+        width,
+        height,
+        color_type: png.color_type,
+        bit_depth: png.bit_depth,
+        pixels,
+        applied_gamma: None,
+        palette: None,
+        source_colors: None,
+    };
+    for &orientation in &args.orientations {
+        (out.width, out.height, out.pixels) =
+            reorient(&out.pixels, out.width, out.height, out.color_type, out.bit_depth, orientation);
+    }
+    if let Some(target) = args.resize_target() {
+        let filter = args.filter_resize.unwrap_or_default();
+        if out.color_type == ColorType::Indexed && filter == ResizeFilter::Bilinear && args.filter_resize.is_some() {
+            warn!("--filter-resize bilinear can't blend palette indices; resizing with nearest instead");
+        }
+        let (width, height) = target.dimensions(out.width, out.height);
+        out.pixels = resize(&out.pixels, out.width, out.height, out.color_type, out.bit_depth, width, height, filter);
+        (out.width, out.height) = (width, height);
+    }
+    if let Some(background) = args.to_rgb {
+        match flatten_alpha(&out.pixels, out.color_type, out.bit_depth, background) {
+            Ok(rgb) => {
+                out.pixels = rgb;
+                out.color_type = ColorType::Rgb;
+            }
+            Err(_) if out.color_type == ColorType::Rgb => {}
+            Err(_) => warn!("--to-rgb only applies to images with an alpha channel; {:?} left unchanged", out.color_type),
+        }
+    }
+    if args.grayscale {
+        match to_grayscale(&out.pixels, out.color_type, out.bit_depth) {
+            Ok(gray) => {
+                out.pixels = gray;
+                out.color_type = ColorType::Grayscale;
+            }
+            Err(_) if matches!(out.color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha) => {}
+            Err(_) => warn!("--grayscale only applies to RGB and RGBA images; {:?} left unchanged", out.color_type),
+        }
+    }
+    if let Some(target) = args.color.filter(|&target| target != out.color_type) {
+        let palette = png.chunks.iter().find(|c| &c.type_code == b"PLTE").map(|c| c.data.as_slice()).unwrap_or_default();
+        let converted = convert_color_type(
+            &out.pixels,
+            out.width,
+            out.height,
+            out.color_type,
+            out.bit_depth,
+            palette,
+            png.trns.as_deref(),
+            target,
+            args.dither,
+        )
+        .map_err(|e| format!("Error: --color: {}", e))?;
+        for loss in &converted.losses {
+            warn!("--color: {}", loss);
+        }
+        out.pixels = converted.pixels;
+        out.color_type = target;
+        out.bit_depth = converted.bit_depth;
+        out.palette = converted.palette;
+        out.source_colors = converted.source_colors;
+    }
+    if let Some(colors) = args.quantize {
+        let palette = png.chunks.iter().find(|c| &c.type_code == b"PLTE").map(|c| c.data.as_slice()).unwrap_or_default();
+        let quantized = quantize(
+            &out.pixels,
+            out.width,
+            out.height,
+            out.color_type,
+            out.bit_depth,
+            palette,
+            png.trns.as_deref(),
+            colors as usize,
+            args.dither,
+        )
+        .map_err(|e| format!("Error: --quantize: {}", e))?;
+        for loss in &quantized.losses {
+            warn!("--quantize: {}", loss);
+        }
+        out.pixels = quantized.pixels;
+        out.color_type = ColorType::Indexed;
+        out.bit_depth = quantized.bit_depth;
+        out.palette = quantized.palette;
+        out.source_colors = quantized.source_colors;
+    }
+    // Gamma correction comes before any depth reduction so it works at full precision
+    if let Some(target) = args.apply_gamma {
+        let file_gamma = png
+            .chunks
+            .iter()
+            .find(|c| &c.type_code == b"gAMA")
+            .and_then(|c| decode::parse_gamma(&c.data))
+            .filter(|gamma| *gamma > 0.0);
+        match file_gamma {
+            // Indexed colors live in PLTE, which is corrected in `output_chunks`
+            Some(file_gamma) if out.color_type == ColorType::Indexed => out.applied_gamma = Some((file_gamma, target)),
+            Some(file_gamma) => {
+                if apply_gamma(&mut out.pixels, out.color_type, out.bit_depth, file_gamma, target) {
+                    out.applied_gamma = Some((file_gamma, target));
+                } else {
+                    warn!("--apply-gamma does not support {:?}-bit samples; image left untouched", out.bit_depth);
+                }
+            }
+            None => warn!("--apply-gamma: no valid gAMA chunk; image left untouched"),
+        }
+    }
+    if let Some(depth) = args.depth {
+        // Indexed color stores palette indices, which can't be wider than 8 bits
+        if out.color_type == ColorType::Indexed && depth == BitDepth::Sixteen {
+            return Err("Error: --depth 16 is not valid for indexed-color images".to_string());
+        }
+        if depth != out.bit_depth {
+            match convert_bit_depth(&out.pixels, out.bit_depth, depth) {
+                Some(converted) => {
+                    out.pixels = converted;
+                    out.bit_depth = depth;
+                }
+                None => warn!("--depth only converts between 8 and 16 bits; {:?}-bit samples left unchanged", out.bit_depth),
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Select the source chunks to write alongside `out`, adjusting those the
+/// conversions made stale.
+pub fn output_chunks(png: &Png, out: &Converted, args: &Args, strip_rules: &StripRules) -> Vec<Chunk> {
+    // Preserve ancillary metadata by default; --strip/--keep narrow what is carried over
+    let mut chunks = select_chunks(&png.chunks, strip_rules);
+    if out.format_changed(png) {
+        chunks = drop_format_dependent(chunks);
+    }
+    let is_gray = |color_type| matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha);
+    if is_gray(out.color_type) && !is_gray(png.color_type) {
+        // An embedded RGB ICC profile is invalid for a grayscale image
+        chunks.retain(|chunk| &chunk.type_code != b"iCCP");
+    }
+    if let Some((plte, trns)) = &out.palette {
+        // A generated palette replaces the source's, and its tRNS is kept even when stripping
+        chunks.retain(|chunk| !matches!(&chunk.type_code, b"PLTE" | b"tRNS"));
+        chunks.push(Chunk::new(*b"PLTE", plte.clone()));
+        if let Some(trns) = trns {
+            chunks.push(Chunk::new(*b"tRNS", trns.clone()));
+        }
+    } else if png.color_type == ColorType::Indexed && out.color_type != ColorType::Indexed {
+        chunks.retain(|chunk| &chunk.type_code != b"PLTE");
+    }
+    if let Some((file_gamma, target)) = out.applied_gamma {
+        for chunk in chunks.iter_mut() {
+            match &chunk.type_code {
+                b"gAMA" => *chunk = Chunk::new(*b"gAMA", target.gama_value().to_be_bytes().to_vec()),
+                b"PLTE" if out.color_type == ColorType::Indexed => {
+                    let mut palette = chunk.data.clone();
+                    apply_gamma_to_palette(&mut palette, file_gamma, target);
+                    *chunk = Chunk::new(*b"PLTE", palette);
+                }
+                _ => {}
+            }
+        }
+        if target == GammaTarget::Linear {
+            // sRGB asserts the sRGB transfer curve, which linear output no longer has
+            chunks.retain(|chunk| &chunk.type_code != b"sRGB");
+        }
+    }
+    if args.orientations.iter().filter(|o| o.swaps_dimensions()).count() % 2 == 1 {
+        // A quarter turn swaps which axis each pixel density applies to
+        for chunk in chunks.iter_mut().filter(|c| &c.type_code == b"pHYs" && c.data.len() == 9) {
+            let mut data = chunk.data.clone();
+            data[..8].rotate_left(4);
+            *chunk = Chunk::new(*b"pHYs", data);
+        }
+    }
+    if args.remove_gps {
+        chunks.retain_mut(|chunk| {
+            if &chunk.type_code != b"eXIf" {
+                return true;
+            }
+            match exif::remove_gps(&chunk.data) {
+                Ok(Some(data)) => *chunk = Chunk::new(*b"eXIf", data),
+                Ok(None) => {}
+                Err(e) => {
+                    // Without parsing it there is no way to know the location is gone
+                    warn!("could not parse eXIf chunk to remove GPS data ({}); dropping it", e);
+                    return false;
+                }
+            }
+            true
+        });
+    }
+    if !args.set_text.is_empty() {
+        // --set-text replaces every existing text chunk with the same keyword
+        chunks.retain(|chunk| {
+            let is_text = matches!(&chunk.type_code, b"tEXt" | b"zTXt" | b"iTXt");
+            let keyword = chunk.data.split(|&b| b == 0).next().unwrap_or_default();
+            !(is_text && args.set_text.iter().any(|(key, _)| key.chars().map(|c| c as u8).eq(keyword.iter().copied())))
+        });
+    }
+    if let Some((x_dpi, y_dpi)) = args.set_phys {
+        let per_meter = |dpi: u32| ((dpi as f64 / decode::METERS_PER_INCH).round() as u32).to_be_bytes();
+        let mut data = [per_meter(x_dpi), per_meter(y_dpi)].concat();
+        data.push(1);
+        chunks.retain(|chunk| &chunk.type_code != b"pHYs");
+        // pHYs has to come before the image data
+        let at = chunks.iter().position(|chunk| &chunk.type_code == b"IDAT").unwrap_or(chunks.len());
+        chunks.insert(at, Chunk::new(*b"pHYs", data));
+    }
+    chunks
+}
@@ -0,0 +1,651 @@
+//! Helpers for interpreting the payload of common ancillary chunks.
+//!
+//! Each parser takes the raw chunk data and returns `None` when the payload
+//! is too short or otherwise malformed.
+
+use std::io;
+
+use flate2::{Decompress, FlushDecompress, Status};
+use png::ColorType;
+
+/// Upper bound on inflated text/profile size, so a tiny chunk can't expand into gigabytes.
+const MAX_INFLATED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Inflate a zlib stream, as used by zTXt, iTXt and iCCP.
+///
+/// Unlike a plain `read_to_end`, a stream that ends before its final block is
+/// reported as an error instead of silently returning partial output.
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decompress = Decompress::new(true);
+    let mut out = Vec::with_capacity(data.len().saturating_mul(4).min(MAX_INFLATED_BYTES));
+    loop {
+        if out.len() == out.capacity() {
+            if out.len() >= MAX_INFLATED_BYTES {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "inflated data exceeds size limit"));
+            }
+            out.reserve(out.len().max(1024));
+        }
+        let consumed = decompress.total_in() as usize;
+        let produced = out.len();
+        let status = decompress
+            .decompress_vec(&data[consumed..], &mut out, FlushDecompress::None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if status == Status::StreamEnd {
+            return Ok(out);
+        }
+        // No progress with room left in the output means the input ran out
+        let stalled = decompress.total_in() as usize == consumed && out.len() == produced;
+        if stalled && out.len() < out.capacity() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated zlib stream"));
+        }
+    }
+}
+
+/// Split `data` at the first null byte into (before, after).
+pub fn split_null(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let null_pos = data.iter().position(|&b| b == 0)?;
+    Some((&data[..null_pos], &data[null_pos + 1..]))
+}
+
+/// Decode ISO-8859-1 (Latin-1) bytes, the encoding of tEXt and zTXt and of
+/// every text chunk keyword. Each byte is the Unicode code point of the same
+/// value, so nothing is lost or replaced.
+pub fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Read a big-endian u32 starting at `pos`.
+pub fn be_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Read a big-endian u16 starting at `pos`.
+pub fn be_u16(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// tEXt: keyword\0text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub keyword: String,
+    pub text: String,
+}
+
+pub fn parse_text(data: &[u8]) -> Option<TextChunk> {
+    let (keyword, text) = split_null(data)?;
+    Some(TextChunk {
+        keyword: latin1(keyword),
+        text: latin1(text),
+    })
+}
+
+/// zTXt: keyword\0compression_method compressed_text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    pub keyword: String,
+    pub method: u8,
+    /// Decompressed text, or a description of why inflating failed
+    pub text: Result<String, String>,
+}
+
+pub fn parse_ztxt(data: &[u8]) -> Option<CompressedTextChunk> {
+    let (keyword, rest) = split_null(data)?;
+    let method = rest.first().copied().unwrap_or(0);
+    let text = match method {
+        // Method 0 (deflate) is the only one defined by the spec
+        0 => inflate(rest.get(1..).unwrap_or(&[]))
+            .map(|bytes| latin1(&bytes))
+            .map_err(|e| e.to_string()),
+        other => Err(format!("unknown compression method {}", other)),
+    };
+    Some(CompressedTextChunk {
+        keyword: latin1(keyword),
+        method,
+        text,
+    })
+}
+
+/// iTXt: keyword\0 compression_flag compression_method language_tag\0
+/// translated_keyword\0 text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    pub keyword: String,
+    pub compressed: bool,
+    pub language_tag: String,
+    pub translated_keyword: String,
+    /// UTF-8 text (inflated if compressed), or a description of why decoding failed
+    pub text: Result<String, String>,
+}
+
+/// Returns `None` when the chunk has fewer null separators than the format requires.
+pub fn parse_itxt(data: &[u8]) -> Option<InternationalTextChunk> {
+    let (keyword, rest) = split_null(data)?;
+    let compression_flag = *rest.first()?;
+    let compression_method = *rest.get(1)?;
+    let (language_tag, rest) = split_null(rest.get(2..)?)?;
+    let (translated_keyword, text) = split_null(rest)?;
+
+    let text = if compression_flag == 1 {
+        if compression_method == 0 {
+            inflate(text).map_err(|e| e.to_string())
+        } else {
+            Err(format!("unknown compression method {}", compression_method))
+        }
+    } else {
+        Ok(text.to_vec())
+    };
+    let text = text.and_then(|bytes| String::from_utf8(bytes).map_err(|_| "text is not valid UTF-8".to_string()));
+
+    Some(InternationalTextChunk {
+        // Only the keyword is Latin-1; the translated keyword and text are UTF-8
+        keyword: latin1(keyword),
+        compressed: compression_flag == 1,
+        language_tag: String::from_utf8_lossy(language_tag).into_owned(),
+        translated_keyword: String::from_utf8_lossy(translated_keyword).into_owned(),
+        text,
+    })
+}
+
+/// The keywords the PNG spec registers for text chunks, in the spec's order,
+/// with a label for reports.
+pub const REGISTERED_KEYWORDS: [(&str, &str); 10] = [
+    ("Title", "Title"),
+    ("Author", "Author"),
+    ("Description", "Description"),
+    ("Copyright", "Copyright"),
+    ("Creation Time", "Created"),
+    ("Software", "Software"),
+    ("Disclaimer", "Disclaimer"),
+    ("Warning", "Warning"),
+    ("Source", "Source device"),
+    ("Comment", "Comment"),
+];
+
+/// The keyword and value of a tEXt, zTXt or iTXt chunk, or `None` for other
+/// chunk types and text that doesn't decode.
+pub fn text_entry(type_code: &[u8; 4], data: &[u8]) -> Option<(String, String)> {
+    match type_code {
+        b"tEXt" => parse_text(data).map(|t| (t.keyword, t.text)),
+        b"zTXt" => parse_ztxt(data).and_then(|z| Some((z.keyword, z.text.ok()?))),
+        b"iTXt" => parse_itxt(data).and_then(|i| Some((i.keyword, i.text.ok()?))),
+        _ => None,
+    }
+}
+
+/// PLTE: a series of RGB triples. Returns `None` if the length isn't a multiple of 3.
+pub fn parse_palette(data: &[u8]) -> Option<Vec<[u8; 3]>> {
+    if !data.len().is_multiple_of(3) {
+        return None;
+    }
+    Some(data.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect())
+}
+
+/// Format an 8-bit RGB triple as `#RRGGBB`.
+pub fn hex_color(rgb: [u8; 3]) -> String {
+    format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2])
+}
+
+/// Parse a `#RRGGBB` (or `RRGGBB`) color into an 8-bit RGB triple.
+pub fn parse_hex_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// bKGD: suggested background color, laid out according to the image color type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    PaletteIndex(u8),
+}
+
+impl std::fmt::Display for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Background::Gray(level) => write!(f, "gray level {}", level),
+            Background::Rgb(r, g, b) => write!(f, "RGB({}, {}, {})", r, g, b),
+            Background::PaletteIndex(index) => write!(f, "palette index {}", index),
+        }
+    }
+}
+
+/// Returns `None` if the data length doesn't match what `color_type` requires.
+pub fn parse_background(data: &[u8], color_type: ColorType) -> Option<Background> {
+    match color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha if data.len() == 2 => {
+            Some(Background::Gray(be_u16(data, 0)?))
+        }
+        ColorType::Rgb | ColorType::Rgba if data.len() == 6 => {
+            Some(Background::Rgb(be_u16(data, 0)?, be_u16(data, 2)?, be_u16(data, 4)?))
+        }
+        ColorType::Indexed if data.len() == 1 => Some(Background::PaletteIndex(data[0])),
+        _ => None,
+    }
+}
+
+/// tRNS: transparency, laid out according to the image color type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    /// The one gray level drawn fully transparent
+    Gray(u16),
+    /// The one RGB color drawn fully transparent
+    Rgb(u16, u16, u16),
+    /// Alpha for the first palette entries; the rest are opaque
+    PaletteAlpha(Vec<u8>),
+}
+
+impl std::fmt::Display for Transparency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transparency::Gray(level) => write!(f, "gray level {} is transparent", level),
+            Transparency::Rgb(r, g, b) => write!(f, "RGB({}, {}, {}) is transparent", r, g, b),
+            Transparency::PaletteAlpha(alphas) => {
+                let clear = alphas.iter().filter(|&&a| a == 0).count();
+                let partial = alphas.iter().filter(|&&a| a != 0 && a != 255).count();
+                write!(
+                    f,
+                    "alpha for {} palette entries ({} fully transparent, {} partial)",
+                    alphas.len(),
+                    clear,
+                    partial
+                )
+            }
+        }
+    }
+}
+
+/// Returns `None` if the data length doesn't match what `color_type`
+/// requires, or for color types with an alpha channel, which can't have tRNS.
+pub fn parse_transparency(data: &[u8], color_type: ColorType) -> Option<Transparency> {
+    match color_type {
+        ColorType::Grayscale if data.len() == 2 => Some(Transparency::Gray(be_u16(data, 0)?)),
+        ColorType::Rgb if data.len() == 6 => Some(Transparency::Rgb(be_u16(data, 0)?, be_u16(data, 2)?, be_u16(data, 4)?)),
+        ColorType::Indexed if !data.is_empty() && data.len() <= 256 => Some(Transparency::PaletteAlpha(data.to_vec())),
+        _ => None,
+    }
+}
+
+/// Channel names, in storage order, that an sBIT chunk describes for `color_type`.
+pub fn sbit_channels(color_type: ColorType) -> &'static [&'static str] {
+    match color_type {
+        ColorType::Grayscale => &["Gray"],
+        ColorType::GrayscaleAlpha => &["Gray", "Alpha"],
+        // Indexed sBIT describes the RGB palette entries
+        ColorType::Rgb | ColorType::Indexed => &["R", "G", "B"],
+        ColorType::Rgba => &["R", "G", "B", "Alpha"],
+    }
+}
+
+/// sBIT: significant bits per channel. Returns `None` if the length doesn't
+/// match the number of channels for `color_type`.
+pub fn parse_significant_bits(data: &[u8], color_type: ColorType) -> Option<Vec<(&'static str, u8)>> {
+    let channels = sbit_channels(color_type);
+    if data.len() != channels.len() {
+        return None;
+    }
+    Some(channels.iter().copied().zip(data.iter().copied()).collect())
+}
+
+/// acTL: APNG animation control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// 0 means loop forever
+    pub num_plays: u32,
+}
+
+pub fn parse_actl(data: &[u8]) -> Option<AnimationControl> {
+    if data.len() != 8 {
+        return None;
+    }
+    Some(AnimationControl {
+        num_frames: be_u32(data, 0)?,
+        num_plays: be_u32(data, 4)?,
+    })
+}
+
+/// fcTL: APNG frame control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: u8,
+    pub blend_op: u8,
+}
+
+impl FrameControl {
+    /// Frame delay in seconds; a zero denominator means 1/100 s units.
+    pub fn delay_seconds(&self) -> f64 {
+        let den = if self.delay_den == 0 { 100 } else { self.delay_den };
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn dispose_name(&self) -> &'static str {
+        match self.dispose_op {
+            0 => "none",
+            1 => "background",
+            2 => "previous",
+            _ => "unknown",
+        }
+    }
+
+    pub fn blend_name(&self) -> &'static str {
+        match self.blend_op {
+            0 => "source",
+            1 => "over",
+            _ => "unknown",
+        }
+    }
+}
+
+pub fn parse_fctl(data: &[u8]) -> Option<FrameControl> {
+    if data.len() != 26 {
+        return None;
+    }
+    Some(FrameControl {
+        sequence_number: be_u32(data, 0)?,
+        width: be_u32(data, 4)?,
+        height: be_u32(data, 8)?,
+        x_offset: be_u32(data, 12)?,
+        y_offset: be_u32(data, 16)?,
+        delay_num: be_u16(data, 20)?,
+        delay_den: be_u16(data, 22)?,
+        dispose_op: data[24],
+        blend_op: data[25],
+    })
+}
+
+/// gAMA: image gamma times 100000.
+pub fn parse_gamma(data: &[u8]) -> Option<f64> {
+    if data.len() != 4 {
+        return None;
+    }
+    Some(be_u32(data, 0)? as f64 / 100000.0)
+}
+
+/// The everyday name of a gAMA value: 1/2.2 is "gamma 2.2", 1/1.8 is
+/// "gamma 1.8" and 1.0 is "linear". Encoders round 1/2.2 to 45454 or
+/// 45455, so values within two units of the fifth decimal match.
+pub fn gamma_name(gamma: f64) -> Option<&'static str> {
+    const NAMED: [(f64, &str); 3] = [(1.0 / 2.2, "gamma 2.2"), (1.0 / 1.8, "gamma 1.8"), (1.0, "linear")];
+    NAMED.iter().find(|(value, _)| (gamma - value).abs() <= 0.00002).map(|&(_, name)| name)
+}
+
+/// pHYs: pixels per unit on each axis plus a unit specifier (1 = meter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    pub x_ppu: u32,
+    pub y_ppu: u32,
+    pub unit: u8,
+}
+
+impl PhysicalDimensions {
+    pub fn unit_name(&self) -> &'static str {
+        if self.unit == 1 { "meter" } else { "unknown" }
+    }
+
+    /// Horizontal and vertical dots per inch, when the unit is meters and
+    /// both densities are non-zero.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        if self.unit != 1 || self.x_ppu == 0 || self.y_ppu == 0 {
+            return None;
+        }
+        Some((self.x_ppu as f64 * METERS_PER_INCH, self.y_ppu as f64 * METERS_PER_INCH))
+    }
+
+    /// Printed width and height in inches of a `width` x `height` image.
+    pub fn print_size_inches(&self, width: u32, height: u32) -> Option<(f64, f64)> {
+        let (x_dpi, y_dpi) = self.dpi()?;
+        Some((width as f64 / x_dpi, height as f64 / y_dpi))
+    }
+}
+
+/// One inch is exactly 0.0254 meters.
+pub const METERS_PER_INCH: f64 = 0.0254;
+
+pub fn parse_phys(data: &[u8]) -> Option<PhysicalDimensions> {
+    if data.len() != 9 {
+        return None;
+    }
+    Some(PhysicalDimensions {
+        x_ppu: be_u32(data, 0)?,
+        y_ppu: be_u32(data, 4)?,
+        unit: data[8],
+    })
+}
+
+/// oFFs: position of the image on the page, in pixels (unit 0) or
+/// micrometers (unit 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOffset {
+    pub x: i32,
+    pub y: i32,
+    pub unit: u8,
+}
+
+impl ImageOffset {
+    pub fn unit_name(&self) -> &'static str {
+        match self.unit {
+            0 => "pixel",
+            1 => "micrometer",
+            _ => "unknown",
+        }
+    }
+}
+
+pub fn parse_offs(data: &[u8]) -> Option<ImageOffset> {
+    if data.len() != 9 {
+        return None;
+    }
+    Some(ImageOffset {
+        x: be_u32(data, 0)? as i32,
+        y: be_u32(data, 4)? as i32,
+        unit: data[8],
+    })
+}
+
+/// sCAL: physical width and height of one pixel, in meters (unit 1) or
+/// radians (unit 2), stored as ASCII floating-point strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhysicalScale {
+    pub unit: u8,
+    pub width: String,
+    pub height: String,
+}
+
+impl PhysicalScale {
+    pub fn unit_name(&self) -> &'static str {
+        match self.unit {
+            1 => "meter",
+            2 => "radian",
+            _ => "unknown",
+        }
+    }
+
+    /// The pixel width as a number, if the string is a valid positive value.
+    pub fn width_value(&self) -> Option<f64> {
+        parse_positive_float(&self.width)
+    }
+
+    /// The pixel height as a number, if the string is a valid positive value.
+    pub fn height_value(&self) -> Option<f64> {
+        parse_positive_float(&self.height)
+    }
+}
+
+/// Returns `None` without the null separator; the strings are returned
+/// as stored so unparseable values can still be shown.
+pub fn parse_scal(data: &[u8]) -> Option<PhysicalScale> {
+    let (&unit, rest) = data.split_first()?;
+    let (width, height) = split_null(rest)?;
+    Some(PhysicalScale {
+        unit,
+        width: String::from_utf8_lossy(width).into_owned(),
+        height: String::from_utf8_lossy(height).into_owned(),
+    })
+}
+
+fn parse_positive_float(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok().filter(|value| value.is_finite() && *value > 0.0)
+}
+
+/// iCCP: profile name\0compression_method compressed_profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfileHeader {
+    pub name: String,
+    pub compressed_size: usize,
+}
+
+pub fn parse_iccp(data: &[u8]) -> Option<IccProfileHeader> {
+    let (name, rest) = split_null(data)?;
+    Some(IccProfileHeader {
+        name: String::from_utf8_lossy(name).into_owned(),
+        compressed_size: rest.len().saturating_sub(1),
+    })
+}
+
+/// Inflate the embedded ICC profile from an iCCP chunk.
+pub fn extract_icc_profile(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (_, rest) = split_null(data).ok_or("iCCP chunk has no profile name terminator")?;
+    match rest.first() {
+        Some(0) => inflate(&rest[1..]).map_err(|e| format!("could not decompress ICC profile: {}", e)),
+        Some(method) => Err(format!("unknown iCCP compression method {}", method)),
+        None => Err("iCCP chunk is missing its compression method".to_string()),
+    }
+}
+
+/// sRGB: a single rendering-intent byte.
+pub fn parse_srgb(data: &[u8]) -> Option<&'static str> {
+    if data.len() != 1 {
+        return None;
+    }
+    Some(match data[0] {
+        0 => "Perceptual",
+        1 => "Relative colorimetric",
+        2 => "Saturation",
+        3 => "Absolute colorimetric",
+        _ => "Unknown",
+    })
+}
+
+/// tIME: last-modification time (UTC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+pub fn parse_time(data: &[u8]) -> Option<Timestamp> {
+    if data.len() != 7 {
+        return None;
+    }
+    Some(Timestamp {
+        year: be_u16(data, 0)?,
+        month: data[2],
+        day: data[3],
+        hour: data[4],
+        minute: data[5],
+        second: data[6],
+    })
+}
+
+/// sPLT: a named suggested palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    pub name: String,
+    /// 8 or 16; entry samples are stored at this depth
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedPaletteEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+impl SuggestedPaletteEntry {
+    /// The color as `#RRGGBB` (8-bit) or `#RRRRGGGGBBBB` (16-bit).
+    pub fn hex(&self, sample_depth: u8) -> String {
+        if sample_depth == 16 {
+            format!("#{:04X}{:04X}{:04X}", self.red, self.green, self.blue)
+        } else {
+            format!("#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)
+        }
+    }
+}
+
+pub fn parse_splt(data: &[u8]) -> Result<SuggestedPalette, String> {
+    let (name, rest) = split_null(data).ok_or("missing null separator after palette name")?;
+    let (&sample_depth, entries) = rest.split_first().ok_or("missing sample depth")?;
+    // Each entry is red, green, blue, alpha at the sample depth plus a 16-bit frequency
+    let entry_size = match sample_depth {
+        8 => 6,
+        16 => 10,
+        _ => return Err(format!("invalid sample depth {} (expected 8 or 16)", sample_depth)),
+    };
+    if !entries.len().is_multiple_of(entry_size) {
+        return Err(format!(
+            "{} bytes of entries is not a multiple of the {}-byte entry size",
+            entries.len(),
+            entry_size
+        ));
+    }
+    let entries = entries
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            let sample = |i: usize| {
+                if sample_depth == 16 {
+                    u16::from_be_bytes([entry[i * 2], entry[i * 2 + 1]])
+                } else {
+                    entry[i] as u16
+                }
+            };
+            SuggestedPaletteEntry {
+                red: sample(0),
+                green: sample(1),
+                blue: sample(2),
+                alpha: sample(3),
+                frequency: u16::from_be_bytes([entry[entry_size - 2], entry[entry_size - 1]]),
+            }
+        })
+        .collect();
+    Ok(SuggestedPalette { name: String::from_utf8_lossy(name).into_owned(), sample_depth, entries })
+}
+
+/// hIST: approximate usage frequency of each palette entry. Returns `None`
+/// for an odd length.
+pub fn parse_hist(data: &[u8]) -> Option<Vec<u16>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(data.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect())
+}
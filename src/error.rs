@@ -0,0 +1,115 @@
+use std::fmt;
+use std::io;
+
+use crate::chunk::FileFormat;
+use crate::transform::CropRect;
+
+/// Errors produced while reading or writing PNG files.
+#[derive(Debug)]
+pub enum UnpeelError {
+    /// Underlying I/O failure (open, read, write)
+    IoError(io::Error),
+    /// The `png` crate rejected the image stream
+    DecodeError(png::DecodingError),
+    /// The `png` crate failed to encode the output image
+    EncodeError(png::EncodingError),
+    /// libwebp failed to encode the output image
+    WebpEncodeError(String),
+    /// The input lacks the PNG signature; the format is named when it is
+    /// another image type we recognize
+    NotPng(Option<FileFormat>),
+    /// A chunk could not be parsed from the raw byte stream
+    MalformedChunk(String),
+    /// The decoded image would exceed the allowed buffer size; `bytes` is
+    /// `None` if the size doesn't even fit in a `usize`
+    ImageTooLarge { width: u32, height: u32, bytes: Option<usize>, limit: usize },
+    /// IHDR gives a width or height of zero, which the spec forbids
+    InvalidDimensions { width: u32, height: u32 },
+    /// An operation was asked to handle a color type it doesn't support
+    UnsupportedColorType(png::ColorType),
+    /// An APNG frame index past the last frame; `frames` is how many there are
+    FrameOutOfRange { index: usize, frames: usize },
+    /// A crop rectangle that doesn't fit inside the `width` x `height` image
+    CropOutOfBounds { rect: CropRect, width: u32, height: u32 },
+    /// Frame `index` of an animation being assembled is `size`, unlike the
+    /// first frame's `expected`
+    FrameSizeMismatch { index: usize, size: (u32, u32), expected: (u32, u32) },
+}
+
+impl fmt::Display for UnpeelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnpeelError::IoError(e) => write!(f, "I/O error: {}", e),
+            UnpeelError::DecodeError(e) => write!(f, "PNG decode error: {}", e),
+            UnpeelError::EncodeError(e) => write!(f, "PNG encode error: {}", e),
+            UnpeelError::WebpEncodeError(e) => write!(f, "WebP encode error: {}", e),
+            UnpeelError::NotPng(Some(format)) => write!(f, "This is a {} file, not a PNG", format),
+            UnpeelError::NotPng(None) => write!(f, "Not a PNG: the file does not start with the PNG signature"),
+            UnpeelError::MalformedChunk(msg) => write!(f, "Malformed chunk: {}", msg),
+            UnpeelError::ImageTooLarge { width, height, bytes: Some(bytes), limit } => write!(
+                f,
+                "Image too large: {}x{} needs {} bytes, over the limit of {} bytes",
+                width, height, bytes, limit
+            ),
+            UnpeelError::ImageTooLarge { width, height, bytes: None, limit } => write!(
+                f,
+                "Image too large: {}x{} overflows the buffer size (limit {} bytes)",
+                width, height, limit
+            ),
+            UnpeelError::InvalidDimensions { width, height } => {
+                write!(f, "Invalid image dimensions {}x{}: width and height must both be at least 1", width, height)
+            }
+            UnpeelError::UnsupportedColorType(color_type) => write!(f, "Unsupported color type: {:?}", color_type),
+            UnpeelError::FrameOutOfRange { index, frames } => {
+                write!(f, "Frame {} is out of range: the image has {} frame(s)", index, frames)
+            }
+            UnpeelError::CropOutOfBounds { rect, width, height } => write!(
+                f,
+                "Crop rectangle {}x{} at {},{} does not fit inside the {}x{} image",
+                rect.width, rect.height, rect.x, rect.y, width, height
+            ),
+            UnpeelError::FrameSizeMismatch { index, size, expected } => write!(
+                f,
+                "Frame {} is {}x{}, but every frame must match the first one's {}x{}",
+                index, size.0, size.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UnpeelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnpeelError::IoError(e) => Some(e),
+            UnpeelError::DecodeError(e) => Some(e),
+            UnpeelError::EncodeError(e) => Some(e),
+            UnpeelError::WebpEncodeError(_)
+            | UnpeelError::NotPng(_)
+            | UnpeelError::MalformedChunk(_)
+            | UnpeelError::ImageTooLarge { .. }
+            | UnpeelError::InvalidDimensions { .. }
+            | UnpeelError::UnsupportedColorType(_)
+            | UnpeelError::FrameOutOfRange { .. }
+            | UnpeelError::CropOutOfBounds { .. }
+            | UnpeelError::FrameSizeMismatch { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for UnpeelError {
+    fn from(e: io::Error) -> Self {
+        UnpeelError::IoError(e)
+    }
+}
+
+impl From<png::DecodingError> for UnpeelError {
+    fn from(e: png::DecodingError) -> Self {
+        UnpeelError::DecodeError(e)
+    }
+}
+
+impl From<png::EncodingError> for UnpeelError {
+    fn from(e: png::EncodingError) -> Self {
+        UnpeelError::EncodeError(e)
+    }
+}
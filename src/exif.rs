@@ -0,0 +1,365 @@
+//! Minimal EXIF (TIFF structure) parser for the eXIf chunk.
+//!
+//! Walks IFD0 plus the Exif and GPS sub-IFDs it points to. Every entry is
+//! kept, including tags this module has no name for, so nothing is silently
+//! dropped.
+
+use std::fmt;
+
+/// Which IFD an entry came from. Tag numbers are only unique within an IFD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ifd {
+    Primary,
+    Exif,
+    Gps,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Bytes(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SignedShort(Vec<i16>),
+    SignedLong(Vec<i32>),
+    SignedRational(Vec<(i32, i32)>),
+    Float(Vec<f64>),
+}
+
+impl ExifValue {
+    /// First value as a float, for numeric and rational types.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.to_f64_vec().into_iter().next()
+    }
+
+    /// All values as floats (rationals divided out); empty for text/bytes.
+    pub fn to_f64_vec(&self) -> Vec<f64> {
+        match self {
+            ExifValue::Short(v) => v.iter().map(|&x| x as f64).collect(),
+            ExifValue::Long(v) => v.iter().map(|&x| x as f64).collect(),
+            ExifValue::SignedShort(v) => v.iter().map(|&x| x as f64).collect(),
+            ExifValue::SignedLong(v) => v.iter().map(|&x| x as f64).collect(),
+            ExifValue::Float(v) => v.clone(),
+            ExifValue::Rational(v) => v.iter().filter(|r| r.1 != 0).map(|&(n, d)| n as f64 / d as f64).collect(),
+            ExifValue::SignedRational(v) => {
+                v.iter().filter(|r| r.1 != 0).map(|&(n, d)| n as f64 / d as f64).collect()
+            }
+            ExifValue::Bytes(_) | ExifValue::Ascii(_) => Vec::new(),
+        }
+    }
+}
+
+fn join<T: fmt::Display>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for ExifValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExifValue::Ascii(s) => write!(f, "{}", s),
+            ExifValue::Bytes(b) if b.len() > 16 => write!(f, "[{} bytes]", b.len()),
+            ExifValue::Bytes(b) => write!(f, "{:?}", b),
+            ExifValue::Short(v) => write!(f, "{}", join(v)),
+            ExifValue::Long(v) => write!(f, "{}", join(v)),
+            ExifValue::SignedShort(v) => write!(f, "{}", join(v)),
+            ExifValue::SignedLong(v) => write!(f, "{}", join(v)),
+            ExifValue::Float(v) => write!(f, "{}", join(v)),
+            ExifValue::Rational(v) => {
+                let parts: Vec<String> = v.iter().map(|(n, d)| format!("{}/{}", n, d)).collect();
+                write!(f, "{}", parts.join(", "))
+            }
+            ExifValue::SignedRational(v) => {
+                let parts: Vec<String> = v.iter().map(|(n, d)| format!("{}/{}", n, d)).collect();
+                write!(f, "{}", parts.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExifEntry {
+    pub ifd: Ifd,
+    pub tag: u16,
+    pub value: ExifValue,
+}
+
+impl ExifEntry {
+    /// Human-readable tag name, or `Tag 0xNNNN` for tags not in the table.
+    pub fn name(&self) -> String {
+        match tag_name(self.ifd, self.tag) {
+            Some(name) => name.to_string(),
+            None => format!("Tag 0x{:04X}", self.tag),
+        }
+    }
+
+    /// Value formatted with units where the tag has a conventional form.
+    pub fn display_value(&self) -> String {
+        match (self.ifd, self.tag, self.value.as_f64()) {
+            (Ifd::Exif, 0x829A, Some(t)) if t > 0.0 && t < 1.0 => format!("1/{:.0} s", 1.0 / t),
+            (Ifd::Exif, 0x829A, Some(t)) => format!("{} s", t),
+            (Ifd::Exif, 0x829D, Some(n)) => format!("f/{:.1}", n),
+            (Ifd::Exif, 0x920A, Some(mm)) => format!("{} mm", mm),
+            _ => self.value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exif {
+    /// True for "MM" (Motorola) byte order, false for "II" (Intel)
+    pub big_endian: bool,
+    pub entries: Vec<ExifEntry>,
+}
+
+impl Exif {
+    /// Parse the TIFF structure stored in an eXIf chunk.
+    pub fn parse(data: &[u8]) -> Result<Exif, String> {
+        let big_endian = match data.get(0..2) {
+            Some(b"MM") => true,
+            Some(b"II") => false,
+            _ => return Err("missing II/MM byte-order marker".to_string()),
+        };
+        let reader = TiffReader { data, big_endian };
+        if reader.u16(2) != Some(42) {
+            return Err("bad TIFF magic number".to_string());
+        }
+        let ifd0 = reader.u32(4).ok_or("truncated TIFF header")? as usize;
+
+        let mut entries = Vec::new();
+        reader.read_ifd(ifd0, Ifd::Primary, &mut entries)?;
+
+        // Follow the Exif and GPS sub-IFD pointers from IFD0 (once each)
+        let pointers: Vec<(u16, u32)> = entries
+            .iter()
+            .filter(|e| e.ifd == Ifd::Primary && (e.tag == 0x8769 || e.tag == 0x8825))
+            .filter_map(|e| match &e.value {
+                ExifValue::Long(v) => v.first().map(|&offset| (e.tag, offset)),
+                _ => None,
+            })
+            .collect();
+        for (tag, offset) in pointers {
+            let ifd = if tag == 0x8769 { Ifd::Exif } else { Ifd::Gps };
+            reader.read_ifd(offset as usize, ifd, &mut entries)?;
+        }
+
+        Ok(Exif { big_endian, entries })
+    }
+
+    pub fn get(&self, ifd: Ifd, tag: u16) -> Option<&ExifValue> {
+        self.entries.iter().find(|e| e.ifd == ifd && e.tag == tag).map(|e| &e.value)
+    }
+
+    /// GPS position as signed decimal degrees (latitude, longitude).
+    pub fn gps_coordinates(&self) -> Option<(f64, f64)> {
+        let lat = dms_to_degrees(self.get(Ifd::Gps, 0x0002)?)?;
+        let lon = dms_to_degrees(self.get(Ifd::Gps, 0x0004)?)?;
+        let lat_sign = match self.get(Ifd::Gps, 0x0001) {
+            Some(ExifValue::Ascii(r)) if r.starts_with('S') => -1.0,
+            _ => 1.0,
+        };
+        let lon_sign = match self.get(Ifd::Gps, 0x0003) {
+            Some(ExifValue::Ascii(r)) if r.starts_with('W') => -1.0,
+            _ => 1.0,
+        };
+        Some((lat * lat_sign, lon * lon_sign))
+    }
+}
+
+/// Rewrite eXIf chunk data without its GPS IFD, keeping every other tag.
+///
+/// The GPS pointer is removed from IFD0 and the GPS IFD and the values it
+/// points to are zeroed. Nothing else moves, so the offsets used by the rest
+/// of the TIFF structure stay valid. Returns `None` when there is no GPS IFD.
+pub fn remove_gps(data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let big_endian = match data.get(0..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return Err("missing II/MM byte-order marker".to_string()),
+    };
+    let reader = TiffReader { data, big_endian };
+    if reader.u16(2) != Some(42) {
+        return Err("bad TIFF magic number".to_string());
+    }
+    let ifd0 = reader.u32(4).ok_or("truncated TIFF header")? as usize;
+    let count = reader.u16(ifd0).ok_or("IFD offset out of bounds")? as usize;
+    let ifd0_end = ifd0 + 2 + count * 12 + 4;
+    if ifd0_end > data.len() {
+        return Err("truncated IFD".to_string());
+    }
+    let Some(index) = (0..count).find(|i| reader.u16(ifd0 + 2 + i * 12) == Some(0x8825)) else {
+        return Ok(None);
+    };
+    let gps = reader.u32(ifd0 + 2 + index * 12 + 8).ok_or("truncated IFD entry")? as usize;
+
+    let mut out = data.to_vec();
+    let mut wipe = |start: usize, len: usize| {
+        let end = start.saturating_add(len).min(out.len());
+        if start < end {
+            out[start..end].fill(0);
+        }
+    };
+    // Zero the values stored outside the GPS IFD's entries, then the IFD itself
+    let gps_count = reader.u16(gps).unwrap_or(0) as usize;
+    for i in 0..gps_count {
+        let entry = gps + 2 + i * 12;
+        let size = reader.u16(entry + 2).and_then(value_size);
+        let total = reader.u32(entry + 4).zip(size).and_then(|(n, size)| size.checked_mul(n as usize));
+        if let (Some(total), Some(offset)) = (total.filter(|&total| total > 4), reader.u32(entry + 8)) {
+            wipe(offset as usize, total);
+        }
+    }
+    wipe(gps, 2 + gps_count * 12 + 4);
+
+    // Close the gap left by the pointer entry; the freed 12 bytes at the end are zeroed
+    let entry = ifd0 + 2 + index * 12;
+    out.copy_within(entry + 12..ifd0_end, entry);
+    out[ifd0_end - 12..ifd0_end].fill(0);
+    let new_count = (count - 1) as u16;
+    let count_bytes = if big_endian { new_count.to_be_bytes() } else { new_count.to_le_bytes() };
+    out[ifd0..ifd0 + 2].copy_from_slice(&count_bytes);
+    Ok(Some(out))
+}
+
+/// Bytes per value of a TIFF field type, or `None` for unknown types.
+fn value_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// Degrees/minutes/seconds rationals to decimal degrees.
+fn dms_to_degrees(value: &ExifValue) -> Option<f64> {
+    let parts = value.to_f64_vec();
+    if parts.len() != 3 {
+        return None;
+    }
+    Some(parts[0] + parts[1] / 60.0 + parts[2] / 3600.0)
+}
+
+/// Names for the common IFD0, Exif and GPS tags.
+pub fn tag_name(ifd: Ifd, tag: u16) -> Option<&'static str> {
+    let name = match (ifd, tag) {
+        (Ifd::Primary, 0x010E) => "ImageDescription",
+        (Ifd::Primary, 0x010F) => "Make",
+        (Ifd::Primary, 0x0110) => "Model",
+        (Ifd::Primary, 0x0112) => "Orientation",
+        (Ifd::Primary, 0x011A) => "XResolution",
+        (Ifd::Primary, 0x011B) => "YResolution",
+        (Ifd::Primary, 0x0128) => "ResolutionUnit",
+        (Ifd::Primary, 0x0131) => "Software",
+        (Ifd::Primary, 0x0132) => "DateTime",
+        (Ifd::Primary, 0x013B) => "Artist",
+        (Ifd::Primary, 0x8298) => "Copyright",
+        (Ifd::Primary, 0x8769) => "ExifIFDPointer",
+        (Ifd::Primary, 0x8825) => "GPSInfoIFDPointer",
+        (Ifd::Exif, 0x829A) => "ExposureTime",
+        (Ifd::Exif, 0x829D) => "FNumber",
+        (Ifd::Exif, 0x8822) => "ExposureProgram",
+        (Ifd::Exif, 0x8827) => "ISOSpeedRatings",
+        (Ifd::Exif, 0x9000) => "ExifVersion",
+        (Ifd::Exif, 0x9003) => "DateTimeOriginal",
+        (Ifd::Exif, 0x9004) => "DateTimeDigitized",
+        (Ifd::Exif, 0x9201) => "ShutterSpeedValue",
+        (Ifd::Exif, 0x9202) => "ApertureValue",
+        (Ifd::Exif, 0x9204) => "ExposureBiasValue",
+        (Ifd::Exif, 0x9207) => "MeteringMode",
+        (Ifd::Exif, 0x9209) => "Flash",
+        (Ifd::Exif, 0x920A) => "FocalLength",
+        (Ifd::Exif, 0xA002) => "PixelXDimension",
+        (Ifd::Exif, 0xA003) => "PixelYDimension",
+        (Ifd::Exif, 0xA405) => "FocalLengthIn35mmFilm",
+        (Ifd::Exif, 0xA433) => "LensMake",
+        (Ifd::Exif, 0xA434) => "LensModel",
+        (Ifd::Gps, 0x0000) => "GPSVersionID",
+        (Ifd::Gps, 0x0001) => "GPSLatitudeRef",
+        (Ifd::Gps, 0x0002) => "GPSLatitude",
+        (Ifd::Gps, 0x0003) => "GPSLongitudeRef",
+        (Ifd::Gps, 0x0004) => "GPSLongitude",
+        (Ifd::Gps, 0x0005) => "GPSAltitudeRef",
+        (Ifd::Gps, 0x0006) => "GPSAltitude",
+        (Ifd::Gps, 0x0007) => "GPSTimeStamp",
+        (Ifd::Gps, 0x001D) => "GPSDateStamp",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Endian-aware reads over the TIFF byte stream. Offsets are relative to the
+/// start of the TIFF header, which is the start of the eXIf chunk data.
+struct TiffReader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn u16(&self, pos: usize) -> Option<u16> {
+        let b = self.data.get(pos..pos + 2)?;
+        Some(if self.big_endian { u16::from_be_bytes([b[0], b[1]]) } else { u16::from_le_bytes([b[0], b[1]]) })
+    }
+
+    fn u32(&self, pos: usize) -> Option<u32> {
+        let b = self.data.get(pos..pos + 4)?;
+        let bytes = [b[0], b[1], b[2], b[3]];
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&self, pos: usize) -> Option<u64> {
+        let b = self.data.get(pos..pos + 8)?;
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(b);
+        Some(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+
+    fn read_ifd(&self, offset: usize, ifd: Ifd, entries: &mut Vec<ExifEntry>) -> Result<(), String> {
+        let count = self.u16(offset).ok_or("IFD offset out of bounds")? as usize;
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry).ok_or("truncated IFD entry")?;
+            let field_type = self.u16(entry + 2).ok_or("truncated IFD entry")?;
+            let n = self.u32(entry + 4).ok_or("truncated IFD entry")? as usize;
+            if let Some(value) = self.read_value(entry + 8, field_type, n) {
+                entries.push(ExifEntry { ifd, tag, value });
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode a value whose 4-byte slot is at `slot`; values larger than four
+    /// bytes live at the offset stored in the slot.
+    fn read_value(&self, slot: usize, field_type: u16, count: usize) -> Option<ExifValue> {
+        let size = value_size(field_type)?;
+        let total = size.checked_mul(count)?;
+        let start = if total <= 4 { slot } else { self.u32(slot)? as usize };
+        let bytes = self.data.get(start..start.checked_add(total)?)?;
+
+        let at = |i: usize| start + i * size;
+        Some(match field_type {
+            2 => {
+                let text = bytes.split(|&b| b == 0).next().unwrap_or(&[]);
+                ExifValue::Ascii(String::from_utf8_lossy(text).trim().to_string())
+            }
+            1 | 6 | 7 => ExifValue::Bytes(bytes.to_vec()),
+            3 => ExifValue::Short((0..count).map(|i| self.u16(at(i))).collect::<Option<_>>()?),
+            8 => ExifValue::SignedShort((0..count).map(|i| self.u16(at(i)).map(|v| v as i16)).collect::<Option<_>>()?),
+            4 => ExifValue::Long((0..count).map(|i| self.u32(at(i))).collect::<Option<_>>()?),
+            9 => ExifValue::SignedLong((0..count).map(|i| self.u32(at(i)).map(|v| v as i32)).collect::<Option<_>>()?),
+            5 => ExifValue::Rational(
+                (0..count)
+                    .map(|i| Some((self.u32(at(i))?, self.u32(at(i) + 4)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            10 => ExifValue::SignedRational(
+                (0..count)
+                    .map(|i| Some((self.u32(at(i))? as i32, self.u32(at(i) + 4)? as i32)))
+                    .collect::<Option<_>>()?,
+            ),
+            11 => ExifValue::Float((0..count).map(|i| self.u32(at(i)).map(|v| f32::from_bits(v) as f64)).collect::<Option<_>>()?),
+            _ => ExifValue::Float((0..count).map(|i| self.u64(at(i)).map(f64::from_bits)).collect::<Option<_>>()?),
+        })
+    }
+}
@@ -0,0 +1,201 @@
+//! Writing pieces of the source file out to separate files.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use png::ColorType;
+use serde_json::{json, Map, Value};
+
+use unpeel::decode;
+use unpeel::{drop_format_dependent, encode_pnm, extract_frame, select_chunks, write_png_image, Chunk, EncodeOptions, Png};
+use unpeel::{write_output_file, StripRules, UnpeelError};
+
+use crate::{write_error, Failure, EXIT_WRITE};
+
+/// Write the raw (decompressed) ICC profile from the iCCP chunk to `target`.
+/// Returns the profile size in bytes.
+pub fn dump_icc_profile(png: &Png, target: &Path, overwrite: bool) -> Result<usize, Failure> {
+    let iccp = png
+        .chunks
+        .iter()
+        .find(|c| &c.type_code == b"iCCP")
+        .ok_or("Error: file has no iCCP chunk, so there is no ICC profile to dump".to_string())?;
+    let profile = decode::extract_icc_profile(&iccp.data).map_err(|e| format!("Error: {}", e))?;
+
+    // The first header field of an ICC profile is its own total size
+    match decode::be_u32(&profile, 0) {
+        Some(declared) if declared as usize == profile.len() => {}
+        Some(declared) => warn!(
+            "ICC header declares {} bytes but the profile is {} bytes",
+            declared,
+            profile.len()
+        ),
+        None => warn!("ICC profile is too short to contain a header"),
+    }
+
+    write_file(target, &profile, overwrite)?;
+    Ok(profile.len())
+}
+
+/// Write the bytes that follow IEND to `target`. Returns how many there were.
+pub fn dump_trailing(png: &Png, target: &Path, overwrite: bool) -> Result<usize, Failure> {
+    if png.trailing.is_empty() {
+        return Err("Error: file has no data after IEND to dump".to_string().into());
+    }
+    write_file(target, &png.trailing, overwrite)?;
+    Ok(png.trailing.len())
+}
+
+/// Write the raw data of the `type_code` chunk to `target`. When there are
+/// several, each goes to `<stem>-<N>.<ext>` beside `target`, numbered from 0
+/// in file order. Returns each path written with its size in bytes.
+pub fn dump_chunks(
+    png: &Png,
+    type_code: [u8; 4],
+    target: &Path,
+    overwrite: bool,
+) -> Result<Vec<(PathBuf, usize)>, Failure> {
+    let found: Vec<&Chunk> = png.chunks.iter().filter(|c| c.type_code == type_code).collect();
+    if found.is_empty() {
+        return Err(format!("Error: file has no {} chunk to extract", String::from_utf8_lossy(&type_code)).into());
+    }
+    let mut written = Vec::with_capacity(found.len());
+    for (index, chunk) in found.iter().enumerate() {
+        let path = if found.len() == 1 { target.to_path_buf() } else { indexed_path(target, index) };
+        write_file(&path, &chunk.data, overwrite)?;
+        written.push((path, chunk.data.len()));
+    }
+    Ok(written)
+}
+
+/// `target` with `-<index>` added to its file stem: `out.bin` becomes `out-2.bin`.
+fn indexed_path(target: &Path, index: usize) -> PathBuf {
+    let stem = target.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match target.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, index, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, index),
+    };
+    target.with_file_name(name)
+}
+
+/// Composite frame `index` of the (A)PNG in `bytes` and write it to
+/// `target` as a still PNG, carrying over the metadata `strip_rules` keeps.
+///
+/// Frames are expanded to RGB(A) or grayscale(-alpha), so the palette and the
+/// chunks laid out per color type are dropped.
+pub fn write_frame(
+    png: &Png,
+    bytes: &[u8],
+    index: usize,
+    target: &Path,
+    strip_rules: &StripRules,
+    max_bytes: usize,
+    overwrite: bool,
+) -> Result<(), Failure> {
+    let frame = extract_frame(bytes, index, max_bytes).map_err(|e| Failure::reading(format!("Error: {}", e), &e))?;
+    let mut chunks = drop_format_dependent(select_chunks(&png.chunks, strip_rules));
+    chunks.retain(|chunk| &chunk.type_code != b"PLTE");
+    write_png_image(
+        target,
+        frame.width,
+        frame.height,
+        frame.color_type,
+        frame.bit_depth,
+        &chunks,
+        &frame.pixels,
+        &EncodeOptions { overwrite, ..EncodeOptions::default() },
+    )
+    .map_err(|e| match e {
+        UnpeelError::IoError(e) => write_failure(target, &e),
+        e => Failure::reading(format!("Error: could not write {}: {}", target.display(), e), &e),
+    })
+}
+
+/// Where `--extract-frame` writes frame `index` of `input_path`:
+/// `<stem>-frame<index>.png` next to the input or inside an `--output`
+/// directory, or the `--output` path itself.
+pub fn frame_output_path(input_path: &Path, index: usize, output: Option<&Path>) -> PathBuf {
+    let stem = input_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = format!("{}-frame{}.png", stem, index);
+    match output {
+        None => input_path.with_file_name(name),
+        Some(dir) if dir.is_dir() => dir.join(name),
+        Some(target) => target.to_path_buf(),
+    }
+}
+
+/// Write the decoded pixels to `target` as a binary PGM or PPM. Returns the
+/// format written, "PGM" or "PPM".
+pub fn write_pnm(png: &Png, target: &Path, overwrite: bool) -> Result<&'static str, Failure> {
+    if matches!(png.color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) || png.trns.is_some() {
+        warn!("PPM and PGM have no alpha channel; transparency is dropped from {}", target.display());
+    }
+    let encoded = encode_pnm(png).map_err(|e| format!("Error: {}", e))?;
+    write_file(target, &encoded, overwrite)?;
+    Ok(if encoded.starts_with(b"P5") { "PGM" } else { "PPM" })
+}
+
+/// Write every tEXt, zTXt and iTXt chunk to `target` as a JSON object mapping
+/// each keyword to a list of its entries in file order, so repeated keywords
+/// are all kept. Compressed text is inflated. Returns the number of entries.
+pub fn dump_text(png: &Png, target: &Path, overwrite: bool) -> Result<usize, Failure> {
+    let mut keywords: Map<String, Value> = Map::new();
+    let mut count = 0;
+    for chunk in &png.chunks {
+        let data = &chunk.data;
+        let (keyword, entry) = match &chunk.type_code {
+            b"tEXt" => match decode::parse_text(data) {
+                Some(t) => (t.keyword, json!({ "chunk": "tEXt", "text": t.text })),
+                None => continue,
+            },
+            b"zTXt" => match decode::parse_ztxt(data) {
+                Some(z) => (z.keyword, text_entry(json!({ "chunk": "zTXt" }), z.text)),
+                None => continue,
+            },
+            b"iTXt" => match decode::parse_itxt(data) {
+                Some(i) => {
+                    let entry = json!({
+                        "chunk": "iTXt",
+                        "language_tag": i.language_tag,
+                        "translated_keyword": i.translated_keyword,
+                    });
+                    (i.keyword, text_entry(entry, i.text))
+                }
+                None => continue,
+            },
+            _ => continue,
+        };
+        if let Value::Array(entries) = keywords.entry(keyword).or_insert_with(|| json!([])) {
+            entries.push(entry);
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return Err("Error: file has no tEXt, zTXt or iTXt chunks, so there is no text to dump".to_string().into());
+    }
+
+    let mut contents = serde_json::to_string_pretty(&keywords).map_err(|e| format!("Error: {}", e))?;
+    contents.push('\n');
+    write_file(target, contents.as_bytes(), overwrite)?;
+    Ok(count)
+}
+
+/// Write `contents` to `target`, replacing an existing file only when
+/// `overwrite` is set.
+fn write_file(target: &Path, contents: &[u8], overwrite: bool) -> Result<(), Failure> {
+    write_output_file(target, contents, overwrite).map_err(|e| write_failure(target, &e))
+}
+
+fn write_failure(target: &Path, e: &io::Error) -> Failure {
+    Failure { message: format!("Error: {}", write_error(target, e)), code: EXIT_WRITE }
+}
+
+/// Add the decoded `text` to `entry`, or an `error` describing why it couldn't be inflated.
+fn text_entry(mut entry: Value, text: Result<String, String>) -> Value {
+    match text {
+        Ok(text) => entry["text"] = json!(text),
+        Err(e) => entry["error"] = json!(e),
+    }
+    entry
+}
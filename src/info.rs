@@ -0,0 +1,70 @@
+//! A summary of a PNG's header and chunk layout, for printing without
+//! formatting every field by hand.
+
+use std::fmt;
+
+use png::{BitDepth, ColorType};
+
+use crate::Png;
+
+/// The header fields and chunk counts of a PNG, from `Png::info`.
+///
+/// `Display` prints one `Key: value` line per field, the lines the `unpeel`
+/// report opens its Summary section with; `Debug` dumps the raw fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PngInfo {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: BitDepth,
+    pub interlaced: bool,
+    /// Every chunk in the file, IHDR and IEND included
+    pub chunk_count: usize,
+    pub idat_count: usize,
+}
+
+impl From<&Png> for PngInfo {
+    fn from(png: &Png) -> Self {
+        PngInfo {
+            width: png.width,
+            height: png.height,
+            color_type: png.color_type,
+            bit_depth: png.bit_depth,
+            interlaced: png.interlaced,
+            chunk_count: png.chunks.len(),
+            idat_count: png.chunks.iter().filter(|c| &c.type_code == b"IDAT").count(),
+        }
+    }
+}
+
+impl fmt::Display for PngInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dimensions: {}x{}", self.width, self.height)?;
+        writeln!(f, "Color format: {:?} at {:?} bits", self.color_type, self.bit_depth)?;
+        writeln!(f, "Interlaced: {}", if self.interlaced { "Adam7" } else { "None (progressive not used)" })?;
+        write!(f, "Chunks: {} ({} IDAT)", self.chunk_count, self.idat_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_prints_one_labelled_line_per_field() {
+        let info = PngInfo {
+            width: 640,
+            height: 480,
+            color_type: ColorType::Rgba,
+            bit_depth: BitDepth::Eight,
+            interlaced: false,
+            chunk_count: 5,
+            idat_count: 2,
+        };
+        let expected = "Dimensions: 640x480\n\
+                        Color format: Rgba at Eight bits\n\
+                        Interlaced: None (progressive not used)\n\
+                        Chunks: 5 (2 IDAT)";
+        assert_eq!(info.to_string(), expected);
+    }
+}
@@ -0,0 +1,48 @@
+//! Adam7 pass layout, used to interlace output (the encoder only writes
+//! non-interlaced images) and to size interlaced image data.
+
+/// Each pass's starting column and row, then its column and row step.
+const ADAM7: [(u32, u32, u32, u32); 7] =
+    [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+/// The width and height of each Adam7 pass for an image of `width` x `height`.
+pub fn adam7_pass_sizes(width: u32, height: u32) -> [(u32, u32); 7] {
+    ADAM7.map(|(x0, y0, dx, dy)| (width.saturating_sub(x0).div_ceil(dx), height.saturating_sub(y0).div_ceil(dy)))
+}
+
+/// Split packed, non-interlaced `pixels` into the seven Adam7 reduced images,
+/// each returned as `(width, height, pixels)` with its rows packed the same
+/// way. Passes that contain no pixels come back with a zero width or height.
+pub fn adam7_passes(pixels: &[u8], width: u32, height: u32, bits_per_pixel: usize) -> Vec<(u32, u32, Vec<u8>)> {
+    let stride = (width as usize * bits_per_pixel).div_ceil(8);
+    ADAM7
+        .iter()
+        .zip(adam7_pass_sizes(width, height))
+        .map(|(&(x0, y0, dx, dy), (pass_width, pass_height))| {
+            let pass_stride = (pass_width as usize * bits_per_pixel).div_ceil(8);
+            let mut pass = vec![0u8; pass_stride * pass_height as usize];
+            for py in 0..pass_height as usize {
+                let row = &pixels[(y0 as usize + py * dy as usize) * stride..][..stride];
+                let pass_row = &mut pass[py * pass_stride..][..pass_stride];
+                for px in 0..pass_width as usize {
+                    copy_pixel(row, x0 as usize + px * dx as usize, pass_row, px, bits_per_pixel);
+                }
+            }
+            (pass_width, pass_height, pass)
+        })
+        .collect()
+}
+
+/// Copy pixel `from` of `src` to pixel `to` of `dst`; sub-byte pixels are
+/// packed most significant bits first.
+fn copy_pixel(src: &[u8], from: usize, dst: &mut [u8], to: usize, bits_per_pixel: usize) {
+    if bits_per_pixel >= 8 {
+        let n = bits_per_pixel / 8;
+        dst[to * n..][..n].copy_from_slice(&src[from * n..][..n]);
+    } else {
+        let mask = (1u8 << bits_per_pixel) - 1;
+        let shift = |bit: usize| 8 - bits_per_pixel - bit % 8;
+        let value = (src[from * bits_per_pixel / 8] >> shift(from * bits_per_pixel)) & mask;
+        dst[to * bits_per_pixel / 8] |= value << shift(to * bits_per_pixel);
+    }
+}
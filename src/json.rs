@@ -0,0 +1,173 @@
+//! Machine-readable report for `--json`.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Map, Value};
+
+use unpeel::decode;
+use unpeel::exif::Exif;
+use unpeel::{declared_color_space, validate_chunk_order, ChannelEntropy, Chunk, EmbeddedSignature, Png};
+
+/// Build the full JSON report: filesystem metadata, image header, and chunks.
+pub fn build_report(path: &Path, png: &Png) -> Value {
+    json!({
+        "file": file_metadata(path),
+        "image": {
+            "width": png.width,
+            "height": png.height,
+            "color_type": format!("{:?}", png.color_type),
+            "bit_depth": format!("{:?}", png.bit_depth),
+            "interlaced": png.interlaced,
+            "color_space": declared_color_space(&png.chunks)
+                .map(|color_space| json!({ "label": color_space.label, "source": color_space.source })),
+            "idat": png.idat_stats().map(|stats| json!({
+                "count": stats.count,
+                "min": stats.min,
+                "max": stats.max,
+                "average": stats.average(),
+            })),
+        },
+        "chunks": png.chunks.iter().map(|chunk| chunk_json(chunk, png)).collect::<Vec<_>>(),
+        "trailing_bytes": png.trailing.len(),
+        "violations": validate_chunk_order(&png.chunks, png.color_type),
+    })
+}
+
+/// `--stego-check` scores: each channel's bit-plane entropies and whether it
+/// looks suspicious, plus the channels flagged overall.
+pub fn stego_report(scores: &[ChannelEntropy]) -> Value {
+    let channels: Vec<Value> = scores
+        .iter()
+        .map(|s| json!({ "channel": s.channel, "lsb_entropy": s.lsb, "higher_entropy": s.higher, "suspicious": s.is_suspicious() }))
+        .collect();
+    let suspicious: Vec<&str> = scores.iter().filter(|s| s.is_suspicious()).map(|s| s.channel).collect();
+    json!({ "channels": channels, "suspicious_channels": suspicious })
+}
+
+/// `--polyglot-check` findings: each embedded signature's format, offset and
+/// the chunk it sits in (`null` after IEND).
+pub fn polyglot_report(found: &[EmbeddedSignature]) -> Value {
+    let signature = |s: &EmbeddedSignature| {
+        json!({ "format": s.format, "offset": s.offset, "chunk": s.chunk.map(|c| String::from_utf8_lossy(&c).into_owned()) })
+    };
+    Value::Array(found.iter().map(signature).collect())
+}
+
+fn file_metadata(path: &Path) -> Value {
+    let mut file = Map::new();
+    file.insert("path".to_string(), json!(path.display().to_string()));
+    if let Ok(metadata) = std::fs::metadata(path) {
+        file.insert("size".to_string(), json!(metadata.len()));
+        if let Ok(modified) = metadata.modified() {
+            file.insert("modified".to_string(), unix_seconds(modified));
+        }
+        if let Ok(created) = metadata.created() {
+            file.insert("created".to_string(), unix_seconds(created));
+        }
+    }
+    Value::Object(file)
+}
+
+fn unix_seconds(time: SystemTime) -> Value {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => json!(d.as_secs()),
+        Err(_) => Value::Null,
+    }
+}
+
+fn chunk_json(chunk: &Chunk, png: &Png) -> Value {
+    let mut obj = Map::new();
+    obj.insert("type".to_string(), json!(chunk.type_str()));
+    obj.insert("length".to_string(), json!(chunk.data.len()));
+    obj.insert("offset".to_string(), json!(chunk.offset));
+    obj.insert("crc_valid".to_string(), json!(chunk.crc_is_valid()));
+
+    let data = &chunk.data;
+    let fields = match &chunk.type_code {
+        b"tEXt" => decode::parse_text(data).map(|t| json!({ "keyword": t.keyword, "text": t.text })),
+        b"zTXt" => decode::parse_ztxt(data).map(|z| {
+            json!({ "keyword": z.keyword, "method": z.method, "text": z.text.ok() })
+        }),
+        b"iTXt" => decode::parse_itxt(data).map(|i| {
+            json!({
+                "keyword": i.keyword,
+                "compressed": i.compressed,
+                "language_tag": i.language_tag,
+                "translated_keyword": i.translated_keyword,
+                "text": i.text.ok(),
+            })
+        }),
+        b"gAMA" => decode::parse_gamma(data).map(|g| json!({ "gamma": g })),
+        b"pHYs" => decode::parse_phys(data).map(|p| {
+            let mut fields = json!({ "x_ppu": p.x_ppu, "y_ppu": p.y_ppu, "unit": p.unit_name() });
+            if let (Some((x_dpi, y_dpi)), Some((width, height))) = (p.dpi(), p.print_size_inches(png.width, png.height)) {
+                fields["dpi"] = json!([x_dpi, y_dpi]);
+                fields["print_size_inches"] = json!([width, height]);
+            }
+            fields
+        }),
+        b"oFFs" => decode::parse_offs(data).map(|o| json!({ "x": o.x, "y": o.y, "unit": o.unit_name() })),
+        b"sCAL" => decode::parse_scal(data).map(|s| {
+            json!({ "unit": s.unit_name(), "width": s.width_value(), "height": s.height_value() })
+        }),
+        b"iCCP" => decode::parse_iccp(data).map(|i| {
+            json!({ "profile_name": i.name, "compressed_size": i.compressed_size })
+        }),
+        b"sRGB" => decode::parse_srgb(data).map(|intent| json!({ "rendering_intent": intent })),
+        b"tIME" => decode::parse_time(data).map(|t| json!({ "modified": t.to_string() })),
+        b"PLTE" => decode::parse_palette(data).map(|palette| {
+            let colors: Vec<String> = palette.iter().map(|rgb| decode::hex_color(*rgb)).collect();
+            json!({ "entries": palette.len(), "colors": colors })
+        }),
+        b"bKGD" => decode::parse_background(data, png.color_type).map(|b| json!({ "background": b.to_string() })),
+        b"sBIT" => decode::parse_significant_bits(data, png.color_type).map(|channels| {
+            let bits: Map<String, Value> = channels.iter().map(|(name, bits)| (name.to_string(), json!(bits))).collect();
+            json!({ "significant_bits": bits })
+        }),
+        b"hIST" => decode::parse_hist(data).map(|frequencies| json!({ "frequencies": frequencies })),
+        b"sPLT" => decode::parse_splt(data).ok().map(|splt| {
+            let entries: Vec<Value> = splt
+                .entries
+                .iter()
+                .map(|e| json!({ "color": e.hex(splt.sample_depth), "alpha": e.alpha, "frequency": e.frequency }))
+                .collect();
+            json!({ "name": splt.name, "sample_depth": splt.sample_depth, "entries": entries })
+        }),
+        b"acTL" => decode::parse_actl(data).map(|a| json!({ "num_frames": a.num_frames, "num_plays": a.num_plays })),
+        b"fcTL" => decode::parse_fctl(data).map(|f| {
+            json!({
+                "sequence_number": f.sequence_number,
+                "width": f.width,
+                "height": f.height,
+                "x_offset": f.x_offset,
+                "y_offset": f.y_offset,
+                "delay_num": f.delay_num,
+                "delay_den": f.delay_den,
+                "dispose_op": f.dispose_name(),
+                "blend_op": f.blend_name(),
+            })
+        }),
+        b"fdAT" => decode::be_u32(data, 0).map(|seq| json!({ "sequence_number": seq })),
+        b"eXIf" => Exif::parse(data).ok().map(|exif| {
+            let tags: Map<String, Value> = exif
+                .entries
+                .iter()
+                .map(|entry| (entry.name(), json!(entry.display_value())))
+                .collect();
+            json!({ "tags": tags, "gps": exif.gps_coordinates().map(|(lat, lon)| json!([lat, lon])) })
+        }),
+        b"tRNS" => Some(match decode::parse_transparency(data, png.color_type) {
+            Some(decode::Transparency::Gray(level)) => json!({ "transparent_gray": level }),
+            Some(decode::Transparency::Rgb(r, g, b)) => json!({ "transparent_rgb": [r, g, b] }),
+            Some(decode::Transparency::PaletteAlpha(alphas)) => json!({ "palette_alpha": alphas }),
+            None => json!({ "transparency": data }),
+        }),
+        _ => None,
+    };
+    if let Some(Value::Object(fields)) = fields {
+        obj.extend(fields);
+    }
+
+    Value::Object(obj)
+}
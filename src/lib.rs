@@ -0,0 +1,401 @@
+//! Read PNG metadata and raw chunks, and re-encode images with their metadata peeled off.
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use flate2::read::ZlibDecoder;
+use png::{BitDepth, ColorType, Decoder, Limits};
+use sha2::{Digest, Sha256};
+
+use crate::interlace::adam7_pass_sizes;
+
+mod alpha;
+mod apng;
+mod chunk;
+mod colorspace;
+mod crc;
+pub mod decode;
+mod error;
+pub mod exif;
+mod info;
+mod interlace;
+mod output;
+mod phash;
+mod pixels;
+mod pnm;
+mod polyglot;
+mod quantize;
+mod resize;
+mod stego;
+mod strip;
+mod thumbnail;
+mod transform;
+mod validate;
+#[cfg(feature = "webp")]
+mod webp_output;
+
+pub use alpha::{alpha_stats, AlphaStats};
+pub use apng::{assemble_apng, extract_frame, Frame};
+pub use chunk::{
+    check_signature, detect_format, parse_chunks, repair_crcs, Chunk, ChunkReader, FileFormat, PNG_SIGNATURE,
+};
+pub use colorspace::{declared_color_space, DeclaredColorSpace};
+pub use crc::chunk_crc;
+pub use error::UnpeelError;
+pub use info::PngInfo;
+pub use output::{
+    create_output_file, create_output_path, in_place_temp_path, replace_original, resolve_output_path,
+    write_output_file, write_png_image, EncodeOptions, Filter, OutputFormat, OutputTemplate,
+};
+pub use phash::difference_hash;
+pub use pixels::{repack_samples, unpack_samples, Pixel};
+pub use pnm::encode_pnm;
+pub use resize::{resize, ResizeFilter, ResizeTarget};
+pub use polyglot::{find_embedded_formats, EmbeddedSignature};
+pub use stego::{lsb_entropy, ChannelEntropy};
+pub use strip::{can_carry_forward, drop_format_dependent, parse_chunk_list, select_chunks, MetadataMode, StripRules};
+pub use thumbnail::{thumbnail, Thumbnail};
+pub use transform::{
+    add_randomized_noise, add_seeded_noise, apply_gamma, apply_gamma_to_palette, convert_bit_depth, convert_color_type, crop_image, crop_rect,
+    flatten_alpha, quantize, reorient, to_grayscale, ColorConversion, CropRect, GammaTarget, Orientation,
+};
+pub use validate::{color_depth_violation, validate_chunk_order};
+#[cfg(feature = "webp")]
+pub use webp_output::write_webp_image;
+
+/// Default cap on the decoded image buffer: 512 MiB.
+pub const DEFAULT_MAX_BYTES: usize = 512 * 1024 * 1024;
+
+/// Pixel count above which a header is suspiciously large: 100 megapixels,
+/// well past any camera, so more often a corrupt or crafted IHDR.
+pub const LARGE_PIXEL_COUNT: u64 = 100_000_000;
+
+/// A decoded PNG: header fields, decoded pixels, and every raw chunk in file order.
+#[derive(Debug, Clone)]
+pub struct Png {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: BitDepth,
+    pub interlaced: bool,
+    pub trns: Option<Vec<u8>>,
+    pub chunks: Vec<Chunk>,
+    /// Decoded image data, rows top to bottom. Allocated at `bytes_per_pixel()`
+    /// bytes per pixel, so sub-byte images leave slack at the end; `pixels()`
+    /// trims it. Empty when only the metadata was read.
+    pub pixels: Vec<u8>,
+    /// Bytes after the IEND chunk, which decoders ignore. Appended data is
+    /// a common way to smuggle a payload inside an innocent-looking image.
+    pub trailing: Vec<u8>,
+}
+
+/// More IDAT chunks than this is flagged as excessive fragmentation.
+pub const IDAT_FRAGMENTATION_LIMIT: usize = 64;
+
+/// How the image data is split across IDAT chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdatStats {
+    pub count: usize,
+    /// Payload size of the smallest and largest chunk, in bytes
+    pub min: usize,
+    pub max: usize,
+    /// Sum of every payload
+    pub total: u64,
+}
+
+impl IdatStats {
+    /// Mean payload size in bytes.
+    pub fn average(&self) -> f64 {
+        self.total as f64 / self.count as f64
+    }
+
+    /// Whether there are more than `IDAT_FRAGMENTATION_LIMIT` chunks. Some
+    /// encoders write one per row, which slows decoding down.
+    pub fn is_fragmented(&self) -> bool {
+        self.count > IDAT_FRAGMENTATION_LIMIT
+    }
+}
+
+/// How long `Png::read_timed` spent in each of its two passes over the file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadTimings {
+    /// Header and image data through the decoder
+    pub decode: Duration,
+    /// The raw chunk walk
+    pub chunk_parse: Duration,
+}
+
+impl Png {
+    /// Decode the PNG at `path` and walk its chunks.
+    ///
+    /// The file is read into memory once and both the decoder and the chunk
+    /// walker run over that buffer. Images needing more than
+    /// `DEFAULT_MAX_BYTES` of pixel data are rejected.
+    pub fn from_path(path: &Path) -> Result<Png, UnpeelError> {
+        Png::from_path_with_limit(path, DEFAULT_MAX_BYTES)
+    }
+
+    /// `from_path` with a custom cap on the decoded buffer size, checked
+    /// against the IHDR dimensions before anything is allocated.
+    pub fn from_path_with_limit(path: &Path, max_bytes: usize) -> Result<Png, UnpeelError> {
+        let bytes = fs::read(path)?;
+        Png::from_bytes_with_limit(&bytes, max_bytes)
+    }
+
+    /// Decode a PNG held in memory, e.g. one read from stdin.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Png, UnpeelError> {
+        Png::from_bytes_with_limit(bytes, DEFAULT_MAX_BYTES)
+    }
+
+    /// `from_bytes` with a custom cap on the decoded buffer size.
+    pub fn from_bytes_with_limit(bytes: &[u8], max_bytes: usize) -> Result<Png, UnpeelError> {
+        Png::read(bytes, Some(max_bytes))
+    }
+
+    /// Read the header and chunks of the PNG at `path` without decoding pixels,
+    /// so even images too large to buffer can be inspected. `pixels` is empty.
+    pub fn metadata_from_path(path: &Path) -> Result<Png, UnpeelError> {
+        let bytes = fs::read(path)?;
+        Png::metadata_from_bytes(&bytes)
+    }
+
+    /// `metadata_from_path` for a PNG held in memory.
+    pub fn metadata_from_bytes(bytes: &[u8]) -> Result<Png, UnpeelError> {
+        Png::read(bytes, None)
+    }
+
+    /// Decode pixels when given a buffer limit; read metadata only otherwise.
+    fn read(bytes: &[u8], pixel_limit: Option<usize>) -> Result<Png, UnpeelError> {
+        Png::read_timed(bytes, pixel_limit).map(|(png, _)| png)
+    }
+
+    /// `from_bytes_with_limit` when given a buffer limit, `metadata_from_bytes`
+    /// otherwise, also returning how long the decode and the chunk walk took.
+    pub fn read_timed(bytes: &[u8], pixel_limit: Option<usize>) -> Result<(Png, ReadTimings), UnpeelError> {
+        // Name other image formats up front instead of surfacing a decoder error
+        check_signature(bytes)?;
+        // A zero-sized image is invalid, and would otherwise decode to an empty buffer
+        if let Some(header) = Png::raw_header(bytes).filter(|header| header.width == 0 || header.height == 0) {
+            return Err(UnpeelError::InvalidDimensions { width: header.width, height: header.height });
+        }
+        let start = Instant::now();
+        let mut png = match Png::decode(bytes, pixel_limit) {
+            // The decoder refuses color type and bit depth pairs the spec
+            // forbids, but without pixels the header and chunks can still be
+            // read and the pair reported as a violation
+            Err(e) if pixel_limit.is_none() => Png::raw_header(bytes).ok_or(e)?,
+            result => result?,
+        };
+        let decoded = Instant::now();
+        // Walk the raw chunks too, including ones the decoder skips
+        png.chunks = parse_chunks(bytes)?;
+        if let Some(iend) = png.chunks.last().filter(|c| &c.type_code == b"IEND") {
+            png.trailing = bytes.get(iend.offset + 12..).unwrap_or_default().to_vec();
+        }
+        let timings = ReadTimings { decode: decoded - start, chunk_parse: decoded.elapsed() };
+        Ok((png, timings))
+    }
+
+    /// The header fields straight from the IHDR chunk, for a header the
+    /// decoder rejected. `None` unless IHDR comes first and its fields fit
+    /// png's types.
+    fn raw_header(bytes: &[u8]) -> Option<Png> {
+        let ihdr = bytes.get(8..8 + 8 + 13).filter(|ihdr| &ihdr[4..8] == b"IHDR")?;
+        Some(Png {
+            width: decode::be_u32(ihdr, 8)?,
+            height: decode::be_u32(ihdr, 12)?,
+            bit_depth: BitDepth::from_u8(ihdr[16])?,
+            color_type: ColorType::from_u8(ihdr[17])?,
+            interlaced: ihdr[20] == 1,
+            trns: None,
+            chunks: Vec::new(),
+            pixels: Vec::new(),
+            trailing: Vec::new(),
+        })
+    }
+
+    /// Decode the header and, if given a `pixel_limit`, the image data;
+    /// `chunks` is left empty for the caller to fill.
+    fn decode(input: impl Read, pixel_limit: Option<usize>) -> Result<Png, UnpeelError> {
+        let mut decoder = Decoder::new(input);
+        // Also caps what the decoder itself allocates for chunks and rows
+        decoder.set_limits(Limits { bytes: pixel_limit.unwrap_or(DEFAULT_MAX_BYTES) });
+        // CRCs are checked against the raw chunks below, so a corrupt ancillary
+        // chunk is reported rather than aborting the decode
+        decoder.ignore_checksums(true);
+        // Text chunks are decoded from the raw chunk list, so a malformed one
+        // shouldn't stop the pixels from being read
+        decoder.set_ignore_text_chunk(true);
+        let mut reader = decoder.read_info()?;
+
+        // Get info and clone it before reading frame (to avoid borrowing issues)
+        let info = reader.info();
+        let width = info.width;
+        let height = info.height;
+        let color_type = info.color_type;
+        let bit_depth = info.bit_depth;
+        let interlaced = info.interlaced;
+        let bytes_per_pixel = info.bytes_per_pixel();
+        let trns = info.trns.as_ref().map(|cow| cow.to_vec());
+
+        // Metadata-only reads never allocate the image buffer
+        let mut pixels = Vec::new();
+        if let Some(limit) = pixel_limit {
+            // Calculate buffer size: width * height * bytes_per_pixel, refusing
+            // crafted dimensions before allocating anything
+            let buffer_size = (width as usize)
+                .checked_mul(height as usize)
+                .and_then(|n| n.checked_mul(bytes_per_pixel));
+            match buffer_size {
+                Some(size) if size <= limit => pixels = vec![0; size],
+                bytes => return Err(UnpeelError::ImageTooLarge { width, height, bytes, limit }),
+            }
+            reader.next_frame(&mut pixels)?;
+        }
+
+        Ok(Png {
+            width,
+            height,
+            color_type,
+            bit_depth,
+            interlaced,
+            trns,
+            chunks: Vec::new(),
+            pixels,
+            trailing: Vec::new(),
+        })
+    }
+
+    /// Bytes in one row of packed pixels, rounded up to a whole byte.
+    pub fn row_bytes(&self) -> usize {
+        (self.width as usize * self.color_type.samples() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// The decoded rows, packed as PNG stores them: `row_bytes()` per row,
+    /// sub-byte pixels sharing bytes most significant bits first, 16-bit
+    /// samples big-endian. Empty when only the metadata was read.
+    pub fn pixels(&self) -> &[u8] {
+        self.pixels.get(..self.row_bytes() * self.height as usize).unwrap_or_default()
+    }
+
+    /// The packed bytes of row `y`, or `None` past the last row or when the
+    /// pixels weren't decoded.
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        let stride = self.row_bytes();
+        self.pixels().get(y.checked_mul(stride)?..)?.get(..stride)
+    }
+
+    /// Row `y` with every sample unpacked into its own `u16`, `width *
+    /// samples` of them. Useful for 1, 2 and 4-bit images, whose packed rows
+    /// share bytes between pixels. Values are not rescaled.
+    pub fn unpacked_row(&self, y: usize) -> Option<Vec<u16>> {
+        let row = self.row(y)?;
+        Some(pixels::sample_values(row, self.width as usize, 1, self.color_type.samples(), self.bit_depth))
+    }
+
+    /// The samples of the pixel at column `x`, row `y`, or `None` outside
+    /// the image or when the pixels weren't decoded.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Pixel> {
+        if x >= self.width as usize {
+            return None;
+        }
+        Some(Pixel::from_row(self.row(y)?, x, self.color_type.samples(), self.bit_depth))
+    }
+
+    /// Number of bytes per decoded pixel (rounded up for sub-byte depths).
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.color_type.samples() * ((self.bit_depth as usize + 7) >> 3)
+    }
+
+    /// Header fields and chunk counts, whose `Display` opens the report's Summary.
+    pub fn info(&self) -> PngInfo {
+        PngInfo::from(self)
+    }
+
+    /// Total compressed size of the image data: the sum of all IDAT payloads.
+    pub fn idat_bytes(&self) -> u64 {
+        self.chunks.iter().filter(|c| &c.type_code == b"IDAT").map(|c| c.data.len() as u64).sum()
+    }
+
+    /// Count and size distribution of the IDAT chunks, or `None` if there
+    /// are none.
+    pub fn idat_stats(&self) -> Option<IdatStats> {
+        let sizes: Vec<usize> = self.chunks.iter().filter(|c| &c.type_code == b"IDAT").map(|c| c.data.len()).collect();
+        Some(IdatStats {
+            count: sizes.len(),
+            min: *sizes.iter().min()?,
+            max: *sizes.iter().max()?,
+            total: sizes.iter().map(|&size| size as u64).sum(),
+        })
+    }
+
+    /// Whether the header claims more than `LARGE_PIXEL_COUNT` pixels.
+    pub fn is_unusually_large(&self) -> bool {
+        self.width as u64 * self.height as u64 > LARGE_PIXEL_COUNT
+    }
+
+    /// Size of the decoded image, width * height * bytes_per_pixel, computed
+    /// from the header so it is available without decoding pixels.
+    pub fn decoded_size(&self) -> Option<u64> {
+        (self.width as u64).checked_mul(self.height as u64)?.checked_mul(self.bytes_per_pixel() as u64)
+    }
+
+    /// Size of the decompressed IDAT stream the header implies: a filter byte
+    /// plus the packed samples of every row, summed over the Adam7 passes if
+    /// interlaced. Empty passes have no rows at all.
+    pub fn expected_idat_size(&self) -> Option<u64> {
+        let bits_per_pixel = self.color_type.samples() as u64 * self.bit_depth as u64;
+        let passes = if self.interlaced {
+            adam7_pass_sizes(self.width, self.height).to_vec()
+        } else {
+            vec![(self.width, self.height)]
+        };
+        passes.into_iter().filter(|&(width, height)| width > 0 && height > 0).try_fold(0u64, |total, (width, height)| {
+            let row = (width as u64).checked_mul(bits_per_pixel)?.div_ceil(8) + 1;
+            total.checked_add(row.checked_mul(height as u64)?)
+        })
+    }
+
+    /// Inflate the IDAT stream, counting up to `limit + 1` bytes so an
+    /// oversized stream is detected without decompressing all of it. A stream
+    /// that fails to inflate returns `Err` with the count reached.
+    pub fn inflated_idat_size(&self, limit: u64) -> Result<u64, u64> {
+        let compressed: Vec<u8> =
+            self.chunks.iter().filter(|c| &c.type_code == b"IDAT").flat_map(|c| c.data.iter().copied()).collect();
+        let mut inflater = ZlibDecoder::new(compressed.as_slice()).take(limit.saturating_add(1));
+        let mut buf = [0u8; 8192];
+        let mut total = 0u64;
+        loop {
+            match inflater.read(&mut buf) {
+                Ok(0) => return Ok(total),
+                Ok(n) => total += n as u64,
+                Err(_) => return Err(total),
+            }
+        }
+    }
+
+    /// SHA-256 of the decoded pixels as a lowercase hex string.
+    ///
+    /// The dimensions, color type and bit depth are hashed ahead of the pixel
+    /// data, so two images whose buffers happen to hold the same bytes in a
+    /// different shape or format get different digests.
+    pub fn pixel_sha256(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.width.to_be_bytes());
+        hasher.update(self.height.to_be_bytes());
+        hasher.update([self.color_type as u8, self.bit_depth as u8]);
+        // Only the image itself: not the slack past the last row, nor the
+        // unused low bits that pad a sub-byte row out to a whole byte
+        let row_bits = self.width as usize * self.color_type.samples() * self.bit_depth as usize;
+        let last_byte_mask = 0xFFu8 << ((8 - row_bits % 8) % 8);
+        for row in self.pixels().chunks(self.row_bytes().max(1)) {
+            if let Some((last, rest)) = row.split_last() {
+                hasher.update(rest);
+                hasher.update([last & last_byte_mask]);
+            }
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
@@ -1,293 +1,725 @@
-use std::env;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Read};
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use png::{Decoder, Encoder, ColorType};
-use rand::Rng;
+use std::time::Instant;
+
+use log::{debug, info, trace, warn};
+use serde_json::Value;
+
+use unpeel::{alpha_stats, declared_color_space, difference_hash, in_place_temp_path, lsb_entropy, replace_original, resolve_output_path, validate_chunk_order, write_png_image};
+use unpeel::{decode, find_embedded_formats, ChannelEntropy, EmbeddedSignature, LARGE_PIXEL_COUNT, OutputFormat, Png, StripRules, UnpeelError};
+#[cfg(feature = "webp")]
+use unpeel::write_webp_image;
+
+use cli::Args;
+use timing::Timings;
+
+mod assemble;
+mod batch;
+mod cli;
+mod compare;
+mod config;
+mod convert;
+mod extract;
+mod json;
+mod progress;
+mod repair;
+mod report;
+mod style;
+mod summary;
+mod timing;
+mod verify;
+
+/// Stand-in file name for input read from stdin, used to name the output.
+const STDIN_NAME: &str = "stdin.png";
+
+// Exit statuses; keep the list in `--help` (cli.rs) in step
+
+/// Any failure not listed below, including a batch run where some files failed.
+const EXIT_FAILURE: i32 = 1;
+/// The input file does not exist. clap also exits with 2 on invalid arguments.
+const EXIT_NOT_FOUND: i32 = 2;
+/// The input is a JPEG, GIF, WebP or BMP rather than a PNG.
+const EXIT_NOT_PNG: i32 = 3;
+/// The PNG could not be decoded: corrupt data, a malformed chunk, or too large.
+const EXIT_DECODE: i32 = 4;
+/// A `--strict` check failed: a CRC, the IDAT size, or chunk ordering.
+const EXIT_STRICT: i32 = 5;
+/// The output image could not be encoded or written.
+const EXIT_WRITE: i32 = 6;
+
+/// A failure that stops processing a file: the complete message to print
+/// and the exit status it maps to.
+#[derive(Debug)]
+pub struct Failure {
+    pub message: String,
+    pub code: i32,
+}
+
+impl Failure {
+    /// A failure caused by `error` while reading the input, with the exit
+    /// status for its class of error.
+    pub fn reading(message: String, error: &UnpeelError) -> Failure {
+        let code = match error {
+            UnpeelError::IoError(e) if e.kind() == io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+            UnpeelError::IoError(_) => EXIT_FAILURE,
+            UnpeelError::NotPng(_) => EXIT_NOT_PNG,
+            UnpeelError::DecodeError(_)
+            | UnpeelError::MalformedChunk(_)
+            | UnpeelError::ImageTooLarge { .. }
+            | UnpeelError::InvalidDimensions { .. }
+            | UnpeelError::UnsupportedColorType(_)
+            | UnpeelError::FrameOutOfRange { .. } => EXIT_DECODE,
+            UnpeelError::CropOutOfBounds { .. } | UnpeelError::FrameSizeMismatch { .. } => EXIT_FAILURE,
+            UnpeelError::EncodeError(_) | UnpeelError::WebpEncodeError(_) => EXIT_WRITE,
+        };
+        Failure { message, code }
+    }
+
+    fn strict(message: String) -> Failure {
+        Failure { message, code: EXIT_STRICT }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure { message, code: EXIT_FAILURE }
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    
-    if args.len() != 2 {
-        eprintln!("Usage: {} <path_to_png>", args[0]);
-        std::process::exit(1);
-    }
-    
-    let file_path = &args[1];
-    let path = Path::new(file_path);
-    
-    // Check if file exists
-    if !path.exists() {
-        eprintln!("Error: File '{}' does not exist", file_path);
-        std::process::exit(1);
-    }
-    
-    // File system metadata
-    println!("=== File System Metadata ===");
-    if let Ok(metadata) = std::fs::metadata(path) {
-        println!("File size: {} bytes", metadata.len());
-        if let Ok(modified) = metadata.modified() {
-            println!("Last modified: {:?}", modified);
-        }
-        if let Ok(created) = metadata.created() {
-            println!("Created: {:?}", created);
-        }
-    }
-    
-    println!("\n=== PNG Image Metadata ===");
-    
-    // Open and decode PNG
-    let file = match File::open(path) {
-        Ok(f) => f,
+    let args = Args::parse_args();
+    init_logging(&args);
+    let code = match run(&args) {
+        Ok(true) => 0,
+        // Batch runs and --verify-only have already reported what failed
+        Ok(false) => EXIT_FAILURE,
         Err(e) => {
-            eprintln!("Error opening file: {}", e);
-            std::process::exit(1);
+            eprintln!("{}", e.message);
+            e.code
         }
     };
-    
-    let reader = BufReader::new(file);
-    let decoder = Decoder::new(reader);
-    
-    let mut reader = match decoder.read_info() {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!("Error reading PNG: {}", e);
-            std::process::exit(1);
+    std::process::exit(code);
+}
+
+/// Send log records to stderr, keeping stdout for the report. --log-level
+/// wins over RUST_LOG, and warnings and errors are shown when neither is set.
+fn init_logging(args: &Args) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(log::LevelFilter::Warn).parse_default_env();
+    if let Some(level) = args.log_level {
+        builder.filter_level(level);
+    }
+    builder
+        .format(|buf, record| {
+            use std::io::Write;
+            writeln!(buf, "{} {}", style::level_label(record.level()), record.args())
+        })
+        .init();
+}
+
+/// Process the input named on the command line: a single file, a directory,
+/// or a glob pattern. Returns whether every file succeeded, or the failure
+/// that stopped the run.
+fn run(args: &Args) -> Result<bool, Failure> {
+    let strip_rules = args.strip_rules().map_err(|e| format!("Error: {}", e))?;
+
+    if args.assemble {
+        assemble::run(&assemble::input_files(args)?, args)?;
+        return Ok(true);
+    }
+
+    if let Some(list) = &args.from_list {
+        let files = batch::read_list(list).map_err(|e| format!("Error: {}", e))?;
+        // Listed paths are mirrored into --output relative to the current directory
+        return Ok(batch::run_files(&files, Path::new(""), args, &strip_rules));
+    }
+    let file_path = args.input();
+    if let Some(other) = &args.compare {
+        compare::run(other, file_path, args)?;
+        return Ok(true);
+    }
+    if Path::new(file_path).is_dir() {
+        return Ok(batch::run(Path::new(file_path), args, &strip_rules));
+    }
+
+    // Expand globs ourselves so quoted patterns work on shells that don't, like cmd.exe
+    let mut file_path = file_path.to_string();
+    if !Path::new(&file_path).exists() && batch::is_glob_pattern(&file_path) {
+        let files = batch::expand_glob(&file_path).map_err(|e| format!("Error: {}", e))?;
+        match files.as_slice() {
+            [] => {
+                let message = format!("Error: no files match '{}'", file_path);
+                return Err(Failure { message, code: EXIT_NOT_FOUND });
+            }
+            [single] => file_path = single.to_string_lossy().into_owned(),
+            _ => return Ok(batch::run_files(&files, &batch::glob_root(&file_path), args, &strip_rules)),
         }
-    };
-    
-    // Get info and clone it before reading frame (to avoid borrowing issues)
-    let info = reader.info();
-    let width = info.width;
-    let height = info.height;
-    let color_type = info.color_type;
-    let bit_depth = info.bit_depth;
-    let bytes_per_pixel = info.bytes_per_pixel();
-    let trns = info.trns.as_ref().map(|cow| cow.to_vec());
-    
-    // Allocate buffer for image data
-    // Calculate buffer size: width * height * bytes_per_pixel
-    let buffer_size = (width as usize) * (height as usize) * bytes_per_pixel;
-    let mut buf = vec![0; buffer_size];
-    
-    // Read image data
-    match reader.next_frame(&mut buf) {
-        Ok(_) => {},
-        Err(e) => {
-            eprintln!("Error reading image data: {}", e);
-            std::process::exit(1);
-        }
-    }
-    
-    // Add randomized noise to each pixel
-    add_randomized_noise(&mut buf, color_type);
-    
-    // Crop image to 88% of original size (keeping top-left portion)
-    let (new_width, new_height, cropped_buf) = crop_image(&buf, width, height, bytes_per_pixel);
-    
-    // Basic image information
-    println!("Width: {} pixels", width);
-    println!("Height: {} pixels", height);
-    println!("Color type: {:?}", color_type);
-    println!("Bit depth: {:?}", bit_depth);
-    println!("Bytes per pixel: {}", bytes_per_pixel);
-    
-    // Manually parse PNG file to read chunks
-    let mut file = match File::open(path) {
-        Ok(f) => f,
-        Err(e) => {
-            eprintln!("Error opening file: {}", e);
-            std::process::exit(1);
+    }
+
+    if args.fix_crc {
+        repair::run(&file_path, args)?;
+        return Ok(true);
+    }
+    if args.summary_only {
+        return Ok(match summary::summarize_file(&file_path) {
+            Ok(line) => {
+                println!("{}", line);
+                true
+            }
+            Err(reason) => {
+                eprintln!("{}: {}", file_path, reason);
+                false
+            }
+        });
+    }
+    if args.verify_only {
+        return Ok(match verify::verify_file(&file_path) {
+            Ok(()) => {
+                println!("OK");
+                true
+            }
+            Err(reason) => {
+                println!("FAILED: {}", reason);
+                false
+            }
+        });
+    }
+
+    let output = match &args.output_template {
+        Some(template) => {
+            let source = if file_path == "-" { STDIN_NAME } else { &file_path };
+            let target = batch::templated_output(template, Path::new(source), 1, !args.dry_run)
+                .map_err(|e| format!("Error creating output directory: {}", e))?;
+            Some(target)
         }
+        None => args.output.clone(),
     };
-    
-    // Skip PNG signature (8 bytes)
-    let mut signature = [0u8; 8];
-    if let Err(_) = file.read_exact(&mut signature) {
-        println!("Could not read PNG signature.");
-    } else {
-        // Verify PNG signature
-        let png_signature = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
-        if signature != png_signature {
-            println!("Warning: File does not have a valid PNG signature.");
-        }
-    }
-    
-    println!("\n=== Summary ===");
-    println!("File: {}", file_path);
-    println!("Original dimensions: {}x{}", width, height);
-    println!("Cropped dimensions: {}x{}", new_width, new_height);
-    println!("Color format: {:?} at {:?} bits", color_type, bit_depth);
-    
-    // Create output file path with "-unpeeled" before extension
-    let output_path = create_output_path(path);
-    println!("\n=== Writing Output Image ===");
-    println!("Output file: {}", output_path.display());
-    
-    // Write the cropped image to the new file
-    match write_png_image(&output_path, new_width, new_height, color_type, bit_depth, &trns, &cropped_buf) {
-        Ok(_) => {
-            println!("Successfully wrote image to: {}", output_path.display());
-        }
-        Err(e) => {
-            eprintln!("Error writing output image: {}", e);
-            std::process::exit(1);
-        }
+    let mut json_report = None;
+    let result = process_file(&file_path, output.as_deref(), args, &strip_rules, true, &mut json_report);
+    if let Some(report) = json_report {
+        println!("{}", report);
     }
+    let done = result?;
+    if args.timing {
+        timing::print(&done.timings);
+    }
+    Ok(true)
 }
 
-fn crop_image(
-    buf: &[u8],
-    original_width: u32,
-    original_height: u32,
-    bytes_per_pixel: usize,
-) -> (u32, u32, Vec<u8>) {
-    // Calculate new dimensions (88% of original, rounded down)
-    let new_width = (original_width as f64 * 0.88) as u32;
-    let new_height = (original_height as f64 * 0.88) as u32;
-    
-    // Calculate row size in bytes
-    let original_row_size = (original_width as usize) * bytes_per_pixel;
-    let new_row_size = (new_width as usize) * bytes_per_pixel;
-    
-    // Allocate buffer for cropped image
-    let mut cropped_buf = Vec::with_capacity((new_width as usize) * (new_height as usize) * bytes_per_pixel);
-    
-    // Extract top-left portion: rows 0 to new_height-1, columns 0 to new_width-1
-    for row in 0..new_height as usize {
-        let start_idx = row * original_row_size;
-        let end_idx = start_idx + new_row_size;
-        cropped_buf.extend_from_slice(&buf[start_idx..end_idx]);
-    }
-    
-    (new_width, new_height, cropped_buf)
+/// What was written for one input file; no output path for metadata-only and
+/// dry runs.
+pub struct Processed {
+    pub output_path: Option<PathBuf>,
+    pub original: (u32, u32),
+    pub cropped: Option<(u32, u32)>,
+    /// The `--hash` digest of the source pixels
+    pub pixel_hash: Option<String>,
+    /// The `--phash` difference hash of the source pixels
+    pub perceptual_hash: Option<u64>,
+    /// The `--stego-check` bit-plane scores of the source pixels
+    pub stego: Option<Vec<ChannelEntropy>>,
+    /// The `--polyglot-check` findings
+    pub polyglot: Option<Vec<EmbeddedSignature>>,
+    /// Time spent in each phase, for `--timing`
+    pub timings: Timings,
 }
 
-fn add_randomized_noise(buf: &mut [u8], color_type: ColorType) {
-    let mut rng = rand::thread_rng();
-    
-    match color_type {
-        ColorType::Rgb => {
-            // RGB: 3 bytes per pixel (R, G, B)
-            for pixel in buf.chunks_exact_mut(3) {
-                // Randomly select R (0), G (1), or B (2)
-                let channel = rng.gen_range(0..3);
-                // Randomly add or subtract 1
-                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
-                let new_value = pixel[channel] as i16 + change;
-                pixel[channel] = new_value.clamp(0, 255) as u8;
-            }
+/// Read, report on, and re-encode one PNG.
+///
+/// `output` takes the place of `--output` so batch mode can direct each file
+/// separately. With `report` unset, only warnings are printed; `--quiet`
+/// keeps just the confirmation of files written. The `--json` report is
+/// left in `json_report` for the caller to print, even if processing then
+/// fails, so batch mode can print reports in input order. Errors are
+/// returned as complete messages for the caller to print.
+pub fn process_file(
+    file_path: &str,
+    output: Option<&Path>,
+    args: &Args,
+    strip_rules: &StripRules,
+    report: bool,
+    json_report: &mut Option<Value>,
+) -> Result<Processed, Failure> {
+    let started = Instant::now();
+    let mut timings = Timings::default();
+    let json_output = args.json;
+    let human_report = report && !json_output && !args.quiet;
+    let confirm_writes = report && !json_output;
+    let quiet_report = confirm_writes && args.quiet;
+    let path = Path::new(file_path);
+    // "-" reads the PNG from stdin; there is no file on disk to describe or overwrite
+    let from_stdin = file_path == "-";
+
+    if from_stdin {
+        if args.in_place {
+            return Err("Error: --in-place cannot be used when reading from stdin".to_string().into());
         }
-        ColorType::Rgba => {
-            // RGBA: 4 bytes per pixel (R, G, B, A)
-            for pixel in buf.chunks_exact_mut(4) {
-                // Randomly select R (0), G (1), or B (2) - skip Alpha (3)
-                let channel = rng.gen_range(0..3);
-                // Randomly add or subtract 1
-                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
-                let new_value = pixel[channel] as i16 + change;
-                pixel[channel] = new_value.clamp(0, 255) as u8;
-            }
+    } else if !path.exists() {
+        let message = format!("Error: File '{}' does not exist", file_path);
+        return Err(Failure { message, code: EXIT_NOT_FOUND });
+    }
+
+    if human_report {
+        if !from_stdin {
+            report::print_file_metadata(path);
         }
-        ColorType::Grayscale => {
-            // Grayscale: 1 byte per pixel
-            for pixel in buf.iter_mut() {
-                // Randomly add or subtract 1
-                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
-                let new_value = *pixel as i16 + change;
-                *pixel = new_value.clamp(0, 255) as u8;
-            }
+        println!("\n{}", style::header("PNG Image Metadata"));
+    }
+
+    // Decode the image and walk its chunks; metadata-only runs skip the pixel data
+    let with_pixels = args.needs_pixels();
+    let bytes = if from_stdin { read_stdin() } else { std::fs::read(path).map_err(UnpeelError::from) };
+    timings.read = started.elapsed();
+    let decoded = bytes.and_then(|bytes| {
+        // Batch runs have their own progress bar
+        let spinner = progress::decode_spinner(file_path, bytes.len() as u64, report && with_pixels && !args.quiet);
+        let read = Png::read_timed(&bytes, with_pixels.then_some(args.max_bytes));
+        spinner.finish_and_clear();
+        let (png, read_timings) = read?;
+        Ok((png, read_timings, bytes))
+    });
+    let (mut png, read_timings, bytes) = decoded.map_err(|e| match e {
+        UnpeelError::NotPng(_) => Failure::reading(format!("Error: {}", e), &e),
+        e => Failure::reading(format!("Error reading PNG: {}", e), &e),
+    })?;
+    debug!(
+        "{}: {} bytes, {} chunks, read in {:?}",
+        file_path,
+        bytes.len(),
+        png.chunks.len(),
+        started.elapsed()
+    );
+    for chunk in &png.chunks {
+        trace!("{}: {} chunk at offset {}, {} bytes", file_path, chunk.type_str(), chunk.offset, chunk.data.len());
+    }
+    if png.is_unusually_large() {
+        warn!(
+            "{}x{} is over {} megapixels; the header may be corrupt or crafted",
+            png.width,
+            png.height,
+            LARGE_PIXEL_COUNT / 1_000_000
+        );
+    }
+    timings.decode = read_timings.decode;
+    timings.chunk_parse = read_timings.chunk_parse;
+    // Hash the pixels as decoded, before noise or any conversion touches them
+    let pixel_hash = args.hash.then(|| png.pixel_sha256());
+    let perceptual_hash = if args.phash { difference_hash(&png) } else { None };
+    let stego = if args.stego_check { lsb_entropy(&png) } else { None };
+    if args.stego_check && stego.is_none() {
+        warn!("--stego-check needs 8- or 16-bit samples; {:?}-bit image not checked", png.bit_depth);
+    }
+    let polyglot = args.polyglot_check.then(|| find_embedded_formats(&bytes, &png.chunks));
+
+    if json_output {
+        let mut report = json::build_report(path, &png);
+        if let Some(hash) = &pixel_hash {
+            report["image"]["pixel_sha256"] = hash.as_str().into();
+        }
+        if let Some(hash) = perceptual_hash {
+            report["image"]["dhash"] = format!("{:016x}", hash).into();
         }
-        ColorType::GrayscaleAlpha => {
-            // GrayscaleAlpha: 2 bytes per pixel (G, A)
-            for pixel in buf.chunks_exact_mut(2) {
-                // Modify the grayscale channel (0), skip Alpha (1)
-                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
-                let new_value = pixel[0] as i16 + change;
-                pixel[0] = new_value.clamp(0, 255) as u8;
+        if let Some(scores) = &stego {
+            report["stego"] = json::stego_report(scores);
+        }
+        if let Some(found) = &polyglot {
+            report["polyglot"] = json::polyglot_report(found);
+        }
+        *json_report = Some(report);
+    } else if human_report {
+        report::print_image_metadata(&png);
+        if args.preview {
+            report::print_preview(&png);
+        }
+        report::print_chunks(&png, args.verbose, args.text_limit());
+        report::print_descriptive_metadata(&png, args.text_limit());
+    }
+    if args.chunk_map && report && !json_output {
+        report::print_chunk_map(&png);
+    }
+
+    // Verify every chunk's stored CRC against its contents
+    let mut crc_failures = 0;
+    for chunk in verify::crc_failures(&png) {
+        crc_failures += 1;
+        warn!(
+            "CRC mismatch in chunk {} at offset {} (expected 0x{:08X}, got 0x{:08X})",
+            chunk.type_str(),
+            chunk.offset,
+            chunk.crc,
+            chunk.computed_crc()
+        );
+    }
+    // Compare the decompressed image data with what the header implies
+    let idat_problem = verify::idat_problem(&png);
+    if let Some(problem) = &idat_problem {
+        warn!("IDAT data decompresses to {}", problem);
+    }
+    // Not a spec violation as such, so even --strict only warns
+    if args.strict && !png.trailing.is_empty() {
+        warn!("{} bytes of data follow the IEND chunk", png.trailing.len());
+    }
+    if let Some(stats) = png.idat_stats().filter(|stats| stats.is_fragmented()) {
+        warn!(
+            "image data is fragmented into {} IDAT chunks averaging {:.0} bytes; --recompress writes a single IDAT",
+            stats.count,
+            stats.average()
+        );
+    }
+    if args.strict && idat_problem.is_some() {
+        return Err(Failure::strict("Error: IDAT data size does not match the image header (--strict)".to_string()));
+    }
+    if args.strict && crc_failures > 0 {
+        return Err(Failure::strict(format!("Error: {} chunk(s) failed CRC validation (--strict)", crc_failures)));
+    }
+
+    // Check chunk ordering and multiplicity against the spec
+    let violations = validate_chunk_order(&png.chunks, png.color_type);
+    if human_report {
+        report::print_validation(&violations);
+    }
+    if args.strict && !violations.is_empty() {
+        return Err(Failure::strict(format!("Error: {} spec violation(s) found (--strict)", violations.len())));
+    }
+
+    if let Some(target) = &args.dump_icc {
+        let size = extract::dump_icc_profile(&png, target, args.force)?;
+        if human_report {
+            println!();
+        }
+        if confirm_writes {
+            println!("Wrote {}-byte ICC profile to: {}", size, target.display());
+        }
+    }
+    if let Some(target) = &args.dump_text {
+        let count = extract::dump_text(&png, target, args.force)?;
+        if human_report {
+            println!();
+        }
+        if confirm_writes {
+            println!("Wrote {} text entries to: {}", count, target.display());
+        }
+    }
+    if let Some(target) = &args.dump_trailing {
+        let size = extract::dump_trailing(&png, target, args.force)?;
+        if human_report {
+            println!();
+        }
+        if confirm_writes {
+            println!("Wrote {} trailing bytes to: {}", size, target.display());
+        }
+    }
+    if let Some((type_code, target)) = args.extract_chunk() {
+        let written = extract::dump_chunks(&png, type_code, target, args.force)?;
+        if human_report {
+            println!();
+        }
+        for (path, size) in written.iter().filter(|_| confirm_writes) {
+            println!("Wrote {}-byte {} chunk to: {}", size, String::from_utf8_lossy(&type_code), path.display());
+        }
+    }
+    if let Some(target) = &args.to_ppm {
+        let format = extract::write_pnm(&png, target, args.force)?;
+        if human_report {
+            println!();
+        }
+        if confirm_writes {
+            println!("Wrote {}x{} {} to: {}", png.width, png.height, format, target.display());
+        }
+    }
+    if let Some(index) = args.extract_frame {
+        let source = if from_stdin { Path::new(STDIN_NAME) } else { path };
+        let target = extract::frame_output_path(source, index, output);
+        extract::write_frame(&png, &bytes, index, &target, strip_rules, args.max_bytes, args.force)?;
+        if human_report {
+            println!();
+        }
+        if confirm_writes {
+            println!("Wrote frame {} to: {}", index, target.display());
+        }
+    }
+
+    match &stego {
+        Some(scores) if human_report => report::print_stego_check(scores),
+        Some(scores) if quiet_report => println!("{}", report::stego_check_line(scores)),
+        _ => {}
+    }
+    match &polyglot {
+        Some(found) if human_report => report::print_polyglot_check(found),
+        Some(found) if quiet_report => {
+            for signature in found {
+                println!("{}", report::polyglot_line(signature));
             }
         }
-        ColorType::Indexed => {
-            // Indexed: 1 byte per pixel (palette index)
-            // For indexed color, we modify the palette index value
-            // This will change which color from the palette is used
-            for pixel in buf.iter_mut() {
-                // Randomly add or subtract 1 from the palette index
-                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
-                let new_value = *pixel as i16 + change;
-                *pixel = new_value.clamp(0, 255) as u8;
+        _ => {}
+    }
+    // Only images with an alpha channel get the section, and only when their pixels were decoded
+    if let (Some(stats), true) = (alpha_stats(&png), human_report) {
+        report::print_alpha_analysis(&stats, png.color_type);
+    }
+
+    // --quiet only silences the report; hashes that were asked for still print
+    if quiet_report {
+        if let Some(hash) = &pixel_hash {
+            println!("pixel sha256 {}", hash);
+        }
+        if let Some(hash) = perceptual_hash {
+            println!("dhash {:016x}", hash);
+        }
+    }
+    if human_report {
+        println!("\n{}", style::header("Summary"));
+        println!("{} {}", style::key("File"), file_path);
+        report::print_info(&png.info());
+        match declared_color_space(&png.chunks) {
+            Some(color_space) => println!("{} {}", style::key("Color space"), color_space),
+            None => println!("{} unspecified (viewers assume sRGB)", style::key("Color space")),
+        }
+        if let Some(hash) = &pixel_hash {
+            println!("{} {}", style::key("Pixel SHA-256"), hash);
+        }
+        if let Some(hash) = perceptual_hash {
+            println!("{} {:016x}", style::key("Perceptual hash (dHash)"), hash);
+        }
+        report::print_compression(&png);
+        report::print_size_breakdown(&png);
+    }
+
+    if !args.writes_output() {
+        if human_report && args.extract_frame.is_none() {
+            if args.metadata_only {
+                println!("\nMetadata only: no output image written");
+            } else {
+                println!("\nInspected only: no output image written (--write writes a copy)");
             }
         }
+        timings.total = started.elapsed();
+        return Ok(Processed {
+            output_path: None,
+            original: (png.width, png.height),
+            cropped: None,
+            pixel_hash,
+            perceptual_hash,
+            stego,
+            polyglot,
+            timings,
+        });
     }
-}
 
-fn create_output_path(input_path: &Path) -> PathBuf {
-    let mut output_path = input_path.to_path_buf();
-    
-    // Get the file stem and extension
-    if let Some(file_stem) = input_path.file_stem() {
-        if let Some(extension) = input_path.extension() {
-            // Create new filename with "-unpeeled" before extension
-            let new_filename = format!("{}-unpeeled.{}", 
-                file_stem.to_string_lossy(), 
-                extension.to_string_lossy());
-            output_path.set_file_name(new_filename);
+    // Taken before convert_pixels, which adds noise in place
+    let source_hash = args.only_metadata.then(|| pixel_hash.clone().unwrap_or_else(|| png.pixel_sha256()));
+    let convert_start = Instant::now();
+    let out = convert::convert_pixels(&mut png, args)?;
+    timings.convert = convert_start.elapsed();
+
+    // Write next to the input with "-unpeeled" before the extension unless --output says otherwise
+    // --in-place writes to a temp file first and renames it over the input once complete
+    // Stdin output is named as if the input were "stdin.png"
+    // Other formats swap the extension of generated names, but not of an explicit --output file
+    let format = args.output_format();
+    let mut output_path = if args.in_place {
+        path.to_path_buf()
+    } else if from_stdin {
+        resolve_output_path(Path::new(STDIN_NAME), output)
+    } else {
+        resolve_output_path(path, output)
+    };
+    if format != OutputFormat::Png && output.is_none_or(Path::is_dir) {
+        output_path.set_extension(format.extension());
+    }
+    let write_path = if args.in_place { in_place_temp_path(path) } else { output_path.clone() };
+
+    if human_report {
+        let label = if args.resize_target().is_some() {
+            "Resized dimensions"
+        } else if args.only_metadata {
+            "Output dimensions"
         } else {
-            // No extension, just append "-unpeeled"
-            let new_filename = format!("{}-unpeeled", file_stem.to_string_lossy());
-            output_path.set_file_name(new_filename);
+            "Cropped dimensions"
+        };
+        println!("{} {}x{}", style::key(label), out.width, out.height);
+        if out.format_changed(&png) {
+            println!("{} {:?} at {:?} bits", style::key("Output color format"), out.color_type, out.bit_depth);
+        }
+        if let (Some((plte, _)), Some(source_colors)) = (&out.palette, out.source_colors) {
+            println!("{} {} colors (from {})", style::key("Output palette"), plte.len() / 3, source_colors);
         }
-    } else {
-        // Fallback: append "-unpeeled" to the path
-        let mut path_str = input_path.to_string_lossy().to_string();
-        path_str.push_str("-unpeeled");
-        output_path = PathBuf::from(path_str);
     }
-    
-    output_path
-}
 
-fn write_png_image(
-    output_path: &Path,
-    width: u32,
-    height: u32,
-    color_type: png::ColorType,
-    bit_depth: png::BitDepth,
-    trns: &Option<Vec<u8>>,
-    image_data: &[u8],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::create(output_path)?;
-    let writer = BufWriter::new(file);
-    
-    let mut encoder = Encoder::new(writer, width, height);
-    
-    // Set only essential metadata: color type and bit depth
-    encoder.set_color(color_type);
-    encoder.set_depth(bit_depth);
-    
-    // Only include transparency (tRNS) if:
-    // 1. The color type supports transparency via tRNS (Grayscale, RGB, or Indexed)
-    // 2. AND transparency data actually exists
-    // Note: GrayscaleAlpha and RgbAlpha have transparency built into pixel data, so tRNS is not needed
-    match color_type {
-        png::ColorType::Grayscale | png::ColorType::Rgb | png::ColorType::Indexed => {
-            // These color types can use tRNS for transparency
-            if let Some(trns_data) = trns {
-                if !trns_data.is_empty() {
-                    encoder.set_trns(trns_data.clone());
+    let kept_chunks = convert::output_chunks(&png, &out, args, strip_rules);
+    let encode_options = args.encode_options(png.interlaced);
+
+    if human_report {
+        let title = if args.dry_run { "Output Image (dry run)" } else { "Writing Output Image" };
+        println!("\n{}", style::header(title));
+        println!("{} {}", style::key("Output file"), output_path.display());
+        if format == OutputFormat::Png {
+            report::print_carried_metadata(&png.chunks, &kept_chunks);
+            if args.strip_all_but_critical {
+                report::print_removed_chunks(&png.chunks, &kept_chunks);
+            }
+            if !args.set_text.is_empty() {
+                let keywords: Vec<&str> = args.set_text.iter().map(|(key, _)| key.as_str()).collect();
+                println!("{} {}", style::key("Text chunks set"), keywords.join(", "));
+            }
+            if let Some((x_dpi, y_dpi)) = args.set_phys {
+                println!("{} {}x{} DPI", style::key("Print resolution set"), x_dpi, y_dpi);
+            }
+            if args.canonical {
+                println!("{} canonical", style::key("Chunk order"));
+            }
+            match (png.interlaced, encode_options.interlaced) {
+                (true, true) => println!("{} Adam7, preserved", style::key("Interlacing")),
+                (true, false) if args.canonical && args.interlace.is_none() => {
+                    println!("{} dropped (--canonical)", style::key("Interlacing"))
                 }
+                (true, false) => println!("{} dropped (--interlace none)", style::key("Interlacing")),
+                (false, true) => println!("{} Adam7 (--interlace adam7)", style::key("Interlacing")),
+                (false, false) => {}
             }
+        } else {
+            let encoding = match args.quality {
+                Some(quality) => format!("lossy at quality {}", quality),
+                None => "lossless".to_string(),
+            };
+            println!("{} WebP, {}; metadata is not carried over", style::key("Format"), encoding);
+        }
+    }
+
+    if args.dry_run {
+        if human_report {
+            println!("\nDry run: no output image written");
+        }
+        timings.total = started.elapsed();
+        return Ok(Processed {
+            output_path: None,
+            original: (png.width, png.height),
+            cropped: Some((out.width, out.height)),
+            pixel_hash,
+            perceptual_hash,
+            stego,
+            polyglot,
+            timings,
+        });
+    }
+
+    // Write the cropped image to the new file
+    let encode_start = Instant::now();
+    let written = match format {
+        OutputFormat::Png => write_png_image(
+            &write_path,
+            out.width,
+            out.height,
+            out.color_type,
+            out.bit_depth,
+            &kept_chunks,
+            &out.pixels,
+            &encode_options,
+        ),
+        #[cfg(feature = "webp")]
+        OutputFormat::Webp => write_webp_image(
+            &write_path,
+            out.width,
+            out.height,
+            out.color_type,
+            out.bit_depth,
+            &kept_chunks,
+            &out.pixels,
+            args.quality,
+            encode_options.overwrite,
+        ),
+    };
+    let written = written.and_then(|_| {
+        let output_size = std::fs::metadata(&write_path)?.len();
+        if args.in_place {
+            replace_original(path, &write_path)?;
         }
-        _ => {
-            // GrayscaleAlpha and RgbAlpha have transparency in pixel data, tRNS not needed
+        Ok(output_size)
+    });
+    timings.encode = encode_start.elapsed();
+    let output_size = match written {
+        Ok(size) => size,
+        Err(e) => {
+            if args.in_place {
+                let _ = std::fs::remove_file(&write_path);
+            }
+            let message = match &e {
+                UnpeelError::IoError(e) => format!("Error: {}", write_error(&write_path, e)),
+                e => format!("Error writing output image: {}", e),
+            };
+            return Err(Failure { message, code: EXIT_WRITE });
         }
+    };
+    info!("{}: wrote {} bytes to {}", file_path, output_size, output_path.display());
+    if confirm_writes {
+        println!("Successfully wrote image to: {}", output_path.display());
     }
-    
-    // Write header (creates IHDR chunk)
-    let mut writer = encoder.write_header()?;
-    // Write image data (creates IDAT chunks)
-    writer.write_image_data(image_data)?;
-    // Writer automatically closes with IEND chunk
-    
-    Ok(())
+    if let Some(source_hash) = source_hash {
+        // Decode what was written to prove no step along the way was lossy
+        match Png::from_path_with_limit(&output_path, args.max_bytes) {
+            Ok(written) if written.pixel_sha256() == source_hash => {
+                if human_report {
+                    println!("Pixels unchanged \u{2713}");
+                }
+            }
+            Ok(_) => warn!("the pixels written to {} differ from the source", output_path.display()),
+            Err(e) => warn!("could not decode {} to check its pixels: {}", output_path.display(), e),
+        }
+    }
+    if let (Some((x_dpi, y_dpi)), OutputFormat::Png) = (args.set_phys, format) {
+        // Read the pHYs back to confirm the rounding to pixels per meter keeps the requested DPI
+        match written_dpi(&output_path) {
+            Ok(Some(dpi)) if dpi == (x_dpi, y_dpi) => {
+                if human_report {
+                    println!("Print resolution {}x{} DPI \u{2713}", x_dpi, y_dpi);
+                }
+            }
+            Ok(Some((x, y))) => warn!("the pHYs written to {} reads back as {}x{} DPI", output_path.display(), x, y),
+            Ok(None) => warn!("no valid pHYs chunk found in {}", output_path.display()),
+            Err(e) => warn!("could not read {} to check its pHYs: {}", output_path.display(), e),
+        }
+    }
+    if human_report {
+        if format == OutputFormat::Png {
+            report::print_strip_savings(bytes.len() as u64, output_size, &png.chunks, &kept_chunks);
+        } else {
+            report::print_size_change(bytes.len() as u64, output_size);
+        }
+    }
+
+    timings.total = started.elapsed();
+    Ok(Processed {
+        output_path: Some(output_path),
+        original: (png.width, png.height),
+        cropped: Some((out.width, out.height)),
+        pixel_hash,
+        perceptual_hash,
+        stego,
+        polyglot,
+        timings,
+    })
+}
+
+/// Why writing `path` failed, pointing at --force when the file was only
+/// refused because it already exists.
+pub fn write_error(path: &Path, e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::AlreadyExists => format!("{} already exists; use --force to overwrite it", path.display()),
+        _ => format!("could not write {}: {}", path.display(), e),
+    }
+}
+
+fn read_stdin() -> Result<Vec<u8>, UnpeelError> {
+    let mut bytes = Vec::new();
+    io::stdin().lock().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The DPI stored in the pHYs chunk of the PNG at `path`, rounded to whole
+/// dots, or `None` when it has no pHYs in meters.
+fn written_dpi(path: &Path) -> Result<Option<(u32, u32)>, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let png = Png::metadata_from_bytes(&bytes).map_err(|e| e.to_string())?;
+    let phys = png.chunks.iter().find(|c| &c.type_code == b"pHYs").and_then(|c| decode::parse_phys(&c.data));
+    Ok(phys.and_then(|phys| phys.dpi()).map(|(x, y)| (x.round() as u32, y.round() as u32)))
 }
@@ -0,0 +1,663 @@
+use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use png::{AdaptiveFilterType, Encoder, FilterType, ScaledFloat, SourceChromaticities, SrgbRenderingIntent};
+
+use crate::chunk::{parse_chunks, Chunk, PNG_SIGNATURE};
+use crate::error::UnpeelError;
+use crate::interlace::adam7_passes;
+
+/// Largest IDAT chunk written for interlaced output.
+const IDAT_CHUNK_SIZE: usize = 1 << 20;
+/// Largest chunk payload the PNG spec allows.
+const MAX_CHUNK_SIZE: usize = (1 << 31) - 1;
+
+/// Build the default output path: `<stem>-unpeeled.<ext>` next to the input.
+pub fn create_output_path(input_path: &Path) -> PathBuf {
+    let mut output_path = input_path.to_path_buf();
+
+    // Get the file stem and extension
+    if let Some(file_stem) = input_path.file_stem() {
+        if let Some(extension) = input_path.extension() {
+            // Create new filename with "-unpeeled" before extension
+            let new_filename = format!("{}-unpeeled.{}",
+                file_stem.to_string_lossy(),
+                extension.to_string_lossy());
+            output_path.set_file_name(new_filename);
+        } else {
+            // No extension, just append "-unpeeled"
+            let new_filename = format!("{}-unpeeled", file_stem.to_string_lossy());
+            output_path.set_file_name(new_filename);
+        }
+    } else {
+        // Fallback: append "-unpeeled" to the path
+        let mut path_str = input_path.to_string_lossy().to_string();
+        path_str.push_str("-unpeeled");
+        output_path = PathBuf::from(path_str);
+    }
+
+    output_path
+}
+
+/// Resolve where to write the output for `input_path`.
+///
+/// With no `--output`, this is `create_output_path`. An existing directory
+/// receives `<stem>-unpeeled.<ext>`; anything else is the full target filename.
+pub fn resolve_output_path(input_path: &Path, output: Option<&Path>) -> PathBuf {
+    match output {
+        None => create_output_path(input_path),
+        Some(dir) if dir.is_dir() => {
+            let generated = create_output_path(input_path);
+            match generated.file_name() {
+                Some(name) => dir.join(name),
+                None => dir.join(generated),
+            }
+        }
+        Some(target) => target.to_path_buf(),
+    }
+}
+
+/// An `--output-template` such as `out/{index:04}.png`, naming each output
+/// from its input. Placeholders:
+///
+/// - `{stem}`: the input file name without its extension
+/// - `{ext}`: the input's extension, without the dot
+/// - `{parent}`: the name of the directory holding the input
+/// - `{index}`: the input's 1-based position in the run; `{index:04}` pads it
+///   with zeros to four digits
+///
+/// `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Stem,
+    Ext,
+    Parent,
+    /// The index, zero-padded to this many digits
+    Index(usize),
+}
+
+impl OutputTemplate {
+    /// Parse a template, rejecting unknown placeholders and unbalanced braces.
+    pub fn parse(template: &str) -> Result<OutputTemplate, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| format!("unclosed '{{' in '{}'", template))?;
+                    let part = match &rest[..end] {
+                        "stem" => TemplatePart::Stem,
+                        "ext" => TemplatePart::Ext,
+                        "parent" => TemplatePart::Parent,
+                        "index" => TemplatePart::Index(0),
+                        name => {
+                            let width = name.strip_prefix("index:").and_then(|width| width.parse().ok());
+                            let known = "{stem}, {ext}, {parent}, {index} or {index:04}";
+                            TemplatePart::Index(width.ok_or_else(|| {
+                                format!("unknown placeholder '{{{}}}'; expected {}", name, known)
+                            })?)
+                        }
+                    };
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    parts.push(part);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("unmatched '}}' in '{}'; write '}}}}' for a literal brace", template)),
+                c => text.push(c),
+            }
+        }
+        parts.push(TemplatePart::Text(text));
+        parts.retain(|part| part != &TemplatePart::Text(String::new()));
+        if parts.is_empty() {
+            return Err("the output template is empty".to_string());
+        }
+        Ok(OutputTemplate { parts })
+    }
+
+    /// The output path for `input`, the `index`th file of the run.
+    pub fn render(&self, input: &Path, index: usize) -> PathBuf {
+        let lossy = |name: Option<&std::ffi::OsStr>| name.map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => path.push_str(text),
+                TemplatePart::Stem => path.push_str(&lossy(input.file_stem())),
+                TemplatePart::Ext => path.push_str(&lossy(input.extension())),
+                TemplatePart::Parent => path.push_str(&lossy(input.parent().and_then(Path::file_name))),
+                TemplatePart::Index(width) => path.push_str(&format!("{:0width$}", index, width = width)),
+            }
+        }
+        PathBuf::from(path)
+    }
+}
+
+/// Temporary file next to `path` for `--in-place` writes; being in the same
+/// directory keeps the final rename on one filesystem, and therefore atomic.
+pub fn in_place_temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.unpeel-tmp", name))
+}
+
+/// Open `path` for writing. Unless `overwrite` is set, an existing file is
+/// an `AlreadyExists` error, decided atomically as the file is created.
+/// Devices and pipes such as `/dev/null` are always written to.
+pub fn create_output_file(path: &Path, overwrite: bool) -> io::Result<File> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && (overwrite || !path.is_file()) => {
+            OpenOptions::new().write(true).truncate(true).open(path)
+        }
+        result => result,
+    }
+}
+
+/// `fs::write` through `create_output_file`.
+pub fn write_output_file(path: &Path, contents: &[u8], overwrite: bool) -> io::Result<()> {
+    create_output_file(path, overwrite)?.write_all(contents)
+}
+
+/// Atomically replace `original` with the fully written `temp` file.
+///
+/// The original's permissions and modification time are carried over on a
+/// best-effort basis, since not every filesystem supports setting them.
+pub fn replace_original(original: &Path, temp: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(original)?;
+    let file = OpenOptions::new().write(true).open(temp)?;
+    file.sync_all()?;
+    if let Ok(modified) = metadata.modified() {
+        let _ = file.set_modified(modified);
+    }
+    drop(file);
+    let _ = fs::set_permissions(temp, metadata.permissions());
+    fs::rename(temp, original)
+}
+
+/// Row filter for recompression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    /// Pick the best filter per row
+    Adaptive,
+}
+
+/// Image format to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    /// Only available with the `webp` feature
+    #[cfg(feature = "webp")]
+    Webp,
+}
+
+impl OutputFormat {
+    /// File extension for output paths generated in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            #[cfg(feature = "webp")]
+            OutputFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Encoder settings for `write_png_image`. Fields left as `None` keep the
+/// `png` crate's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct EncodeOptions {
+    pub filter: Option<Filter>,
+    pub compression: Option<png::Compression>,
+    /// Write the image Adam7-interlaced
+    pub interlaced: bool,
+    /// Keyword and Latin-1 text pairs to add as tEXt chunks
+    pub text: Vec<(String, String)>,
+    /// Write all image data in one IDAT chunk. Non-interlaced output always
+    /// is; interlaced output is otherwise split into 1 MiB chunks.
+    pub single_idat: bool,
+    /// Write chunks in `canonical_rank` order instead of the source's, so
+    /// the output doesn't depend on how the input was laid out
+    pub canonical: bool,
+    /// Replace a file already at the output path instead of failing with
+    /// `AlreadyExists`
+    pub overwrite: bool,
+}
+
+/// Encode `image_data` as a PNG at `output_path`, carrying over `chunks`.
+///
+/// `chunks` is the list of source chunks to keep, normally produced by
+/// `select_chunks`. The pixels are always re-encoded, so source IHDR, IDAT and
+/// IEND are regenerated; PLTE and tRNS are taken from `chunks` when the output
+/// color type allows them. Valid gAMA, cHRM and sRGB chunks are handed to the
+/// encoder, which gives sRGB precedence over gAMA and cHRM as the spec
+/// requires. The tEXt entries in `options` are added regardless of `chunks`.
+/// Every other chunk in `chunks` is copied verbatim and
+/// placed in the same position relative to PLTE and IDAT as in the source.
+/// Anything not in `chunks` is not written, which is how stripping works.
+/// With `options.canonical` the source positions are ignored and every chunk
+/// is written in `canonical_rank` order instead.
+#[allow(clippy::too_many_arguments)]
+pub fn write_png_image(
+    output_path: &Path,
+    width: u32,
+    height: u32,
+    color_type: png::ColorType,
+    bit_depth: png::BitDepth,
+    chunks: &[Chunk],
+    image_data: &[u8],
+    options: &EncodeOptions,
+) -> Result<(), UnpeelError> {
+    // Encode pixels into memory first so source chunks can be spliced in around them
+    let encode_at = |width: u32, height: u32, data: &[u8]| {
+        encode(width, height, color_type, bit_depth, chunks, data, options)
+    };
+    let mut encoded = parse_chunks(encode_at(width, height, image_data)?.as_slice())?;
+    if options.interlaced {
+        // Filter each pass as an image of its own, then compress them as one stream
+        let bits_per_pixel = color_type.samples() * bit_depth as usize;
+        let mut filtered = Vec::new();
+        for (pass_width, pass_height, pass) in adam7_passes(image_data, width, height, bits_per_pixel) {
+            if pass_width > 0 && pass_height > 0 {
+                filtered.extend(filtered_rows(&encode_at(pass_width, pass_height, &pass)?)?);
+            }
+        }
+        let chunk_size = if options.single_idat { MAX_CHUNK_SIZE } else { IDAT_CHUNK_SIZE };
+        encoded = with_interlaced_data(encoded, &filtered, options.compression, chunk_size)?;
+    }
+
+    // Group the remaining source chunks by where they sat relative to PLTE and IDAT
+    let color_space = ColorSpace::from_chunks(chunks);
+    let mut before_plte = Vec::new();
+    let mut before_idat = Vec::new();
+    let mut after_idat = Vec::new();
+    let mut seen_plte = false;
+    let mut seen_idat = false;
+    for chunk in chunks {
+        match &chunk.type_code {
+            b"PLTE" => seen_plte = true,
+            b"IDAT" => seen_idat = true,
+            b"IHDR" | b"IEND" | b"tRNS" => {}
+            _ if color_space.covers(chunk) => {}
+            _ if seen_idat => after_idat.push(chunk),
+            _ if seen_plte => before_idat.push(chunk),
+            _ => before_plte.push(chunk),
+        }
+    }
+
+    let mut ordered: Vec<&Chunk> = Vec::new();
+    let mut wrote_before_idat = false;
+    for chunk in &encoded {
+        match &chunk.type_code {
+            b"IDAT" if !wrote_before_idat => {
+                ordered.extend(&before_idat);
+                wrote_before_idat = true;
+            }
+            b"IEND" => ordered.extend(&after_idat),
+            _ => {}
+        }
+        ordered.push(chunk);
+        if &chunk.type_code == b"IHDR" {
+            ordered.extend(&before_plte);
+        }
+    }
+    if options.canonical {
+        // IDAT chunks compare equal so the stable sort keeps the image data in sequence
+        ordered.sort_by(|a, b| {
+            canonical_rank(a).cmp(&canonical_rank(b)).then_with(|| match &a.type_code {
+                b"IDAT" => Ordering::Equal,
+                _ => (a.type_code, &a.data).cmp(&(b.type_code, &b.data)),
+            })
+        });
+    }
+
+    let file = create_output_file(output_path, options.overwrite)?;
+    let mut out = BufWriter::new(file);
+    out.write_all(&PNG_SIGNATURE)?;
+    for chunk in ordered {
+        chunk.write_to(&mut out)?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Where `chunk` goes in canonical output: IHDR, the color space chunks,
+/// PLTE, tRNS, other ancillary chunks, text, IDAT and IEND. Chunks of the same
+/// rank are ordered by type and then contents, which sorts text by keyword.
+fn canonical_rank(chunk: &Chunk) -> u8 {
+    match &chunk.type_code {
+        b"IHDR" => 0,
+        b"cICP" | b"iCCP" | b"sRGB" | b"gAMA" | b"cHRM" | b"mDCV" | b"cLLI" | b"sBIT" => 1,
+        b"PLTE" => 2,
+        b"tRNS" => 3,
+        b"tEXt" | b"zTXt" | b"iTXt" => 5,
+        b"IDAT" => 6,
+        b"IEND" => 7,
+        _ => 4,
+    }
+}
+
+/// Encode `image_data` with the `png` crate, setting PLTE and tRNS from
+/// `chunks` where the color type allows.
+fn encode(
+    width: u32,
+    height: u32,
+    color_type: png::ColorType,
+    bit_depth: png::BitDepth,
+    chunks: &[Chunk],
+    image_data: &[u8],
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, UnpeelError> {
+    let mut encoded = Vec::new();
+    let mut encoder = Encoder::new(&mut encoded, width, height);
+
+    // Set only essential metadata: color type and bit depth
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+
+    match options.filter {
+        Some(Filter::Adaptive) => encoder.set_adaptive_filter(AdaptiveFilterType::Adaptive),
+        Some(filter) => encoder.set_filter(match filter {
+            Filter::Sub => FilterType::Sub,
+            Filter::Up => FilterType::Up,
+            Filter::Avg => FilterType::Avg,
+            Filter::Paeth => FilterType::Paeth,
+            _ => FilterType::NoFilter,
+        }),
+        None => {}
+    }
+    if let Some(compression) = options.compression {
+        encoder.set_compression(compression);
+    }
+
+    let find = |code: &[u8; 4]| chunks.iter().find(|c| &c.type_code == code);
+
+    // PLTE is required for indexed color and allowed (as a suggestion) for truecolor
+    if let Some(plte) = find(b"PLTE") {
+        if matches!(color_type, png::ColorType::Indexed | png::ColorType::Rgb | png::ColorType::Rgba) {
+            encoder.set_palette(plte.data.clone());
+        }
+    }
+
+    // Only include transparency (tRNS) if:
+    // 1. The color type supports transparency via tRNS (Grayscale, RGB, or Indexed)
+    // 2. AND transparency data actually exists
+    // Note: GrayscaleAlpha and RgbAlpha have transparency built into pixel data, so tRNS is not needed
+    match color_type {
+        png::ColorType::Grayscale | png::ColorType::Rgb | png::ColorType::Indexed => {
+            // These color types can use tRNS for transparency
+            if let Some(trns) = find(b"tRNS") {
+                if !trns.data.is_empty() {
+                    encoder.set_trns(trns.data.clone());
+                }
+            }
+        }
+        _ => {
+            // GrayscaleAlpha and RgbAlpha have transparency in pixel data, tRNS not needed
+        }
+    }
+
+    for (keyword, text) in &options.text {
+        encoder.add_text_chunk(keyword.clone(), text.clone())?;
+    }
+
+    let color_space = ColorSpace::from_chunks(chunks);
+    if let Some(gamma) = color_space.gamma {
+        encoder.set_source_gamma(gamma);
+    }
+    if let Some(chromaticities) = color_space.chromaticities {
+        encoder.set_source_chromaticities(chromaticities);
+    }
+    // With sRGB set, gAMA and cHRM are only written if they match sRGB's own values
+    if let Some(intent) = color_space.srgb {
+        encoder.set_source_srgb(intent);
+    }
+
+    // Write header (creates IHDR chunk)
+    let mut writer = encoder.write_header()?;
+    // Write image data (creates IDAT chunks)
+    writer.write_image_data(image_data)?;
+    // Finishing writes the IEND chunk
+    writer.finish()?;
+
+    Ok(encoded)
+}
+
+/// The color space chunks that can be written through the encoder, decoded
+/// from the source chunks. Malformed chunks are left as `None`.
+#[derive(Debug, Default)]
+struct ColorSpace {
+    gamma: Option<ScaledFloat>,
+    chromaticities: Option<SourceChromaticities>,
+    srgb: Option<SrgbRenderingIntent>,
+}
+
+impl ColorSpace {
+    fn from_chunks(chunks: &[Chunk]) -> ColorSpace {
+        let mut color_space = ColorSpace::default();
+        for chunk in chunks {
+            let data = &chunk.data;
+            let scaled = |i: usize| {
+                ScaledFloat::from_scaled(u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]))
+            };
+            match &chunk.type_code {
+                b"gAMA" if data.len() == 4 => color_space.gamma = Some(scaled(0)),
+                b"cHRM" if data.len() == 32 => {
+                    color_space.chromaticities = Some(SourceChromaticities {
+                        white: (scaled(0), scaled(4)),
+                        red: (scaled(8), scaled(12)),
+                        green: (scaled(16), scaled(20)),
+                        blue: (scaled(24), scaled(28)),
+                    })
+                }
+                b"sRGB" if data.len() == 1 => {
+                    color_space.srgb = match data[0] {
+                        0 => Some(SrgbRenderingIntent::Perceptual),
+                        1 => Some(SrgbRenderingIntent::RelativeColorimetric),
+                        2 => Some(SrgbRenderingIntent::Saturation),
+                        3 => Some(SrgbRenderingIntent::AbsoluteColorimetric),
+                        _ => None,
+                    }
+                }
+                _ => {}
+            }
+        }
+        color_space
+    }
+
+    /// Whether the encoder writes `chunk` from these values, so it must not
+    /// also be copied verbatim.
+    fn covers(&self, chunk: &Chunk) -> bool {
+        match &chunk.type_code {
+            b"gAMA" => self.gamma.is_some(),
+            b"cHRM" => self.chromaticities.is_some(),
+            b"sRGB" => self.srgb.is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// The filtered scanlines of an encoded, non-interlaced PNG: its IDAT data
+/// decompressed.
+fn filtered_rows(png: &[u8]) -> Result<Vec<u8>, UnpeelError> {
+    let idat: Vec<u8> = parse_chunks(png)?
+        .into_iter()
+        .filter(|c| &c.type_code == b"IDAT")
+        .flat_map(|c| c.data)
+        .collect();
+    let mut rows = Vec::new();
+    ZlibDecoder::new(idat.as_slice()).read_to_end(&mut rows)?;
+    Ok(rows)
+}
+
+/// Replace the IDAT chunks of `encoded` with the compressed interlaced
+/// `filtered` data, split into IDAT chunks of at most `chunk_size` bytes, and
+/// set the interlace method in IHDR to Adam7.
+fn with_interlaced_data(
+    encoded: Vec<Chunk>,
+    filtered: &[u8],
+    compression: Option<png::Compression>,
+    chunk_size: usize,
+) -> io::Result<Vec<Chunk>> {
+    let level = match compression {
+        Some(png::Compression::Fast) => flate2::Compression::fast(),
+        Some(png::Compression::Best) => flate2::Compression::best(),
+        _ => flate2::Compression::default(),
+    };
+    let mut zlib = ZlibEncoder::new(Vec::new(), level);
+    zlib.write_all(filtered)?;
+    let data = zlib.finish()?;
+
+    let mut chunks = Vec::new();
+    for chunk in encoded {
+        match &chunk.type_code {
+            b"IHDR" => {
+                let mut ihdr = chunk.data;
+                ihdr[12] = 1;
+                chunks.push(Chunk::new(*b"IHDR", ihdr));
+            }
+            b"IDAT" if chunks.iter().any(|c: &Chunk| &c.type_code == b"IDAT") => {}
+            b"IDAT" => chunks.extend(data.chunks(chunk_size).map(|part| Chunk::new(*b"IDAT", part.to_vec()))),
+            _ => chunks.push(chunk),
+        }
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Png;
+    use png::{BitDepth, ColorType};
+
+    /// Write `pixels` to a temporary file with `options` and decode it again.
+    fn round_trip(
+        name: &str,
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: BitDepth,
+        pixels: &[u8],
+        options: &EncodeOptions,
+    ) -> Png {
+        let path = std::env::temp_dir().join(format!("unpeel-test-{}-{}.png", std::process::id(), name));
+        let palette = Chunk::new(*b"PLTE", (0..=255).flat_map(|i| [i, 255 - i, i / 2]).collect());
+        let chunks = if color_type == ColorType::Indexed { vec![palette] } else { Vec::new() };
+        write_png_image(&path, width, height, color_type, bit_depth, &chunks, pixels, options).unwrap();
+        let png = Png::from_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        png
+    }
+
+    /// Distinct bytes for every position, so misplaced pixels are caught.
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 37 + 11) as u8).collect()
+    }
+
+    #[test]
+    fn interlaced_output_keeps_every_pixel() {
+        let interlaced = EncodeOptions { interlaced: true, ..EncodeOptions::default() };
+        // Odd sizes leave some Adam7 passes partly or entirely empty
+        let cases = [
+            ("rgb8", 13, 11, ColorType::Rgb, BitDepth::Eight, 13 * 11 * 3),
+            ("rgba16", 5, 3, ColorType::Rgba, BitDepth::Sixteen, 5 * 3 * 8),
+            ("gray1", 17, 9, ColorType::Grayscale, BitDepth::One, 3 * 9),
+            ("indexed4", 7, 7, ColorType::Indexed, BitDepth::Four, 4 * 7),
+            ("single", 1, 1, ColorType::Grayscale, BitDepth::Eight, 1),
+        ];
+        for (name, width, height, color_type, bit_depth, len) in cases {
+            let mut pixels = pattern(len);
+            if bit_depth == BitDepth::One {
+                // Clear the padding bits at the end of each 17-pixel row
+                pixels.chunks_mut(3).for_each(|row| row[2] &= 0x80);
+            } else if name == "indexed4" {
+                pixels.chunks_mut(4).for_each(|row| row[3] &= 0xF0);
+            }
+            let png = round_trip(name, width, height, color_type, bit_depth, &pixels, &interlaced);
+
+            assert!(png.interlaced, "{} was not written interlaced", name);
+            // The decode buffer allows a whole byte per pixel, so packed rows only fill its start
+            assert_eq!(png.pixels[..pixels.len()], pixels, "{} pixels changed", name);
+        }
+    }
+
+    #[test]
+    fn interlaced_input_round_trips_to_interlaced_output() {
+        let pixels = pattern(9 * 6 * 3);
+        let interlaced = EncodeOptions { interlaced: true, ..EncodeOptions::default() };
+        let first = round_trip("first", 9, 6, ColorType::Rgb, BitDepth::Eight, &pixels, &interlaced);
+        // As the CLI does: keep whatever the source used
+        let options = EncodeOptions { interlaced: first.interlaced, ..EncodeOptions::default() };
+        let second =
+            round_trip("second", first.width, first.height, first.color_type, first.bit_depth, &first.pixels, &options);
+
+        assert!(second.interlaced);
+        assert_eq!(second.pixels, pixels);
+    }
+
+    #[test]
+    fn non_interlaced_output_by_default() {
+        let pixels = pattern(4 * 4);
+        let png = round_trip("plain", 4, 4, ColorType::Grayscale, BitDepth::Eight, &pixels, &EncodeOptions::default());
+
+        assert!(!png.interlaced);
+        assert_eq!(png.pixels, pixels);
+    }
+
+    #[test]
+    fn pixel_hash_ignores_encoding_and_buffer_slack() {
+        let pixels = pattern(3 * 9);
+        let plain =
+            round_trip("hash-plain", 17, 9, ColorType::Grayscale, BitDepth::One, &pixels, &EncodeOptions::default());
+        let recompressed = EncodeOptions {
+            interlaced: true,
+            filter: Some(Filter::Paeth),
+            compression: Some(png::Compression::Best),
+            ..EncodeOptions::default()
+        };
+        let mut other = round_trip("hash-adam7", 17, 9, ColorType::Grayscale, BitDepth::One, &pixels, &recompressed);
+        // The pattern sets the 7 padding bits ending each row, which Adam7 output drops
+        assert_ne!(plain.pixels, other.pixels);
+        assert_eq!(plain.pixel_sha256(), other.pixel_sha256());
+
+        // Neither are bytes past the last row
+        other.pixels.extend_from_slice(&[0xAB; 16]);
+        assert_eq!(plain.pixel_sha256(), other.pixel_sha256());
+    }
+
+    #[test]
+    fn existing_output_is_only_replaced_with_overwrite() {
+        let path = std::env::temp_dir().join(format!("unpeel-test-{}-existing.bin", std::process::id()));
+        fs::write(&path, b"earlier run").unwrap();
+
+        let refused = write_output_file(&path, b"new", false).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"earlier run");
+
+        write_output_file(&path, b"new", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+//! Perceptual "difference hash" (dHash) for spotting near-duplicate images.
+
+use png::ColorType;
+
+use crate::pixels::{cell_range, sample};
+use crate::Png;
+
+const HASH_WIDTH: usize = 9;
+const HASH_HEIGHT: usize = 8;
+
+/// Compute the 64-bit difference hash of the decoded image.
+///
+/// The image is box-filtered down to 9x8 grayscale, and each bit records
+/// whether a cell is darker than its right-hand neighbour, row by row with
+/// the first comparison in the most significant bit. Resaving, recompressing
+/// or lightly resizing an image barely changes the hash, so the Hamming
+/// distance between two hashes (the number of differing bits) estimates how
+/// visually similar the images are: 0 is a near-certain duplicate, and more
+/// than about 10 usually means different pictures.
+///
+/// Alpha is ignored. Returns `None` if the pixels were not decoded.
+pub fn difference_hash(png: &Png) -> Option<u64> {
+    let luma = luma_samples(png)?;
+    let (width, height) = (png.width as usize, png.height as usize);
+
+    let mut cells = [[0.0f64; HASH_WIDTH]; HASH_HEIGHT];
+    for (cy, row) in cells.iter_mut().enumerate() {
+        let (y0, y1) = cell_range(cy, HASH_HEIGHT, height);
+        for (cx, cell) in row.iter_mut().enumerate() {
+            let (x0, x1) = cell_range(cx, HASH_WIDTH, width);
+            let mut sum = 0.0;
+            for y in y0..y1 {
+                sum += luma[y * width + x0..y * width + x1].iter().sum::<f64>();
+            }
+            *cell = sum / ((y1 - y0) * (x1 - x0)) as f64;
+        }
+    }
+
+    let mut hash = 0u64;
+    for row in &cells {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | (pair[0] < pair[1]) as u64;
+        }
+    }
+    Some(hash)
+}
+
+/// One Rec. 709 luma value per pixel, scaled to 0.0..=1.0, for every color
+/// type and bit depth. Indexed pixels are looked up in the PLTE chunk.
+fn luma_samples(png: &Png) -> Option<Vec<f64>> {
+    let (width, height) = (png.width as usize, png.height as usize);
+    let bits = png.bit_depth as usize;
+    let samples = png.color_type.samples();
+    let stride = (width * samples * bits).div_ceil(8);
+    if png.pixels.len() < stride * height || width == 0 || height == 0 {
+        return None;
+    }
+    let max = ((1u32 << bits) - 1) as f64;
+    let palette = png.chunks.iter().find(|c| &c.type_code == b"PLTE").map(|c| c.data.as_slice()).unwrap_or_default();
+
+    let mut luma = Vec::with_capacity(width * height);
+    for row in png.pixels.chunks_exact(stride).take(height) {
+        let at = |i: usize| sample(row, i, png.bit_depth) as f64;
+        for x in 0..width {
+            let first = x * samples;
+            let value = match png.color_type {
+                ColorType::Grayscale | ColorType::GrayscaleAlpha => at(first) / max,
+                ColorType::Rgb | ColorType::Rgba => rec709(at(first), at(first + 1), at(first + 2)) / max,
+                ColorType::Indexed => {
+                    let entry = at(first) as usize * 3;
+                    match palette.get(entry..entry + 3) {
+                        Some(rgb) => rec709(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64) / 255.0,
+                        None => 0.0,
+                    }
+                }
+            };
+            luma.push(value);
+        }
+    }
+    Some(luma)
+}
+
+fn rec709(r: f64, g: f64, b: f64) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
@@ -0,0 +1,230 @@
+//! Sample unpacking shared by the pixel consumers that need plain RGB(A)
+//! color rather than PNG's packed rows.
+
+use std::ops::Deref;
+
+use png::{BitDepth, ColorType};
+
+use crate::error::UnpeelError;
+
+/// The samples of one pixel from `Png::pixel`, one per channel in PNG order
+/// (e.g. R, G, B, A), at their stored values: `0..=1` for 1-bit images,
+/// `0..=65535` for 16-bit ones, and the palette index for indexed color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pixel {
+    samples: [u16; 4],
+    len: usize,
+}
+
+impl Deref for Pixel {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.samples[..self.len]
+    }
+}
+
+impl Pixel {
+    /// The pixel at column `x` of a packed `row` with `channels` samples per pixel.
+    pub(crate) fn from_row(row: &[u8], x: usize, channels: usize, bit_depth: BitDepth) -> Pixel {
+        let mut samples = [0; 4];
+        for (channel, value) in samples.iter_mut().take(channels).enumerate() {
+            *value = sample(row, x * channels + channel, bit_depth);
+        }
+        Pixel { samples, len: channels }
+    }
+}
+
+/// Sample `i` of a packed row. Sub-byte samples are packed most significant
+/// bits first; 16-bit ones are big-endian.
+pub(crate) fn sample(row: &[u8], i: usize, bit_depth: BitDepth) -> u16 {
+    match bit_depth {
+        BitDepth::Sixteen => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]),
+        BitDepth::Eight => row[i] as u16,
+        _ => {
+            let bits = bit_depth as usize;
+            let bit = i * bits;
+            ((row[bit / 8] >> (8 - bits - bit % 8)) as u16) & ((1 << bits) - 1)
+        }
+    }
+}
+
+/// Every sample of every pixel as a number, one per `u16`, whatever the bit
+/// depth. Rows past `height` are ignored.
+pub(crate) fn sample_values(pixels: &[u8], width: usize, height: usize, channels: usize, bit_depth: BitDepth) -> Vec<u16> {
+    let stride = (width * channels * bit_depth as usize).div_ceil(8);
+    let rows = pixels.chunks_exact(stride).take(height);
+    rows.flat_map(|row| (0..width * channels).map(move |i| sample(row, i, bit_depth))).collect()
+}
+
+/// Expand packed 1, 2 and 4-bit rows to one byte per sample, so code that
+/// works on whole bytes can handle them. Values keep their stored range,
+/// e.g. `0..=1` for 1-bit. 8 and 16-bit rows are already byte-aligned and
+/// come back unchanged. `repack_samples` reverses this.
+pub fn unpack_samples(buf: &[u8], width: u32, bit_depth: BitDepth, color_type: ColorType) -> Vec<u8> {
+    let per_row = width as usize * color_type.samples();
+    if bit_depth as u8 >= 8 || per_row == 0 {
+        return buf.to_vec();
+    }
+    let stride = (per_row * bit_depth as usize).div_ceil(8);
+    buf.chunks_exact(stride).flat_map(|row| (0..per_row).map(move |i| sample(row, i, bit_depth) as u8)).collect()
+}
+
+/// Pack one-byte-per-sample rows from `unpack_samples` back into 1, 2 or
+/// 4-bit rows, padding each row to a whole byte with zeros. Bits above the
+/// depth are dropped. 8 and 16-bit rows come back unchanged.
+pub fn repack_samples(samples: &[u8], width: u32, bit_depth: BitDepth, color_type: ColorType) -> Vec<u8> {
+    let per_row = width as usize * color_type.samples();
+    if bit_depth as u8 >= 8 || per_row == 0 {
+        return samples.to_vec();
+    }
+    let bits = bit_depth as usize;
+    let mask = (1u8 << bits) - 1;
+    let stride = (per_row * bits).div_ceil(8);
+    let mut out = Vec::with_capacity(samples.len().div_ceil(per_row) * stride);
+    for row in samples.chunks_exact(per_row) {
+        let mut packed = vec![0u8; stride];
+        for (i, &sample) in row.iter().enumerate() {
+            packed[i * bits / 8] |= (sample & mask) << (8 - bits - (i * bits) % 8);
+        }
+        out.extend(packed);
+    }
+    out
+}
+
+/// Unpacked pixels: one RGBA value per pixel, every sample scaled to
+/// `0..=max`.
+pub(crate) struct Rgba {
+    pub pixels: Vec<[u16; 4]>,
+    /// 65535 for 16-bit sources, 255 for everything else
+    pub max: u16,
+    /// Whether the source had an alpha channel or tRNS
+    pub has_alpha: bool,
+}
+
+/// Unpack PNG rows of any color type and bit depth into RGBA.
+///
+/// Grayscale is widened to RGB, indexed pixels are looked up in `palette`
+/// (raw PLTE data), and sub-byte samples are scaled up to 8 bits. tRNS makes
+/// matching color-keyed pixels, or palette entries, transparent.
+pub(crate) fn to_rgba(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+) -> Result<Rgba, UnpeelError> {
+    let (width, height) = (width as usize, height as usize);
+    let bits = bit_depth as usize;
+    let samples = color_type.samples();
+    let stride = (width * samples * bits).div_ceil(8);
+    if pixels.len() < stride * height {
+        return Err(UnpeelError::MalformedChunk("image data is shorter than the header implies".to_string()));
+    }
+    let has_alpha = matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) || trns.is_some();
+    let max: u16 = if bit_depth == BitDepth::Sixteen { 0xFFFF } else { 0xFF };
+    // tRNS holds 16-bit sample values for grayscale and RGB color keys
+    let key: Option<Vec<u32>> = match color_type {
+        ColorType::Grayscale | ColorType::Rgb => {
+            trns.map(|t| t.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as u32).collect())
+        }
+        _ => None,
+    };
+    // Sub-byte samples are scaled up to 8 bits
+    let scale = |value: u32| -> u16 {
+        match bit_depth {
+            BitDepth::Sixteen | BitDepth::Eight => value as u16,
+            _ => (value * 255 / ((1 << bits) - 1)) as u16,
+        }
+    };
+
+    let mut out = Vec::with_capacity(width * height);
+    for row in pixels.chunks_exact(stride).take(height) {
+        let sample = |i: usize| sample(row, i, bit_depth) as u32;
+        for x in 0..width {
+            let first = x * samples;
+            let raw: Vec<u32> = (first..first + samples).map(sample).collect();
+            let mut pixel = match color_type {
+                ColorType::Indexed => {
+                    let index = raw[0] as usize;
+                    let rgb = palette.get(index * 3..index * 3 + 3).unwrap_or(&[0, 0, 0]);
+                    let alpha = trns.and_then(|t| t.get(index).copied()).unwrap_or(255);
+                    [rgb[0] as u16, rgb[1] as u16, rgb[2] as u16, alpha as u16]
+                }
+                ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                    let gray = scale(raw[0]);
+                    [gray, gray, gray, raw.get(1).map_or(max, |&a| scale(a))]
+                }
+                ColorType::Rgb | ColorType::Rgba => {
+                    [scale(raw[0]), scale(raw[1]), scale(raw[2]), raw.get(3).map_or(max, |&a| scale(a))]
+                }
+            };
+            // A color-keyed pixel matching tRNS is fully transparent
+            if key.as_ref().is_some_and(|key| key.as_slice() == raw.as_slice()) {
+                pixel[3] = 0;
+            }
+            out.push(pixel);
+        }
+    }
+    Ok(Rgba { pixels: out, max, has_alpha })
+}
+
+/// Expand packed PNG pixels to 8-bit RGB, or RGBA when the image has an alpha
+/// channel or tRNS. 16-bit samples keep their high byte. Returns the pixels
+/// and whether they include alpha.
+pub(crate) fn to_rgb8(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+) -> Result<(Vec<u8>, bool), UnpeelError> {
+    let rgba = to_rgba(pixels, width, height, color_type, bit_depth, palette, trns)?;
+    let channels = if rgba.has_alpha { 4 } else { 3 };
+    let shift = if rgba.max == 0xFFFF { 8 } else { 0 };
+    let out = rgba.pixels.iter().flat_map(|pixel| pixel[..channels].iter().map(|&sample| (sample >> shift) as u8));
+    Ok((out.collect(), rgba.has_alpha))
+}
+
+/// The source pixels covered by cell `index` of `cells` when `size` pixels
+/// are box-filtered down to `cells`. Never empty, so images smaller than the
+/// grid still fill every cell.
+pub(crate) fn cell_range(index: usize, cells: usize, size: usize) -> (usize, usize) {
+    let start = (index * size / cells).min(size - 1);
+    let end = ((index + 1) * size / cells).max(start + 1);
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_bit_rows_round_trip() {
+        // Two 10-pixel rows: each takes two bytes, the last six bits padding
+        let packed = [0b1011_0010, 0b0100_0000, 0b0000_1111, 0b1100_0000];
+        let samples = unpack_samples(&packed, 10, BitDepth::One, ColorType::Grayscale);
+        assert_eq!(samples, [1, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(repack_samples(&samples, 10, BitDepth::One, ColorType::Grayscale), packed);
+    }
+
+    #[test]
+    fn four_bit_rows_round_trip() {
+        // Three 3-pixel indexed rows, each padded to two bytes
+        let packed = [0x01, 0x20, 0xAB, 0xC0, 0xF0, 0xF0];
+        let samples = unpack_samples(&packed, 3, BitDepth::Four, ColorType::Indexed);
+        assert_eq!(samples, [0x0, 0x1, 0x2, 0xA, 0xB, 0xC, 0xF, 0x0, 0xF]);
+        assert_eq!(repack_samples(&samples, 3, BitDepth::Four, ColorType::Indexed), packed);
+    }
+
+    #[test]
+    fn byte_aligned_rows_are_unchanged() {
+        let rgb16 = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+        assert_eq!(unpack_samples(&rgb16, 1, BitDepth::Sixteen, ColorType::Rgb), rgb16);
+        assert_eq!(repack_samples(&rgb16, 1, BitDepth::Sixteen, ColorType::Rgb), rgb16);
+    }
+}
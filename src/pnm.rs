@@ -0,0 +1,35 @@
+//! Raw decoded pixels as binary Netpbm files, for image-processing scripts.
+
+use png::ColorType;
+
+use crate::error::UnpeelError;
+use crate::pixels::to_rgba;
+use crate::Png;
+
+/// Encode the decoded image as a binary PGM (P5) for grayscale or a PPM (P6)
+/// for everything else.
+///
+/// Samples up to 8 bits are written with a maxval of 255 (sub-byte ones
+/// scaled up), 16-bit samples with 65535 in the big-endian order Netpbm
+/// requires. Indexed pixels are expanded through the palette. Netpbm has no
+/// alpha, so an alpha channel or tRNS is dropped.
+pub fn encode_pnm(png: &Png) -> Result<Vec<u8>, UnpeelError> {
+    let palette = png.chunks.iter().find(|c| &c.type_code == b"PLTE").map(|c| c.data.as_slice()).unwrap_or_default();
+    let rgba = to_rgba(&png.pixels, png.width, png.height, png.color_type, png.bit_depth, palette, None)?;
+    let (magic, channels) = match png.color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => ("P5", 1),
+        ColorType::Rgb | ColorType::Rgba | ColorType::Indexed => ("P6", 3),
+    };
+
+    let mut out = format!("{}\n{} {}\n{}\n", magic, png.width, png.height, rgba.max).into_bytes();
+    for pixel in &rgba.pixels {
+        for &sample in &pixel[..channels] {
+            if rgba.max > 0xFF {
+                out.extend_from_slice(&sample.to_be_bytes());
+            } else {
+                out.push(sample as u8);
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,52 @@
+//! Scanning a PNG's bytes for the signatures of other formats, the mark of a
+//! polyglot file that is also a valid ZIP or PDF.
+//!
+//! ZIP readers find their directory from the end of the file and PDF readers
+//! look for `%PDF` anywhere near the start, so either can hide in a text
+//! chunk or after IEND without disturbing PNG viewers.
+
+use crate::chunk::Chunk;
+
+/// Format names and the signatures that start them.
+const SIGNATURES: [(&str, &[u8]); 2] = [("ZIP", b"PK\x03\x04"), ("PDF", b"%PDF")];
+
+/// Another format's signature found in the file, from `find_embedded_formats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedSignature {
+    /// "ZIP" or "PDF"
+    pub format: &'static str,
+    /// Byte offset of the signature from the start of the file
+    pub offset: usize,
+    /// The chunk the signature sits in, or `None` when it is outside every
+    /// chunk, normally after IEND
+    pub chunk: Option<[u8; 4]>,
+}
+
+impl EmbeddedSignature {
+    /// Where the signature is, e.g. "after IEND" or "inside tEXt".
+    pub fn location(&self) -> String {
+        match self.chunk {
+            Some(type_code) => format!("inside {}", String::from_utf8_lossy(&type_code)),
+            None => "after IEND".to_string(),
+        }
+    }
+}
+
+/// Every ZIP local file header and PDF header in `bytes`, in file order,
+/// placed within `chunks`. Compressed image data can contain a signature by
+/// chance, so a lone hit inside IDAT is weak evidence.
+pub fn find_embedded_formats(bytes: &[u8], chunks: &[Chunk]) -> Vec<EmbeddedSignature> {
+    let mut found: Vec<EmbeddedSignature> = SIGNATURES
+        .iter()
+        .flat_map(|&(format, signature)| {
+            bytes.windows(signature.len()).enumerate().filter(move |(_, window)| *window == signature).map(
+                move |(offset, _)| {
+                    let chunk = chunks.iter().find(|c| (c.offset..c.offset + 12 + c.data.len()).contains(&offset));
+                    EmbeddedSignature { format, offset, chunk: chunk.map(|c| c.type_code) }
+                },
+            )
+        })
+        .collect();
+    found.sort_by_key(|signature| signature.offset);
+    found
+}
@@ -0,0 +1,43 @@
+//! Progress feedback for long runs, drawn on stderr.
+//!
+//! Bars are only drawn when the caller enables them (not under `--quiet`)
+//! and stdout is a terminal, so piped and scripted runs see exactly the
+//! output they did before.
+
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Inputs at least this large get a spinner while they decode.
+const LARGE_FILE_BYTES: u64 = 8 * 1024 * 1024;
+
+fn visible(enabled: bool) -> bool {
+    enabled && io::stdout().is_terminal()
+}
+
+/// A `files done / total` bar for batch runs.
+pub fn batch_bar(total: usize, enabled: bool) -> ProgressBar {
+    if !visible(enabled) {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total as u64);
+    if let Ok(style) = ProgressStyle::with_template("{bar:40} {pos}/{len} files ({elapsed})") {
+        bar.set_style(style);
+    }
+    // Keep the elapsed time moving while a large file is processed
+    bar.enable_steady_tick(Duration::from_millis(200));
+    bar
+}
+
+/// A spinner shown while a file of `size` bytes decodes, or a hidden one if
+/// the file is small enough to decode in a blink.
+pub fn decode_spinner(file_name: &str, size: u64, enabled: bool) -> ProgressBar {
+    if size < LARGE_FILE_BYTES || !visible(enabled) {
+        return ProgressBar::hidden();
+    }
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_message(format!("Decoding {} ({} MiB)", file_name, size / (1024 * 1024)));
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
@@ -0,0 +1,225 @@
+//! Median-cut color quantization, for writing images as indexed color.
+
+use std::collections::HashMap;
+
+/// Reduce `pixels` to a palette of at most `max_colors` RGBA entries.
+///
+/// Images with few enough distinct colors keep them exactly. Otherwise this
+/// is median cut: starting from one box holding every distinct color, the
+/// box with the widest range on any channel is split at the pixel-weighted
+/// median of that channel until there are `max_colors` boxes, and each box
+/// becomes the weighted average of its colors. Entries with any transparency
+/// come first so a tRNS chunk only needs to cover them.
+pub fn median_cut(pixels: &[[u8; 4]], max_colors: usize) -> Vec<[u8; 4]> {
+    let mut counts: HashMap<[u8; 4], u64> = HashMap::new();
+    for &pixel in pixels {
+        *counts.entry(pixel).or_default() += 1;
+    }
+    let mut colors: Vec<([u8; 4], u64)> = counts.into_iter().collect();
+    // Sorted so the result doesn't depend on hash order
+    colors.sort_unstable();
+
+    let mut palette: Vec<[u8; 4]> = if colors.len() <= max_colors {
+        colors.into_iter().map(|(color, _)| color).collect()
+    } else {
+        let mut boxes = vec![colors];
+        while boxes.len() < max_colors.max(1) {
+            // Split the splittable box with the widest channel range
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, colors)| colors.len() > 1)
+                .map(|(i, colors)| (i, widest_channel(colors)))
+                .max_by_key(|&(_, (_, range))| range);
+            let Some((index, (channel, _))) = widest else {
+                break;
+            };
+            let mut colors = boxes.swap_remove(index);
+            colors.sort_unstable_by_key(|(color, _)| color[channel]);
+            let total: u64 = colors.iter().map(|(_, count)| count).sum();
+            let mut seen = 0;
+            let mut split = colors.len() - 1;
+            for (i, (_, count)) in colors.iter().enumerate() {
+                seen += count;
+                if seen * 2 >= total {
+                    split = i + 1;
+                    break;
+                }
+            }
+            // Both halves must keep at least one color
+            let split = split.clamp(1, colors.len() - 1);
+            let upper = colors.split_off(split);
+            boxes.push(colors);
+            boxes.push(upper);
+        }
+        boxes.iter().map(|colors| average(colors)).collect()
+    };
+    palette.sort_by_key(|color| color[3] == 255);
+    palette
+}
+
+/// Index of the `palette` entry nearest to `color` by squared RGBA distance.
+pub fn nearest_index(palette: &[[u8; 4]], color: [u8; 4]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry, &color))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Map every pixel to its nearest entry in `palette`, caching repeated colors.
+pub fn map_to_palette(pixels: &[[u8; 4]], palette: &[[u8; 4]]) -> Vec<u8> {
+    let mut cache: HashMap<[u8; 4], u8> = HashMap::new();
+    pixels
+        .iter()
+        .map(|&pixel| *cache.entry(pixel).or_insert_with(|| nearest_index(palette, pixel) as u8))
+        .collect()
+}
+
+/// Map pixels to `palette` with Floyd-Steinberg error diffusion, so smooth
+/// gradients become a fine mix of neighbouring entries instead of bands.
+///
+/// Pixels are visited left to right, top to bottom, in rows of `width`.
+/// Each pixel's rounding error is passed on in the standard proportions: 7/16
+/// to the right, then 3/16, 5/16 and 1/16 to the row below (left, center,
+/// right). Error that would fall outside the image is dropped.
+pub fn dither_to_palette(pixels: &[[u8; 4]], width: usize, palette: &[[u8; 4]]) -> Vec<u8> {
+    let height = pixels.len().checked_div(width).unwrap_or_default();
+    let mut errors = vec![[0.0f32; 4]; pixels.len()];
+    let mut indices = Vec::with_capacity(pixels.len());
+    for (i, pixel) in pixels.iter().enumerate() {
+        let (x, y) = (i % width, i / width);
+        let mut wanted = [0u8; 4];
+        for channel in 0..4 {
+            wanted[channel] = (pixel[channel] as f32 + errors[i][channel]).round().clamp(0.0, 255.0) as u8;
+        }
+        let index = nearest_index(palette, wanted);
+        indices.push(index as u8);
+
+        let chosen = palette.get(index).copied().unwrap_or(wanted);
+        let error = [0, 1, 2, 3].map(|channel| wanted[channel] as f32 - chosen[channel] as f32);
+        let mut spread = |target: usize, weight: f32| {
+            for (sum, e) in errors[target].iter_mut().zip(error) {
+                *sum += e * weight;
+            }
+        };
+        if x + 1 < width {
+            spread(i + 1, 7.0 / 16.0);
+        }
+        if y + 1 < height {
+            if x > 0 {
+                spread(i + width - 1, 3.0 / 16.0);
+            }
+            spread(i + width, 5.0 / 16.0);
+            if x + 1 < width {
+                spread(i + width + 1, 1.0 / 16.0);
+            }
+        }
+    }
+    indices
+}
+
+/// PLTE and tRNS chunk data for `palette`; tRNS is `None` when every entry is
+/// opaque and otherwise stops after the last translucent entry.
+pub fn palette_chunks(palette: &[[u8; 4]]) -> (Vec<u8>, Option<Vec<u8>>) {
+    let plte = palette.iter().flat_map(|entry| [entry[0], entry[1], entry[2]]).collect();
+    let alphas: Vec<u8> = palette.iter().map(|entry| entry[3]).collect();
+    let trns = alphas.iter().rposition(|&alpha| alpha != 255).map(|last| alphas[..=last].to_vec());
+    (plte, trns)
+}
+
+fn distance(a: &[u8; 4], b: &[u8; 4]) -> u32 {
+    a.iter().zip(b).map(|(&x, &y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+/// The channel with the largest spread of values, and that spread.
+fn widest_channel(colors: &[([u8; 4], u64)]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| color[channel]);
+            let (min, max) = values.fold((u8::MAX, u8::MIN), |(min, max), v| (min.min(v), max.max(v)));
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+/// Pixel-weighted mean color of a box.
+fn average(colors: &[([u8; 4], u64)]) -> [u8; 4] {
+    let total: u64 = colors.iter().map(|(_, count)| count).sum();
+    let mut sums = [0u64; 4];
+    for (color, count) in colors {
+        for (sum, &value) in sums.iter_mut().zip(color) {
+            *sum += value as u64 * count;
+        }
+    }
+    sums.map(|sum| ((sum + total / 2) / total.max(1)) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 16;
+    const BLACK_AND_WHITE: [[u8; 4]; 2] = [[0, 0, 0, 255], [255, 255, 255, 255]];
+
+    /// A gray ramp from black at the left edge to white at the right.
+    fn gradient() -> Vec<[u8; 4]> {
+        (0..WIDTH * HEIGHT)
+            .map(|i| {
+                let gray = ((i % WIDTH) * 255 / (WIDTH - 1)) as u8;
+                [gray, gray, gray, 255]
+            })
+            .collect()
+    }
+
+    /// Mean absolute difference between each column's average brightness
+    /// after mapping and the gradient it came from.
+    fn column_error(indices: &[u8], palette: &[[u8; 4]]) -> f64 {
+        let source = gradient();
+        let total: f64 = (0..WIDTH)
+            .map(|x| {
+                let mapped: f64 = (0..HEIGHT).map(|y| palette[indices[y * WIDTH + x] as usize][0] as f64).sum();
+                (mapped / HEIGHT as f64 - source[x][0] as f64).abs()
+            })
+            .sum();
+        total / WIDTH as f64
+    }
+
+    #[test]
+    fn nearest_color_mapping_bands_a_gradient() {
+        let indices = map_to_palette(&gradient(), &BLACK_AND_WHITE);
+        // Every row is the same hard split: black up to the middle, white after
+        for row in indices.chunks(WIDTH) {
+            assert_eq!(row.windows(2).filter(|pair| pair[0] != pair[1]).count(), 1);
+        }
+        assert!(column_error(&indices, &BLACK_AND_WHITE) > 50.0);
+    }
+
+    #[test]
+    fn dithering_keeps_a_gradient_average_brightness() {
+        let banded = map_to_palette(&gradient(), &BLACK_AND_WHITE);
+        let dithered = dither_to_palette(&gradient(), WIDTH, &BLACK_AND_WHITE);
+        let error = column_error(&dithered, &BLACK_AND_WHITE);
+        assert!(error < 25.0, "dithered columns are {} levels off on average", error);
+        assert!(error < column_error(&banded, &BLACK_AND_WHITE) / 2.0);
+        // The ends of the ramp are already palette colors and stay solid
+        assert!(dithered.chunks(WIDTH).all(|row| row[0] == 0 && row[WIDTH - 1] == 1));
+    }
+
+    #[test]
+    fn median_cut_keeps_few_colors_exactly() {
+        let pixels = [[10, 20, 30, 255], [10, 20, 30, 255], [200, 0, 0, 128]];
+        let palette = median_cut(&pixels, 256);
+        // Translucent entries come first
+        assert_eq!(palette, vec![[200, 0, 0, 128], [10, 20, 30, 255]]);
+        assert_eq!(palette_chunks(&palette).1, Some(vec![128]));
+    }
+
+    #[test]
+    fn median_cut_limits_the_palette_size() {
+        let palette = median_cut(&gradient(), 4);
+        assert_eq!(palette.len(), 4);
+    }
+}
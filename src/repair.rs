@@ -0,0 +1,69 @@
+//! `--fix-crc`: rewrite a PNG with its chunk CRCs recomputed and nothing
+//! else changed. Pixels are never decoded.
+
+use std::fs;
+use std::path::Path;
+
+use unpeel::{in_place_temp_path, repair_crcs, replace_original, resolve_output_path, write_output_file, UnpeelError};
+
+use crate::cli::Args;
+use crate::{write_error, Failure, EXIT_NOT_FOUND, EXIT_WRITE, STDIN_NAME};
+
+/// Repair the CRCs of one file, or stdin for `-`, listing each chunk fixed
+/// and writing the result to the usual output path. Nothing is written when
+/// every CRC is already correct.
+pub fn run(file_path: &str, args: &Args) -> Result<(), Failure> {
+    let path = Path::new(file_path);
+    let from_stdin = file_path == "-";
+    if from_stdin && args.in_place {
+        return Err("Error: --in-place cannot be used when reading from stdin".to_string().into());
+    }
+    if !from_stdin && !path.exists() {
+        let message = format!("Error: File '{}' does not exist", file_path);
+        return Err(Failure { message, code: EXIT_NOT_FOUND });
+    }
+    let bytes = if from_stdin { crate::read_stdin() } else { fs::read(path).map_err(UnpeelError::from) };
+    let (repaired, fixed) = bytes
+        .and_then(|bytes| repair_crcs(&bytes))
+        .map_err(|e| Failure::reading(format!("Error reading PNG: {}", e), &e))?;
+
+    for chunk in fixed.iter().filter(|_| !args.quiet) {
+        println!(
+            "Fixed CRC of {} chunk at offset {} (was 0x{:08X}, now 0x{:08X})",
+            chunk.type_str(),
+            chunk.offset,
+            chunk.crc,
+            chunk.computed_crc()
+        );
+    }
+    if fixed.is_empty() {
+        if !args.quiet {
+            println!("Every chunk CRC is already valid; nothing written");
+        }
+        return Ok(());
+    }
+
+    let output_path = if args.in_place {
+        path.to_path_buf()
+    } else {
+        resolve_output_path(if from_stdin { Path::new(STDIN_NAME) } else { path }, args.output.as_deref())
+    };
+    if args.dry_run {
+        if !args.quiet {
+            println!("Dry run: {} not written", output_path.display());
+        }
+        return Ok(());
+    }
+    let written = if args.in_place {
+        let temp = in_place_temp_path(path);
+        fs::write(&temp, &repaired).and_then(|_| replace_original(path, &temp)).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+    } else {
+        write_output_file(&output_path, &repaired, args.force)
+    };
+    let target = if args.in_place { path } else { &output_path };
+    written.map_err(|e| Failure { message: format!("Error: {}", write_error(target, &e)), code: EXIT_WRITE })?;
+    println!("Successfully wrote image to: {}", output_path.display());
+    Ok(())
+}
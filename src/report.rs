@@ -0,0 +1,730 @@
+//! Human-readable report printed to stdout.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use log::warn;
+use png::ColorType;
+
+use unpeel::decode;
+use unpeel::exif::Exif;
+use unpeel::{thumbnail, AlphaStats, ChannelEntropy, Chunk, EmbeddedSignature, Png, PngInfo, PNG_SIGNATURE};
+
+use crate::style;
+
+pub fn print_file_metadata(path: &Path) {
+    println!("{}", style::header("File System Metadata"));
+    if let Ok(metadata) = std::fs::metadata(path) {
+        println!("{} {} bytes", style::key("File size"), metadata.len());
+        if let Ok(modified) = metadata.modified() {
+            println!("{} {:?}", style::key("Last modified"), modified);
+        }
+        if let Ok(created) = metadata.created() {
+            println!("{} {:?}", style::key("Created"), created);
+        }
+    }
+}
+
+pub fn print_image_metadata(png: &Png) {
+    // Basic image information
+    println!("{} {} pixels", style::key("Width"), png.width);
+    println!("{} {} pixels", style::key("Height"), png.height);
+    println!("{} {:?}", style::key("Color type"), png.color_type);
+    println!("{} {:?}", style::key("Bit depth"), png.bit_depth);
+    println!("{} {}", style::key("Bytes per pixel"), png.bytes_per_pixel());
+    let interlace = if png.interlaced { "Adam7" } else { "None (progressive not used)" };
+    println!("{} {}", style::key("Interlaced"), interlace);
+}
+
+/// The `Display` lines of `info`, the start of the Summary section, with
+/// their labels in bold.
+pub fn print_info(info: &PngInfo) {
+    for line in info.to_string().lines() {
+        match line.split_once(": ") {
+            Some((label, value)) => println!("{} {}", style::key(label), value),
+            None => println!("{}", line),
+        }
+    }
+}
+
+/// `text_limit` caps how many characters of each text chunk value are shown.
+pub fn print_chunks(png: &Png, verbose: bool, text_limit: Option<usize>) {
+    println!("\n{}", style::header("PNG Chunks"));
+    let mut fdat_count = 0;
+    let mut fdat_bytes = 0;
+    for chunk in &png.chunks {
+        // IDAT is usually split into many chunks; summarize instead of listing each one
+        if &chunk.type_code == b"IDAT" {
+            continue;
+        }
+        // fdAT (APNG frame data) likewise, unless verbose
+        if &chunk.type_code == b"fdAT" {
+            fdat_count += 1;
+            fdat_bytes += chunk.data.len();
+            if verbose {
+                let sequence = decode::be_u32(&chunk.data, 0).unwrap_or(0);
+                println!("fdAT chunk - sequence {}, {} bytes", sequence, chunk.data.len());
+            }
+            continue;
+        }
+        print_chunk(chunk, png, verbose, text_limit);
+    }
+    match png.idat_stats() {
+        Some(stats) => println!(
+            "IDAT chunks: {} ({} bytes total; min {}, max {}, avg {:.0})",
+            stats.count,
+            stats.total,
+            stats.min,
+            stats.max,
+            stats.average()
+        ),
+        None => println!("IDAT chunks: 0 (0 bytes total)"),
+    }
+    if fdat_count > 0 {
+        println!("fdAT chunks: {} ({} bytes total)", fdat_count, fdat_bytes);
+    }
+    print_animation_summary(png);
+    if !png.trailing.is_empty() {
+        println!("Trailing data: {} bytes after IEND", png.trailing.len());
+    }
+}
+
+/// Text chunk values under the keywords the spec registers, with friendly
+/// labels and in the spec's order, followed by any other keywords. Prints
+/// nothing when there is no readable text.
+pub fn print_descriptive_metadata(png: &Png, text_limit: Option<usize>) {
+    let entries: Vec<(String, String)> =
+        png.chunks.iter().filter_map(|c| decode::text_entry(&c.type_code, &c.data)).collect();
+    if entries.is_empty() {
+        return;
+    }
+    println!("\n{}", style::header("Descriptive Metadata"));
+    for (keyword, label) in decode::REGISTERED_KEYWORDS {
+        for (_, text) in entries.iter().filter(|(k, _)| k == keyword) {
+            println!("{} {}", style::key(label), truncate_text(text, text_limit));
+        }
+    }
+    let registered = |keyword: &str| decode::REGISTERED_KEYWORDS.iter().any(|(k, _)| *k == keyword);
+    let other: Vec<&(String, String)> = entries.iter().filter(|(k, _)| !registered(k)).collect();
+    if !other.is_empty() {
+        println!("Other text:");
+        for (keyword, text) in other {
+            println!("  {}: {}", keyword, truncate_text(text, text_limit));
+        }
+    }
+}
+
+/// One-line APNG summary, e.g. `APNG: 12 frames, loops 0 (infinite), total duration 3.4s`.
+fn print_animation_summary(png: &Png) {
+    let actl = match png
+        .chunks
+        .iter()
+        .find(|c| &c.type_code == b"acTL")
+        .and_then(|c| decode::parse_actl(&c.data))
+    {
+        Some(actl) => actl,
+        None => return,
+    };
+    let total_duration: f64 = png
+        .chunks
+        .iter()
+        .filter(|c| &c.type_code == b"fcTL")
+        .filter_map(|c| decode::parse_fctl(&c.data))
+        .map(|fctl| fctl.delay_seconds())
+        .sum();
+    let loops = if actl.num_plays == 0 { " (infinite)" } else { "" };
+    println!(
+        "APNG: {} frames, loops {}{}, total duration {:.1}s",
+        actl.num_frames, actl.num_plays, loops, total_duration
+    );
+}
+
+/// Runs of more consecutive IDAT chunks than this are collapsed into one row of the chunk map.
+const IDAT_MAP_LIMIT: usize = 4;
+
+/// Print every chunk's offset, length, type and CRC as an aligned table.
+pub fn print_chunk_map(png: &Png) {
+    println!("\n{}", style::header("Chunk Map"));
+    println!("Offset          Length  Type  CRC         Valid");
+    let mut rest = png.chunks.as_slice();
+    while let Some(chunk) = rest.first() {
+        let run = rest.iter().take_while(|c| &c.type_code == b"IDAT").count();
+        if run > IDAT_MAP_LIMIT {
+            let idats = &rest[..run];
+            let total: usize = idats.iter().map(|c| c.data.len()).sum();
+            let invalid = idats.iter().filter(|c| !c.crc_is_valid()).count();
+            println!(
+                "0x{:08X}  {:>10}  IDAT  {:<10}  {}",
+                chunk.offset,
+                total,
+                format!("({} chunks)", run),
+                if invalid == 0 { "yes".to_string() } else { format!("{} bad", invalid) }
+            );
+            rest = &rest[run..];
+            continue;
+        }
+        println!(
+            "0x{:08X}  {:>10}  {:<4}  0x{:08X}  {}",
+            chunk.offset,
+            chunk.data.len(),
+            chunk.type_str(),
+            chunk.crc,
+            if chunk.crc_is_valid() { "yes" } else { "NO" }
+        );
+        rest = &rest[1..];
+    }
+}
+
+/// Print the spec violations found by `validate_chunk_order`.
+pub fn print_validation(violations: &[String]) {
+    println!("\n{}", style::header("Validation"));
+    if violations.is_empty() {
+        println!("No spec violations found");
+    }
+    for violation in violations {
+        println!("{} {}", style::warn_label(), violation);
+    }
+}
+
+/// Columns and character rows the `--preview` thumbnail is fitted into.
+const PREVIEW_COLUMNS: u32 = 40;
+const PREVIEW_ROWS: u32 = 20;
+/// Characters for the uncolored preview, from dark to light.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Draw the image as a thumbnail about 40 columns wide. Each character
+/// covers two pixel rows: with color, an upper half block colored with the
+/// top pixel over a background of the bottom one; without, a character from
+/// an ASCII ramp for their average brightness.
+pub fn print_preview(png: &Png) {
+    let Some(thumb) = thumbnail(png, PREVIEW_COLUMNS, PREVIEW_ROWS * 2) else {
+        return;
+    };
+    println!("\n{}", style::header("Preview"));
+    let width = thumb.width as usize;
+    let color = style::stdout_colored();
+    for pair in thumb.pixels.chunks(width * 2) {
+        let (top, bottom) = pair.split_at(width);
+        // An odd last row is paired with black
+        let bottom = |x: usize| bottom.get(x).copied().unwrap_or([0, 0, 0]);
+        let line: String = (0..width)
+            .map(|x| {
+                let ([r, g, b], [r2, g2, b2]) = (top[x], bottom(x));
+                if color {
+                    format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}", r, g, b, r2, g2, b2)
+                } else {
+                    let luma = (luma8([r, g, b]) + luma8([r2, g2, b2])) / 2.0;
+                    let index = (luma / 256.0 * ASCII_RAMP.len() as f64) as usize;
+                    (ASCII_RAMP[index.min(ASCII_RAMP.len() - 1)] as char).to_string()
+                }
+            })
+            .collect();
+        if color {
+            println!("{}\x1b[0m", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Rec. 709 luma of an 8-bit RGB color.
+fn luma8([r, g, b]: [u8; 3]) -> f64 {
+    0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64
+}
+
+/// Per-channel LSB and higher bit-plane entropy from `--stego-check`, with
+/// an overall verdict.
+pub fn print_stego_check(scores: &[ChannelEntropy]) {
+    println!("\n{}", style::header("LSB Steganography Check"));
+    for score in scores {
+        let flag = if score.is_suspicious() { " (suspicious)" } else { "" };
+        println!(
+            "{} LSB entropy {:.4}, next planes {:.4}{}",
+            style::key(score.channel),
+            score.lsb,
+            score.higher,
+            flag
+        );
+    }
+    let suspicious: Vec<&str> = scores.iter().filter(|s| s.is_suspicious()).map(|s| s.channel).collect();
+    if suspicious.is_empty() {
+        println!("{} no sign of LSB embedding", style::key("Verdict"));
+    } else {
+        println!(
+            "{} low bits of {} look random while the bits above them don't; possible hidden data",
+            style::key("Verdict"),
+            suspicious.join(", ")
+        );
+    }
+    println!("This is a statistical indicator, not proof: noise and some encoders randomize low bits too");
+}
+
+/// The one-line stego verdict printed per file in batch runs and under --quiet.
+pub fn stego_check_line(scores: &[ChannelEntropy]) -> String {
+    let suspicious: Vec<&str> = scores.iter().filter(|s| s.is_suspicious()).map(|s| s.channel).collect();
+    match suspicious.as_slice() {
+        [] => "stego check: no sign of LSB embedding".to_string(),
+        channels => format!("stego check: random LSBs in {}", channels.join(", ")),
+    }
+}
+
+/// Embedded ZIP and PDF signatures from `--polyglot-check`, with where each
+/// sits in the file.
+pub fn print_polyglot_check(found: &[EmbeddedSignature]) {
+    println!("\n{}", style::header("Polyglot Check"));
+    if found.is_empty() {
+        println!("No ZIP or PDF signatures found");
+    }
+    for signature in found {
+        println!("Possible {} polyglot at offset 0x{:x} ({})", signature.format, signature.offset, signature.location());
+    }
+}
+
+/// One `--polyglot-check` finding as printed per file in batch runs and under --quiet.
+pub fn polyglot_line(signature: &EmbeddedSignature) -> String {
+    format!("possible {} polyglot at offset 0x{:x} ({})", signature.format, signature.offset, signature.location())
+}
+
+/// How many pixels are opaque, transparent and in between, with a hint when
+/// the alpha channel could be dropped.
+pub fn print_alpha_analysis(stats: &AlphaStats, color_type: ColorType) {
+    println!("\n{}", style::header("Alpha Analysis"));
+    let total = stats.total().max(1) as f64;
+    let rows = [
+        ("Fully opaque", stats.opaque),
+        ("Fully transparent", stats.transparent),
+        ("Partially transparent", stats.partial),
+    ];
+    for (label, count) in rows {
+        println!("{} {} ({:.1}%)", style::key(label), count, count as f64 * 100.0 / total);
+    }
+    if stats.is_unused() {
+        let (name, flag) = if color_type == ColorType::GrayscaleAlpha { ("gray", "gray") } else { ("RGB", "rgb") };
+        println!("{} alpha is unused, safe to convert to {} (--color {})", style::key("Suggestion"), name, flag);
+    } else if stats.partial == 0 {
+        println!("{} alpha is only ever fully on or off", style::key("Note"));
+    }
+}
+
+/// Compare the compressed IDAT size against the decoded image size.
+pub fn print_compression(png: &Png) {
+    let compressed = png.idat_bytes();
+    match png.decoded_size() {
+        Some(decoded) if compressed > 0 && decoded > 0 => println!(
+            "{} {} of {} bytes (ratio {:.2}:1, {:.1}% of decoded size)",
+            style::key("IDAT compression"),
+            compressed,
+            decoded,
+            decoded as f64 / compressed as f64,
+            compressed as f64 * 100.0 / decoded as f64
+        ),
+        _ => println!("{} {} bytes compressed", style::key("IDAT compression"), compressed),
+    }
+}
+
+/// Split the file into compressed pixel data (IDAT payloads), metadata (every
+/// ancillary chunk, framing included) and structure: the signature, the other
+/// critical chunks and the IDAT framing.
+pub fn print_size_breakdown(png: &Png) {
+    let pixels = png.idat_bytes();
+    let metadata: u64 = png.chunks.iter().filter(|c| !c.is_critical()).map(|c| chunk_size(c) as u64).sum();
+    let critical: u64 = png.chunks.iter().filter(|c| c.is_critical()).map(|c| chunk_size(c) as u64).sum();
+    let structure = PNG_SIGNATURE.len() as u64 + critical - pixels;
+    let total = pixels + metadata + structure;
+    let percent = |bytes: u64| bytes as f64 * 100.0 / total as f64;
+    println!("{} {} bytes ({:.1}%)", style::key("Pixel data"), pixels, percent(pixels));
+    println!("{} {} bytes ({:.1}%)", style::key("Metadata"), metadata, percent(metadata));
+    println!("{} {} bytes ({:.1}%)", style::key("Structure"), structure, percent(structure));
+}
+
+/// Before/after file sizes, e.g. `File size: 1480446 -> 1102334 bytes (-25.5%)`.
+pub fn print_size_change(before: u64, after: u64) {
+    let change = if before > 0 { (after as f64 - before as f64) * 100.0 / before as f64 } else { 0.0 };
+    println!("{} {} -> {} bytes ({:+.1}%)", style::key("File size"), before, after, change);
+}
+
+/// List which ancillary chunks will be copied into the output and which are dropped.
+pub fn print_carried_metadata(source: &[Chunk], kept: &[Chunk]) {
+    let ancillary_types = |chunks: &[Chunk]| -> Vec<String> {
+        chunks.iter().filter(|c| !c.is_critical()).map(|c| c.type_str()).collect()
+    };
+    let kept_types = ancillary_types(kept);
+    let dropped_types: Vec<String> = ancillary_types(source)
+        .into_iter()
+        .filter(|t| !kept_types.contains(t))
+        .collect();
+
+    let text_count = kept
+        .iter()
+        .filter(|c| matches!(&c.type_code, b"tEXt" | b"zTXt" | b"iTXt"))
+        .count();
+
+    println!("{} {}", style::key("Metadata carried over"), join_or_none(&kept_types));
+    println!("{} {}", style::key("Text chunks carried over"), text_count);
+    println!("{} {}", style::key("Metadata dropped"), join_or_none(&dropped_types));
+}
+
+/// Format a length in meters with a unit that keeps the number readable.
+fn format_length(meters: f64) -> String {
+    if meters >= 1.0 {
+        format!("{} m", meters)
+    } else if meters >= 1e-3 {
+        format!("{} mm", meters * 1e3)
+    } else {
+        format!("{} µm", meters * 1e6)
+    }
+}
+
+/// The source chunks missing from `kept`, with the bytes they took up, e.g.
+/// `Removed: 4 chunk(s) (tEXt, tEXt, eXIf, tIME), 1234 bytes`.
+pub fn print_removed_chunks(source: &[Chunk], kept: &[Chunk]) {
+    let removed = removed_chunks(source, kept);
+    let types: Vec<String> = removed.iter().map(|c| c.type_str()).collect();
+    let bytes: usize = removed.iter().map(|c| chunk_size(c)).sum();
+    println!("{} {} chunk(s) ({}), {} bytes", style::key("Removed"), removed.len(), join_or_none(&types), bytes);
+}
+
+/// What writing the output saved over the source file, with each removed
+/// chunk's share. Prints nothing beyond the size line when no chunks were
+/// removed. The rest of any difference comes from re-encoding the pixels.
+pub fn print_strip_savings(before: u64, after: u64, source: &[Chunk], kept: &[Chunk]) {
+    print_size_change(before, after);
+    let removed = removed_chunks(source, kept);
+    if removed.is_empty() {
+        return;
+    }
+    let in_chunks: usize = removed.iter().map(|c| chunk_size(c)).sum();
+    let saved = before as i64 - after as i64;
+    let percent = if before > 0 { saved as f64 * 100.0 / before as f64 } else { 0.0 };
+    println!(
+        "{} {} bytes ({:.1}%), {} of them from {} removed chunk(s):",
+        style::key("Saved"),
+        saved,
+        percent,
+        in_chunks,
+        removed.len()
+    );
+    for chunk in removed {
+        println!("  {:<6} {:>10} bytes", chunk.type_str(), chunk_size(chunk));
+    }
+}
+
+/// The ancillary source chunks missing from `kept`.
+fn removed_chunks<'a>(source: &'a [Chunk], kept: &[Chunk]) -> Vec<&'a Chunk> {
+    source
+        .iter()
+        .filter(|c| !c.is_critical() && !kept.iter().any(|k| k.type_code == c.type_code && k.data == c.data))
+        .collect()
+}
+
+/// Bytes a chunk takes in the file: the length, type and CRC fields add 12
+/// to the data.
+fn chunk_size(chunk: &Chunk) -> usize {
+    chunk.data.len() + 12
+}
+
+fn join_or_none(types: &[String]) -> String {
+    if types.is_empty() {
+        "none".to_string()
+    } else {
+        types.join(", ")
+    }
+}
+
+/// `text` cut to `limit` characters with a note of its full length, e.g.
+/// `abc… (truncated, 5000 total)`.
+fn truncate_text(text: &str, limit: Option<usize>) -> Cow<'_, str> {
+    match limit {
+        Some(limit) if text.chars().nth(limit).is_some() => {
+            let cut: String = text.chars().take(limit).collect();
+            Cow::Owned(format!("{}… (truncated, {} total)", cut, text.chars().count()))
+        }
+        _ => Cow::Borrowed(text),
+    }
+}
+
+/// Print a one-line description of a chunk, decoding the common ancillary types.
+fn print_chunk(chunk: &Chunk, png: &Png, verbose: bool, text_limit: Option<usize>) {
+    let data = &chunk.data;
+    match &chunk.type_code {
+        b"tEXt" => match decode::parse_text(data) {
+            Some(text) => println!("tEXt chunk - {}: {}", text.keyword, truncate_text(&text.text, text_limit)),
+            None => warn!("malformed tEXt chunk at offset {} (missing null separator)", chunk.offset),
+        },
+        b"zTXt" => match decode::parse_ztxt(data) {
+            Some(ztxt) => match &ztxt.text {
+                Ok(text) => println!("zTXt chunk - {}: {}", ztxt.keyword, truncate_text(text, text_limit)),
+                Err(e) => {
+                    println!("zTXt chunk - {}: [compressed, method: {}]", ztxt.keyword, ztxt.method);
+                    warn!("could not decompress zTXt chunk '{}': {}", ztxt.keyword, e);
+                }
+            },
+            None => warn!("malformed zTXt chunk at offset {} (missing null separator)", chunk.offset),
+        },
+        b"iTXt" => {
+            match decode::parse_itxt(data) {
+                Some(itxt) => {
+                    match &itxt.text {
+                        Ok(text) => println!("iTXt chunk - {}: {}", itxt.keyword, truncate_text(text, text_limit)),
+                        Err(e) => {
+                            println!("iTXt chunk - {}: [international text]", itxt.keyword);
+                            warn!("could not decode iTXt chunk '{}': {}", itxt.keyword, e);
+                        }
+                    }
+                    println!("  Language: {}", if itxt.language_tag.is_empty() { "(none)" } else { &itxt.language_tag });
+                    println!(
+                        "  Translated keyword: {}",
+                        if itxt.translated_keyword.is_empty() { "(none)" } else { &itxt.translated_keyword }
+                    );
+                }
+                None => warn!("malformed iTXt chunk at offset {} (missing null separators)", chunk.offset),
+            }
+        }
+        b"gAMA" => {
+            if let Some(gamma) = decode::parse_gamma(data) {
+                match decode::gamma_name(gamma) {
+                    Some(name) => println!("gAMA chunk - Gamma: {:.5} ({})", gamma, name),
+                    None => println!("gAMA chunk - Gamma: {:.5}", gamma),
+                }
+            }
+        }
+        b"pHYs" => {
+            if let Some(phys) = decode::parse_phys(data) {
+                println!(
+                    "pHYs chunk - {}x{} pixels per unit, unit: {}",
+                    phys.x_ppu,
+                    phys.y_ppu,
+                    phys.unit_name()
+                );
+                match phys.print_size_inches(png.width, png.height) {
+                    Some((width, height)) => {
+                        let (x_dpi, y_dpi) = phys.dpi().unwrap_or_default();
+                        println!("  Resolution: {:.1} x {:.1} DPI", x_dpi, y_dpi);
+                        println!(
+                            "  Print size: {:.2} x {:.2} in ({:.2} x {:.2} cm)",
+                            width,
+                            height,
+                            width * 2.54,
+                            height * 2.54
+                        );
+                    }
+                    None if phys.unit == 0 => {
+                        println!("  Unit unknown: the values only give the pixel aspect ratio, not a physical size")
+                    }
+                    None => {}
+                }
+            }
+        }
+        b"oFFs" => match decode::parse_offs(data) {
+            Some(offs) if offs.unit == 1 => println!(
+                "oFFs chunk - offset: {} mm right, {} mm down",
+                offs.x as f64 / 1000.0,
+                offs.y as f64 / 1000.0
+            ),
+            Some(offs) => println!("oFFs chunk - offset: {} right, {} down ({} units)", offs.x, offs.y, offs.unit_name()),
+            None => warn!("malformed oFFs chunk at offset {} ({} bytes, expected 9)", chunk.offset, data.len()),
+        },
+        b"sCAL" => match decode::parse_scal(data) {
+            Some(scal) => match (scal.width_value(), scal.height_value()) {
+                (Some(width), Some(height)) if scal.unit == 1 => {
+                    println!("sCAL chunk - pixel size: {} x {}", format_length(width), format_length(height))
+                }
+                (Some(width), Some(height)) if scal.unit == 2 => println!(
+                    "sCAL chunk - pixel size: {:.6}° x {:.6}°",
+                    width.to_degrees(),
+                    height.to_degrees()
+                ),
+                (Some(width), Some(height)) => {
+                    println!("sCAL chunk - pixel size: {} x {} (unit {})", width, height, scal.unit)
+                }
+                _ => {
+                    println!("sCAL chunk - pixel size: '{}' x '{}' ({})", scal.width, scal.height, scal.unit_name());
+                    warn!("sCAL chunk values '{}' and '{}' are not both positive numbers", scal.width, scal.height);
+                }
+            },
+            None => warn!("malformed sCAL chunk at offset {} (missing null separator)", chunk.offset),
+        },
+        b"iCCP" => {
+            if let Some(iccp) = decode::parse_iccp(data) {
+                println!("iCCP chunk - Profile: {} ({} bytes compressed)", iccp.name, iccp.compressed_size);
+            }
+        }
+        b"sRGB" => {
+            if let Some(intent) = decode::parse_srgb(data) {
+                println!("sRGB chunk - Rendering intent: {}", intent);
+            }
+        }
+        b"tIME" => {
+            if let Some(time) = decode::parse_time(data) {
+                println!("tIME chunk - Last modified: {}", time);
+            }
+        }
+        b"tRNS" => match decode::parse_transparency(data, png.color_type) {
+            Some(transparency) => {
+                println!("tRNS chunk - Transparency: {}", transparency);
+                let palette_len = png
+                    .chunks
+                    .iter()
+                    .find(|c| &c.type_code == b"PLTE")
+                    .and_then(|plte| decode::parse_palette(&plte.data))
+                    .map(|palette| palette.len());
+                if let (decode::Transparency::PaletteAlpha(alphas), Some(entries)) = (&transparency, palette_len) {
+                    if alphas.len() > entries {
+                        warn!("tRNS has {} alpha values but PLTE only has {} entries", alphas.len(), entries);
+                    }
+                }
+            }
+            None => {
+                let expected = match png.color_type {
+                    ColorType::Grayscale => "2 bytes",
+                    ColorType::Rgb => "6 bytes",
+                    ColorType::Indexed => "1 to 256 bytes",
+                    _ => "no tRNS chunk, since it has an alpha channel",
+                };
+                println!("tRNS chunk - Transparency: {:?}", data);
+                warn!("tRNS is {} bytes, but a {:?} image takes {}", data.len(), png.color_type, expected);
+            }
+        },
+        b"PLTE" => match decode::parse_palette(data) {
+            Some(palette) => {
+                println!("PLTE chunk - {} entries", palette.len());
+                if verbose {
+                    // tRNS alpha applies per palette index; missing entries are opaque
+                    let alphas = png.trns.as_deref().unwrap_or(&[]);
+                    for (index, rgb) in palette.iter().enumerate() {
+                        let alpha = alphas.get(index).copied().unwrap_or(255);
+                        println!("  [{:3}] {} alpha {}", index, decode::hex_color(*rgb), alpha);
+                    }
+                }
+            }
+            None => warn!(
+                "malformed PLTE chunk: length {} is not a multiple of 3",
+                data.len()
+            ),
+        },
+        b"bKGD" => match decode::parse_background(data, png.color_type) {
+            Some(decode::Background::PaletteIndex(index)) => {
+                // Resolve the index against PLTE so the actual color is visible
+                let color = png
+                    .chunks
+                    .iter()
+                    .find(|c| &c.type_code == b"PLTE")
+                    .and_then(|plte| decode::parse_palette(&plte.data))
+                    .and_then(|palette| palette.get(index as usize).copied());
+                match color {
+                    Some(rgb) => println!("bKGD chunk - Background: palette index {} ({})", index, decode::hex_color(rgb)),
+                    None => println!("bKGD chunk - Background: palette index {}", index),
+                }
+            }
+            Some(background) => println!("bKGD chunk - Background: {}", background),
+            None => warn!(
+                "malformed bKGD chunk: {} bytes is invalid for color type {:?}",
+                data.len(),
+                png.color_type
+            ),
+        },
+        b"sBIT" => match decode::parse_significant_bits(data, png.color_type) {
+            Some(channels) => {
+                // Palette entries are always 8 bits, regardless of the index depth
+                let sample_depth = if png.color_type == png::ColorType::Indexed { 8 } else { png.bit_depth as u8 };
+                let counts: Vec<String> = channels
+                    .iter()
+                    .map(|(name, bits)| format!("{}={}/{}", name, bits, sample_depth))
+                    .collect();
+                println!("sBIT chunk - Significant bits: {}", counts.join(", "));
+            }
+            None => warn!(
+                "malformed sBIT chunk: expected {} bytes for color type {:?}, got {}",
+                decode::sbit_channels(png.color_type).len(),
+                png.color_type,
+                data.len()
+            ),
+        },
+        b"hIST" => match decode::parse_hist(data) {
+            Some(frequencies) => {
+                println!("hIST chunk - {} entries", frequencies.len());
+                if png.color_type != png::ColorType::Indexed {
+                    println!("  Note: hIST describes palette usage; this is a {:?} image", png.color_type);
+                }
+                let palette_len = png
+                    .chunks
+                    .iter()
+                    .find(|c| &c.type_code == b"PLTE")
+                    .and_then(|plte| decode::parse_palette(&plte.data))
+                    .map(|palette| palette.len());
+                match palette_len {
+                    Some(n) if n != frequencies.len() => {
+                        warn!("hIST has {} entries but PLTE has {}", frequencies.len(), n)
+                    }
+                    None => warn!("hIST chunk present without a PLTE chunk"),
+                    _ => {}
+                }
+                for (index, frequency) in frequencies.iter().enumerate() {
+                    println!("  [{:3}] {}", index, frequency);
+                }
+            }
+            None => warn!("malformed hIST chunk: length {} is not a multiple of 2", data.len()),
+        },
+        b"sPLT" => match decode::parse_splt(data) {
+            Ok(splt) => {
+                println!(
+                    "sPLT chunk - Suggested palette: {} ({}-bit, {} entries)",
+                    splt.name,
+                    splt.sample_depth,
+                    splt.entries.len()
+                );
+                if verbose {
+                    for (index, entry) in splt.entries.iter().enumerate() {
+                        println!(
+                            "  [{:3}] {} alpha {} frequency {}",
+                            index,
+                            entry.hex(splt.sample_depth),
+                            entry.alpha,
+                            entry.frequency
+                        );
+                    }
+                }
+            }
+            Err(e) => warn!("malformed sPLT chunk at offset {}: {}", chunk.offset, e),
+        },
+        b"acTL" => match decode::parse_actl(data) {
+            Some(actl) => println!("acTL chunk - {} frames, {} plays", actl.num_frames, actl.num_plays),
+            None => warn!("malformed acTL chunk ({} bytes, expected 8)", data.len()),
+        },
+        b"fcTL" => match decode::parse_fctl(data) {
+            Some(fctl) => println!(
+                "fcTL chunk - sequence {}: {}x{} at ({}, {}), delay {}/{} ({:.3}s), dispose {}, blend {}",
+                fctl.sequence_number,
+                fctl.width,
+                fctl.height,
+                fctl.x_offset,
+                fctl.y_offset,
+                fctl.delay_num,
+                fctl.delay_den,
+                fctl.delay_seconds(),
+                fctl.dispose_name(),
+                fctl.blend_name()
+            ),
+            None => warn!("malformed fcTL chunk ({} bytes, expected 26)", data.len()),
+        },
+        b"eXIf" => match Exif::parse(data) {
+            Ok(exif) => {
+                let order = if exif.big_endian { "Motorola" } else { "Intel" };
+                println!("eXIf chunk - {} tags ({} byte order)", exif.entries.len(), order);
+                for entry in &exif.entries {
+                    println!("  {}: {}", entry.name(), entry.display_value());
+                }
+                if let Some((lat, lon)) = exif.gps_coordinates() {
+                    println!("  GPS position: {:.6}, {:.6}", lat, lon);
+                }
+            }
+            Err(e) => warn!("could not parse eXIf chunk: {}", e),
+        },
+        b"IHDR" | b"IEND" => {
+            println!("{} chunk ({} bytes)", chunk.type_str(), data.len());
+        }
+        _ => {
+            println!("Other chunk: {} ({} bytes)", chunk.type_str(), data.len());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Nearest-neighbor and bilinear resampling of decoded pixels, for
+//! `--resize` and `--scale`.
+
+use png::{BitDepth, ColorType};
+
+use crate::pixels::{repack_samples, sample_values};
+
+/// How `resize` picks output samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// Copy the source pixel under each output pixel's center. Exact on
+    /// integer downscales, and the only choice for indexed color
+    Nearest,
+    /// Blend the four source pixels around each output pixel's center,
+    /// weighted by alpha so transparent pixels don't darken edges
+    #[default]
+    Bilinear,
+}
+
+/// The requested output size, resolved against the source by `dimensions`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeTarget {
+    /// `WxH`; a missing side follows the other to keep the aspect ratio
+    Size { width: Option<u32>, height: Option<u32> },
+    /// Both sides multiplied by this factor
+    Scale(f64),
+}
+
+impl ResizeTarget {
+    /// The output width and height for a `width` x `height` source, never
+    /// less than one pixel on either side.
+    pub fn dimensions(self, width: u32, height: u32) -> (u32, u32) {
+        let scaled = |side: u32, factor: f64| ((side as f64 * factor).round() as u32).max(1);
+        match self {
+            ResizeTarget::Size { width: Some(w), height: Some(h) } => (w, h),
+            ResizeTarget::Size { width: Some(w), height: None } => (w, scaled(height, w as f64 / width.max(1) as f64)),
+            ResizeTarget::Size { width: None, height: Some(h) } => (scaled(width, h as f64 / height.max(1) as f64), h),
+            ResizeTarget::Size { width: None, height: None } => (width, height),
+            ResizeTarget::Scale(factor) => (scaled(width, factor), scaled(height, factor)),
+        }
+    }
+}
+
+/// Resample packed PNG rows from `width` x `height` to `new_width` x
+/// `new_height`, keeping the color type and bit depth. Indexed images are
+/// always resampled with `Nearest`, since blending palette indices would
+/// pick unrelated colors.
+#[allow(clippy::too_many_arguments)]
+pub fn resize(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    new_width: u32,
+    new_height: u32,
+    filter: ResizeFilter,
+) -> Vec<u8> {
+    let samples = sample_values(pixels, width as usize, height as usize, color_type.samples(), bit_depth);
+    let filter = if color_type == ColorType::Indexed { ResizeFilter::Nearest } else { filter };
+    let resized = match filter {
+        ResizeFilter::Nearest => nearest(&samples, width, height, color_type.samples(), new_width, new_height),
+        ResizeFilter::Bilinear => bilinear(&samples, width, height, color_type, new_width, new_height),
+    };
+    pack(&resized, new_width, color_type, bit_depth)
+}
+
+/// The source index under the center of output index `i` of `new_size`.
+fn nearest_source(i: usize, size: u32, new_size: u32) -> usize {
+    ((2 * i + 1) * size as usize / (2 * new_size as usize)).min(size as usize - 1)
+}
+
+fn nearest(samples: &[u16], width: u32, height: u32, channels: usize, new_width: u32, new_height: u32) -> Vec<u16> {
+    let mut out = Vec::with_capacity(new_width as usize * new_height as usize * channels);
+    for y in 0..new_height as usize {
+        let row = nearest_source(y, height, new_height) * width as usize;
+        for x in 0..new_width as usize {
+            let pixel = (row + nearest_source(x, width, new_width)) * channels;
+            out.extend_from_slice(&samples[pixel..pixel + channels]);
+        }
+    }
+    out
+}
+
+/// The two source indices around output index `i`'s center and the weight
+/// of the second.
+fn bilinear_sources(i: usize, size: u32, new_size: u32) -> (usize, usize, f64) {
+    let center = ((i as f64 + 0.5) * size as f64 / new_size as f64 - 0.5).clamp(0.0, size as f64 - 1.0);
+    let first = center.floor() as usize;
+    (first, (first + 1).min(size as usize - 1), center - first as f64)
+}
+
+fn bilinear(
+    samples: &[u16],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    new_width: u32,
+    new_height: u32,
+) -> Vec<u16> {
+    let channels = color_type.samples();
+    // The alpha sample, when there is one, is last
+    let alpha = matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba).then_some(channels - 1);
+    let mut out = Vec::with_capacity(new_width as usize * new_height as usize * channels);
+    for y in 0..new_height as usize {
+        let (y0, y1, fy) = bilinear_sources(y, height, new_height);
+        for x in 0..new_width as usize {
+            let (x0, x1, fx) = bilinear_sources(x, width, new_width);
+            let corners = [
+                (y0 * width as usize + x0, (1.0 - fx) * (1.0 - fy)),
+                (y0 * width as usize + x1, fx * (1.0 - fy)),
+                (y1 * width as usize + x0, (1.0 - fx) * fy),
+                (y1 * width as usize + x1, fx * fy),
+            ];
+            let sample = |pixel: usize, channel: usize| samples[pixel * channels + channel] as f64;
+            let coverage: f64 = match alpha {
+                Some(a) => corners.iter().map(|&(pixel, weight)| sample(pixel, a) * weight).sum(),
+                None => 1.0,
+            };
+            for channel in 0..channels {
+                let value = if Some(channel) == alpha {
+                    coverage
+                } else if let (Some(a), true) = (alpha, coverage > 0.0) {
+                    corners.iter().map(|&(pixel, weight)| sample(pixel, channel) * sample(pixel, a) * weight).sum::<f64>()
+                        / coverage
+                } else {
+                    corners.iter().map(|&(pixel, weight)| sample(pixel, channel) * weight).sum()
+                };
+                out.push(value.round() as u16);
+            }
+        }
+    }
+    out
+}
+
+/// The reverse of `sample_values`.
+fn pack(samples: &[u16], width: u32, color_type: ColorType, bit_depth: BitDepth) -> Vec<u8> {
+    match bit_depth {
+        BitDepth::Sixteen => samples.iter().flat_map(|sample| sample.to_be_bytes()).collect(),
+        _ => {
+            let bytes: Vec<u8> = samples.iter().map(|&sample| sample as u8).collect();
+            repack_samples(&bytes, width, bit_depth, color_type)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x6 grayscale ramp where every pixel has a different value.
+    fn ramp() -> Vec<u8> {
+        (0..48).map(|i| i * 5).collect()
+    }
+
+    #[test]
+    fn target_dimensions_keep_the_aspect_ratio_when_one_side_is_given() {
+        let width_only = ResizeTarget::Size { width: Some(256), height: None };
+        assert_eq!(width_only.dimensions(1024, 768), (256, 192));
+        let height_only = ResizeTarget::Size { width: None, height: Some(100) };
+        assert_eq!(height_only.dimensions(1024, 768), (133, 100));
+        let both = ResizeTarget::Size { width: Some(10), height: Some(10) };
+        assert_eq!(both.dimensions(1024, 768), (10, 10));
+        assert_eq!(ResizeTarget::Scale(0.5).dimensions(1024, 767), (512, 384));
+        assert_eq!(ResizeTarget::Scale(0.001).dimensions(100, 100), (1, 1));
+    }
+
+    #[test]
+    fn output_has_the_requested_size() {
+        for filter in [ResizeFilter::Nearest, ResizeFilter::Bilinear] {
+            let gray = resize(&ramp(), 8, 6, ColorType::Grayscale, BitDepth::Eight, 3, 5, filter);
+            assert_eq!(gray.len(), 3 * 5);
+
+            let rgba16 = vec![0x12; 8 * 6 * 8];
+            let resized = resize(&rgba16, 8, 6, ColorType::Rgba, BitDepth::Sixteen, 5, 2, filter);
+            assert_eq!(resized.len(), 5 * 2 * 8);
+            assert!(resized.iter().all(|&b| b == 0x12));
+
+            // 1-bit rows of 3 pixels take one byte each, padded with zeros
+            let packed = resize(&[0xFF; 4], 8, 4, ColorType::Grayscale, BitDepth::One, 3, 2, filter);
+            assert_eq!(packed, vec![0b1110_0000; 2]);
+        }
+    }
+
+    #[test]
+    fn nearest_is_exact_on_integer_downscales() {
+        let source = ramp();
+        let half = resize(&source, 8, 6, ColorType::Grayscale, BitDepth::Eight, 4, 3, ResizeFilter::Nearest);
+        // Each output pixel takes the source pixel at the center of its 2x2 block
+        let expected: Vec<u8> = (0..3).flat_map(|y| (0..4).map(move |x| (2 * y + 1) * 8 + 2 * x + 1)).map(|i| source[i]).collect();
+        assert_eq!(half, expected);
+        // Every output pixel is one of the source pixels, untouched
+        let quarter = resize(&source, 8, 6, ColorType::Grayscale, BitDepth::Eight, 2, 2, ResizeFilter::Nearest);
+        assert!(quarter.iter().all(|value| source.contains(value)));
+    }
+
+    #[test]
+    fn bilinear_averages_and_ignores_transparent_colors() {
+        // Halving a 2x2 image blends all four pixels equally
+        let gray = [0, 100, 100, 200];
+        assert_eq!(resize(&gray, 2, 2, ColorType::Grayscale, BitDepth::Eight, 1, 1, ResizeFilter::Bilinear), vec![100]);
+
+        // A fully transparent black pixel doesn't darken its opaque white neighbor
+        let rgba = [255, 255, 255, 255, 0, 0, 0, 0];
+        let blended = resize(&rgba, 2, 1, ColorType::Rgba, BitDepth::Eight, 1, 1, ResizeFilter::Bilinear);
+        assert_eq!(blended, vec![255, 255, 255, 128]);
+    }
+}
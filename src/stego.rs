@@ -0,0 +1,98 @@
+//! Heuristic check for data hidden in the least significant bits (LSB
+//! steganography).
+//!
+//! Embedding a message in the low bit of each sample makes that bit plane
+//! look like coin flips, so its entropy approaches 1.0 even where the higher
+//! planes, which carry the picture, are far more ordered. Noisy photographs
+//! have near-random low planes anyway, so a high score is an indicator worth
+//! a closer look, not proof that anything is hidden.
+
+use png::{BitDepth, ColorType};
+
+use crate::Png;
+
+/// LSB entropy at or above which a channel's low bits look random.
+const RANDOM_ENTROPY: f64 = 0.99;
+/// How far the LSB entropy must exceed the next planes' to count as suspicious.
+const SUSPICIOUS_MARGIN: f64 = 0.05;
+/// Bit planes above the LSB that it is compared against.
+const REFERENCE_PLANES: usize = 2;
+
+/// Bit-plane entropies of one channel, each from 0.0 (constant) to 1.0
+/// (evenly split zeros and ones).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelEntropy {
+    /// "Red", "Gray", "Alpha", "Index" and so on
+    pub channel: &'static str,
+    /// Entropy of the least significant bit plane
+    pub lsb: f64,
+    /// Mean entropy of the next `REFERENCE_PLANES` planes up
+    pub higher: f64,
+}
+
+impl ChannelEntropy {
+    /// Whether the LSB plane looks random while the planes above it don't.
+    pub fn is_suspicious(&self) -> bool {
+        self.lsb >= RANDOM_ENTROPY && self.lsb - self.higher >= SUSPICIOUS_MARGIN
+    }
+}
+
+/// Bit-plane entropy of every channel of the decoded image.
+///
+/// Returns `None` if the pixels were not decoded or the samples are narrower
+/// than 8 bits. For 16-bit samples the planes are those of the low byte.
+pub fn lsb_entropy(png: &Png) -> Option<Vec<ChannelEntropy>> {
+    let sample_bytes = match png.bit_depth {
+        BitDepth::Eight => 1,
+        BitDepth::Sixteen => 2,
+        _ => return None,
+    };
+    let names = channel_names(png.color_type);
+    let pixel_count = png.width as usize * png.height as usize;
+    let stride = names.len() * sample_bytes;
+    if png.pixels.len() < pixel_count * stride || pixel_count == 0 {
+        return None;
+    }
+
+    // Count the set bits of each of the low planes, per channel
+    let planes = REFERENCE_PLANES + 1;
+    let mut ones = vec![vec![0usize; planes]; names.len()];
+    for pixel in png.pixels[..pixel_count * stride].chunks_exact(stride) {
+        for (channel, counts) in ones.iter_mut().enumerate() {
+            // The low byte of a big-endian sample is its last
+            let low = pixel[(channel + 1) * sample_bytes - 1];
+            for (plane, count) in counts.iter_mut().enumerate() {
+                *count += ((low >> plane) & 1) as usize;
+            }
+        }
+    }
+
+    let scores = names
+        .iter()
+        .zip(ones)
+        .map(|(&channel, counts)| {
+            let entropy: Vec<f64> = counts.iter().map(|&n| binary_entropy(n as f64 / pixel_count as f64)).collect();
+            let higher = entropy[1..].iter().sum::<f64>() / REFERENCE_PLANES as f64;
+            ChannelEntropy { channel, lsb: entropy[0], higher }
+        })
+        .collect();
+    Some(scores)
+}
+
+fn channel_names(color_type: ColorType) -> &'static [&'static str] {
+    match color_type {
+        ColorType::Grayscale => &["Gray"],
+        ColorType::GrayscaleAlpha => &["Gray", "Alpha"],
+        ColorType::Rgb => &["Red", "Green", "Blue"],
+        ColorType::Rgba => &["Red", "Green", "Blue", "Alpha"],
+        ColorType::Indexed => &["Index"],
+    }
+}
+
+/// Shannon entropy in bits of a bit that is set with probability `p`.
+fn binary_entropy(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 {
+        return 0.0;
+    }
+    -(p * p.log2() + (1.0 - p) * (1.0 - p).log2())
+}
@@ -0,0 +1,104 @@
+//! Deciding which source chunks are carried into the re-encoded output.
+
+use crate::chunk::Chunk;
+
+/// How ancillary metadata is treated when writing the output image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataMode {
+    /// Carry every ancillary chunk that is still valid for the re-encoded pixels
+    Preserve,
+    /// Keep only the critical chunks (IHDR, PLTE, IDAT, IEND) for a byte-minimal file
+    Strip,
+}
+
+/// Registered ancillary chunks that describe the image rather than its exact
+/// pixel layout, so they remain correct after the pixels are re-encoded.
+const KNOWN_PRESERVABLE: [&[u8; 4]; 17] = [
+    b"tRNS", b"gAMA", b"cHRM", b"sRGB", b"iCCP", b"sBIT", b"bKGD", b"pHYs", b"sPLT",
+    b"tIME", b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"oFFs", b"sCAL", b"cICP",
+];
+
+/// Ancillary chunks tied to the original IDAT stream or frame layout. The
+/// output is always a single re-encoded frame, so these are never copied.
+const PIXEL_DEPENDENT: [&[u8; 4]; 4] = [b"hIST", b"acTL", b"fcTL", b"fdAT"];
+
+/// Ancillary chunks whose contents are laid out per color type and scaled to
+/// the bit depth, so they are wrong once the pixel format changes.
+const FORMAT_DEPENDENT: [&[u8; 4]; 3] = [b"tRNS", b"sBIT", b"bKGD"];
+
+/// Whether an ancillary chunk can be copied verbatim next to re-encoded pixels.
+///
+/// Known chunks are decided by type; unknown ones follow the spec's
+/// safe-to-copy bit, since the image data is always rewritten.
+pub fn can_carry_forward(chunk: &Chunk) -> bool {
+    if PIXEL_DEPENDENT.contains(&&chunk.type_code) {
+        return false;
+    }
+    KNOWN_PRESERVABLE.contains(&&chunk.type_code) || chunk.is_safe_to_copy()
+}
+
+/// Per-chunk-type overrides on top of a `MetadataMode`.
+///
+/// - `strip` is a denylist: listed types are always dropped.
+/// - `keep` is an allowlist for `Strip` mode: listed types survive stripping.
+///
+/// A type in both lists is dropped (`strip` takes precedence). Pixel-dependent
+/// chunks are never copied, even when listed in `keep`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StripRules {
+    pub mode: MetadataMode,
+    pub strip: Vec<[u8; 4]>,
+    pub keep: Vec<[u8; 4]>,
+}
+
+impl StripRules {
+    pub fn new(mode: MetadataMode) -> StripRules {
+        StripRules { mode, strip: Vec::new(), keep: Vec::new() }
+    }
+
+    /// Whether `chunk` should be written to the output.
+    pub fn keeps(&self, chunk: &Chunk) -> bool {
+        if chunk.is_critical() {
+            return true;
+        }
+        if self.strip.contains(&chunk.type_code) || !can_carry_forward(chunk) {
+            return false;
+        }
+        match self.mode {
+            MetadataMode::Preserve => true,
+            MetadataMode::Strip => self.keep.contains(&chunk.type_code),
+        }
+    }
+}
+
+/// Parse a comma-separated list of chunk type codes such as `tEXt,eXIf`.
+///
+/// Each entry must be exactly four ASCII letters, as required for chunk types.
+pub fn parse_chunk_list(list: &str) -> Result<Vec<[u8; 4]>, String> {
+    list.split(',')
+        .map(|token| {
+            let token = token.trim();
+            let bytes = token.as_bytes();
+            if bytes.len() != 4 || !bytes.iter().all(|b| b.is_ascii_alphabetic()) {
+                return Err(format!("invalid chunk type '{}': must be exactly four ASCII letters", token));
+            }
+            Ok([bytes[0], bytes[1], bytes[2], bytes[3]])
+        })
+        .collect()
+}
+
+/// Select the source chunks to hand to `write_png_image` under `rules`.
+///
+/// Critical chunks are always kept. In `Strip` mode every ancillary chunk not
+/// in the keep list is dropped, including tRNS, so palette and color-key
+/// transparency is lost unless tRNS is kept explicitly.
+pub fn select_chunks(chunks: &[Chunk], rules: &StripRules) -> Vec<Chunk> {
+    chunks.iter().filter(|chunk| rules.keeps(chunk)).cloned().collect()
+}
+
+/// Drop chunks that only make sense for the source color type and bit depth.
+///
+/// Used when the pixels are converted (e.g. `--to-rgb`) before writing.
+pub fn drop_format_dependent(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    chunks.into_iter().filter(|chunk| !FORMAT_DEPENDENT.contains(&&chunk.type_code)).collect()
+}
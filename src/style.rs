@@ -0,0 +1,81 @@
+//! ANSI colors for the human-readable report.
+//!
+//! Color is only used when the stream is a terminal and `NO_COLOR` is unset,
+//! so redirected output stays plain.
+
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+use log::Level;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+
+/// `NO_COLOR` set to any non-empty value disables color (https://no-color.org).
+fn color_allowed() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+fn stdout_color() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| color_allowed() && io::stdout().is_terminal())
+}
+
+fn stderr_color() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| color_allowed() && io::stderr().is_terminal())
+}
+
+fn paint(enabled: bool, code: &str, text: &str) -> String {
+    if enabled {
+        format!("{}{}{}", code, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether stdout gets ANSI color; `--preview` draws with 24-bit color when
+/// it does and falls back to an ASCII ramp otherwise.
+pub fn stdout_colored() -> bool {
+    stdout_color()
+}
+
+/// A section header such as `=== PNG Chunks ===`.
+pub fn header(title: &str) -> String {
+    paint(stdout_color(), BOLD_CYAN, &format!("=== {} ===", title))
+}
+
+/// The label of a `Key: value` line, including the colon.
+pub fn key(label: &str) -> String {
+    paint(stdout_color(), BOLD, &format!("{}:", label))
+}
+
+/// Status tag for a file that was processed successfully.
+pub fn ok(tag: &str) -> String {
+    paint(stdout_color(), GREEN, tag)
+}
+
+/// Status tag for a file that failed; printed to stderr.
+pub fn fail(tag: &str) -> String {
+    paint(stderr_color(), RED, tag)
+}
+
+pub fn warn_label() -> String {
+    paint(stderr_color(), BOLD_YELLOW, "WARN:")
+}
+
+/// Label for a log record on stderr: `WARN:` and `ERROR:` are colored, the
+/// quieter levels are plain.
+pub fn level_label(level: Level) -> String {
+    match level {
+        Level::Error => paint(stderr_color(), RED, "ERROR:"),
+        Level::Warn => warn_label(),
+        Level::Info => "INFO:".to_string(),
+        Level::Debug => "DEBUG:".to_string(),
+        Level::Trace => "TRACE:".to_string(),
+    }
+}
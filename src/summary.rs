@@ -0,0 +1,69 @@
+//! `--summary-only`: one tab-separated line per file for cataloging, with no
+//! report and no output image.
+//!
+//! The columns are fixed so scripts can rely on them:
+//!
+//! ```text
+//! path    WxH    color type    bit depth    interlacing    file size in bytes
+//! ```
+//!
+//! Color types use the `--color` names (gray, gray-alpha, rgb, rgba,
+//! indexed) and interlacing is `adam7` or `none`.
+
+use std::path::PathBuf;
+
+use png::ColorType;
+
+use unpeel::Png;
+
+use crate::progress;
+
+/// The summary line for `bytes`, read from `file_path`. Pixels are never
+/// decoded.
+pub fn summary_line(file_path: &str, bytes: &[u8]) -> Result<String, String> {
+    let png = Png::metadata_from_bytes(bytes).map_err(|e| e.to_string())?;
+    let color_type = match png.color_type {
+        ColorType::Grayscale => "gray",
+        ColorType::GrayscaleAlpha => "gray-alpha",
+        ColorType::Rgb => "rgb",
+        ColorType::Rgba => "rgba",
+        ColorType::Indexed => "indexed",
+    };
+    Ok(format!(
+        "{}\t{}x{}\t{}\t{}\t{}\t{}",
+        file_path,
+        png.width,
+        png.height,
+        color_type,
+        png.bit_depth as u8,
+        if png.interlaced { "adam7" } else { "none" },
+        bytes.len()
+    ))
+}
+
+/// Read one file, or stdin for `-`, and build its summary line.
+pub fn summarize_file(file_path: &str) -> Result<String, String> {
+    let bytes = if file_path == "-" { crate::read_stdin() } else { std::fs::read(file_path).map_err(Into::into) };
+    summary_line(file_path, &bytes.map_err(|e| e.to_string())?)
+}
+
+/// Print a summary line for each of `files`. Files that can't be read are
+/// reported on stderr so stdout stays one line per image. Returns whether
+/// every file was summarized.
+pub fn run_files(files: &[PathBuf], show_progress: bool) -> bool {
+    let bar = progress::batch_bar(files.len(), show_progress);
+    let mut all_ok = true;
+    for file in files {
+        let result = summarize_file(&file.to_string_lossy());
+        bar.suspend(|| match result {
+            Ok(line) => println!("{}", line),
+            Err(reason) => {
+                eprintln!("{}: {}", file.display(), reason);
+                all_ok = false;
+            }
+        });
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    all_ok
+}
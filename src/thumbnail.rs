@@ -0,0 +1,57 @@
+//! Small RGB thumbnails, for previewing an image in the terminal.
+
+use crate::pixels::{cell_range, to_rgb8};
+use crate::Png;
+
+/// A downscaled copy of an image, one 8-bit RGB triple per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+/// Box-filter the decoded image down to fit within `max_width` x
+/// `max_height` pixels, keeping its aspect ratio; images that already fit
+/// keep their size.
+///
+/// Alpha is composited over black. Returns `None` if the pixels were not
+/// decoded.
+pub fn thumbnail(png: &Png, max_width: u32, max_height: u32) -> Option<Thumbnail> {
+    if png.width == 0 || png.height == 0 || max_width == 0 || max_height == 0 {
+        return None;
+    }
+    let palette = png.chunks.iter().find(|c| &c.type_code == b"PLTE").map(|c| c.data.as_slice()).unwrap_or_default();
+    let (rgb, has_alpha) =
+        to_rgb8(&png.pixels, png.width, png.height, png.color_type, png.bit_depth, palette, png.trns.as_deref()).ok()?;
+    let channels = if has_alpha { 4 } else { 3 };
+
+    // Scale by whichever side is the tighter fit
+    let (mut width, mut height) = (png.width.min(max_width), png.height.min(max_height));
+    if png.width as u64 * height as u64 > png.height as u64 * width as u64 {
+        height = ((png.height as u64 * width as u64 / png.width as u64) as u32).max(1);
+    } else {
+        width = ((png.width as u64 * height as u64 / png.height as u64) as u32).max(1);
+    }
+    let (source_width, source_height) = (png.width as usize, png.height as usize);
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for ty in 0..height as usize {
+        let (y0, y1) = cell_range(ty, height as usize, source_height);
+        for tx in 0..width as usize {
+            let (x0, x1) = cell_range(tx, width as usize, source_width);
+            let mut sum = [0u64; 3];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pixel = &rgb[(y * source_width + x) * channels..][..channels];
+                    let alpha = if has_alpha { pixel[3] as u64 } else { 255 };
+                    for (total, &sample) in sum.iter_mut().zip(pixel) {
+                        *total += sample as u64 * alpha / 255;
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            pixels.push(sum.map(|total| (total / count) as u8));
+        }
+    }
+    Some(Thumbnail { width, height, pixels })
+}
@@ -0,0 +1,73 @@
+//! `--timing`: where the time goes while processing a file.
+//!
+//! The breakdown goes to stderr so it never mixes with the report or a JSON
+//! stream on stdout.
+
+use std::time::Duration;
+
+/// Wall-clock time spent in each phase of `process_file`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    /// Reading the file, or stdin, into memory
+    pub read: Duration,
+    /// The decoder's pass over the file
+    pub decode: Duration,
+    /// The raw chunk walk, the second pass over the file
+    pub chunk_parse: Duration,
+    /// Noise, crop and any format conversions
+    pub convert: Duration,
+    /// Encoding and writing the output image
+    pub encode: Duration,
+    /// The whole file, including reports, hashing and validation
+    pub total: Duration,
+}
+
+impl Timings {
+    /// Each phase with its label, ending with the time outside the phases.
+    fn phases(&self) -> [(&'static str, Duration); 6] {
+        let measured = self.read + self.decode + self.chunk_parse + self.convert + self.encode;
+        [
+            ("read", self.read),
+            ("decode", self.decode),
+            ("chunk parse", self.chunk_parse),
+            ("convert", self.convert),
+            ("re-encode", self.encode),
+            ("other", self.total.saturating_sub(measured)),
+        ]
+    }
+
+    /// Add `other`'s times to these, for batch totals.
+    pub fn add(&mut self, other: &Timings) {
+        self.read += other.read;
+        self.decode += other.decode;
+        self.chunk_parse += other.chunk_parse;
+        self.convert += other.convert;
+        self.encode += other.encode;
+        self.total += other.total;
+    }
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Print the breakdown for a single file.
+pub fn print(timings: &Timings) {
+    eprintln!("\nTiming:");
+    for (phase, duration) in timings.phases() {
+        eprintln!("  {:<12} {:>12}", phase, ms(duration));
+    }
+    eprintln!("  {:<12} {:>12}", "total", ms(timings.total));
+}
+
+/// Print per-phase totals and per-file averages over the `files` that were
+/// processed successfully.
+pub fn print_batch(totals: &Timings, files: usize) {
+    eprintln!("\nTiming over {} file(s):", files);
+    eprintln!("  {:<12} {:>12} {:>12}", "phase", "total", "average");
+    let average = |duration: Duration| ms(duration / files.max(1) as u32);
+    for (phase, duration) in totals.phases() {
+        eprintln!("  {:<12} {:>12} {:>12}", phase, ms(duration), average(duration));
+    }
+    eprintln!("  {:<12} {:>12} {:>12}", "total", ms(totals.total), average(totals.total));
+}
@@ -0,0 +1,611 @@
+use std::collections::HashSet;
+
+use png::{BitDepth, ColorType};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::error::UnpeelError;
+use crate::pixels::{to_rgba, Rgba};
+use crate::quantize::{dither_to_palette, map_to_palette, median_cut, palette_chunks};
+
+/// Crop the image to 88% of its original size, keeping the top-left portion.
+pub fn crop_image(
+    buf: &[u8],
+    original_width: u32,
+    original_height: u32,
+    bytes_per_pixel: usize,
+) -> (u32, u32, Vec<u8>) {
+    // Calculate new dimensions (88% of original, rounded down)
+    let new_width = (original_width as f64 * 0.88) as u32;
+    let new_height = (original_height as f64 * 0.88) as u32;
+
+    // Calculate row size in bytes
+    let original_row_size = (original_width as usize) * bytes_per_pixel;
+    let new_row_size = (new_width as usize) * bytes_per_pixel;
+
+    // Allocate buffer for cropped image
+    let mut cropped_buf = Vec::with_capacity((new_width as usize) * (new_height as usize) * bytes_per_pixel);
+
+    // Extract top-left portion: rows 0 to new_height-1, columns 0 to new_width-1
+    for row in 0..new_height as usize {
+        let start_idx = row * original_row_size;
+        let end_idx = start_idx + new_row_size;
+        cropped_buf.extend_from_slice(&buf[start_idx..end_idx]);
+    }
+
+    (new_width, new_height, cropped_buf)
+}
+
+/// A rotation or mirror image of the whole picture, for `reorient`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Rotate 90 degrees clockwise
+    Rotate90,
+    Rotate180,
+    /// Rotate 270 degrees clockwise (90 counterclockwise)
+    Rotate270,
+    /// Mirror left to right
+    FlipHorizontal,
+    /// Mirror top to bottom
+    FlipVertical,
+}
+
+impl Orientation {
+    /// Whether this swaps the width and height.
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(self, Orientation::Rotate90 | Orientation::Rotate270)
+    }
+}
+
+/// Rotate or flip packed PNG rows, moving whole pixels so every color type
+/// and bit depth works, sub-byte ones included. Returns the new width,
+/// height and pixels; 90 and 270 degree rotations swap the dimensions.
+pub fn reorient(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    orientation: Orientation,
+) -> (u32, u32, Vec<u8>) {
+    let (w, h) = (width as usize, height as usize);
+    let bits = color_type.samples() * bit_depth as usize;
+    let stride = (w * bits).div_ceil(8);
+    let (new_w, new_h) = if orientation.swaps_dimensions() { (h, w) } else { (w, h) };
+    let new_stride = (new_w * bits).div_ceil(8);
+
+    let mut out = vec![0u8; new_stride * new_h];
+    for y in 0..new_h {
+        for x in 0..new_w {
+            // The source pixel that lands at (x, y)
+            let (src_x, src_y) = match orientation {
+                Orientation::Rotate90 => (y, h - 1 - x),
+                Orientation::Rotate180 => (w - 1 - x, h - 1 - y),
+                Orientation::Rotate270 => (w - 1 - y, x),
+                Orientation::FlipHorizontal => (w - 1 - x, y),
+                Orientation::FlipVertical => (x, h - 1 - y),
+            };
+            let from = &pixels[src_y * stride..];
+            let to = &mut out[y * new_stride..];
+            if bits >= 8 {
+                let size = bits / 8;
+                to[x * size..(x + 1) * size].copy_from_slice(&from[src_x * size..(src_x + 1) * size]);
+            } else {
+                // Sub-byte pixels are packed most significant bits first
+                let mask = (1u8 << bits) - 1;
+                let src_shift = 8 - bits - (src_x * bits) % 8;
+                let value = (from[src_x * bits / 8] >> src_shift) & mask;
+                let dst_shift = 8 - bits - (x * bits) % 8;
+                to[x * bits / 8] |= value << dst_shift;
+            }
+        }
+    }
+    (new_w as u32, new_h as u32, out)
+}
+
+/// A rectangle of pixels for `crop_rect`: top-left corner and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Cut `rect` out of packed PNG rows into rows of its own width. Works on
+/// whole pixels at any bit depth. Fails if `rect` extends past the image.
+pub fn crop_rect(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    rect: CropRect,
+) -> Result<Vec<u8>, UnpeelError> {
+    let fits = |start: u32, size: u32, limit: u32| start.checked_add(size).is_some_and(|end| end <= limit);
+    if !fits(rect.x, rect.width, width) || !fits(rect.y, rect.height, height) {
+        return Err(UnpeelError::CropOutOfBounds { rect, width, height });
+    }
+    let bits = color_type.samples() * bit_depth as usize;
+    let stride = (width as usize * bits).div_ceil(8);
+    let new_stride = (rect.width as usize * bits).div_ceil(8);
+    let mut out = Vec::with_capacity(new_stride * rect.height as usize);
+    for y in rect.y as usize..(rect.y + rect.height) as usize {
+        let row = &pixels[y * stride..(y + 1) * stride];
+        let first_bit = rect.x as usize * bits;
+        if first_bit.is_multiple_of(8) {
+            out.extend_from_slice(&row[first_bit / 8..first_bit / 8 + new_stride]);
+        } else {
+            // Sub-byte pixels not starting on a byte boundary: shift each byte into place
+            let shift = first_bit % 8;
+            for i in 0..new_stride {
+                let high = row[first_bit / 8 + i] << shift;
+                let low = row.get(first_bit / 8 + i + 1).map_or(0, |&next| next >> (8 - shift));
+                out.push(high | low);
+            }
+            // Clear the padding bits past the last pixel
+            let used = (rect.width as usize * bits) % 8;
+            if let (true, Some(last)) = (used != 0, out.last_mut()) {
+                *last &= 0xFF << (8 - used);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Nudge one channel of every pixel up or down by a fixed step.
+pub fn add_randomized_noise(buf: &mut [u8], color_type: ColorType) {
+    add_noise(buf, color_type, &mut rand::thread_rng());
+}
+
+/// `add_randomized_noise` with the choices drawn from a generator seeded
+/// with `seed`, so the same seed always nudges the same pixels the same way.
+pub fn add_seeded_noise(buf: &mut [u8], color_type: ColorType, seed: [u8; 32]) {
+    add_noise(buf, color_type, &mut StdRng::from_seed(seed));
+}
+
+fn add_noise(buf: &mut [u8], color_type: ColorType, rng: &mut impl Rng) {
+    match color_type {
+        ColorType::Rgb => {
+            // RGB: 3 bytes per pixel (R, G, B)
+            for pixel in buf.chunks_exact_mut(3) {
+                // Randomly select R (0), G (1), or B (2)
+                let channel = rng.gen_range(0..3);
+                // Randomly add or subtract 1
+                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
+                let new_value = pixel[channel] as i16 + change;
+                pixel[channel] = new_value.clamp(0, 255) as u8;
+            }
+        }
+        ColorType::Rgba => {
+            // RGBA: 4 bytes per pixel (R, G, B, A)
+            for pixel in buf.chunks_exact_mut(4) {
+                // Randomly select R (0), G (1), or B (2) - skip Alpha (3)
+                let channel = rng.gen_range(0..3);
+                // Randomly add or subtract 1
+                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
+                let new_value = pixel[channel] as i16 + change;
+                pixel[channel] = new_value.clamp(0, 255) as u8;
+            }
+        }
+        ColorType::Grayscale => {
+            // Grayscale: 1 byte per pixel
+            for pixel in buf.iter_mut() {
+                // Randomly add or subtract 1
+                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
+                let new_value = *pixel as i16 + change;
+                *pixel = new_value.clamp(0, 255) as u8;
+            }
+        }
+        ColorType::GrayscaleAlpha => {
+            // GrayscaleAlpha: 2 bytes per pixel (G, A)
+            for pixel in buf.chunks_exact_mut(2) {
+                // Modify the grayscale channel (0), skip Alpha (1)
+                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
+                let new_value = pixel[0] as i16 + change;
+                pixel[0] = new_value.clamp(0, 255) as u8;
+            }
+        }
+        ColorType::Indexed => {
+            // Indexed: 1 byte per pixel (palette index)
+            // For indexed color, we modify the palette index value
+            // This will change which color from the palette is used
+            for pixel in buf.iter_mut() {
+                // Randomly add or subtract 1 from the palette index
+                let change: i16 = if rng.gen_bool(0.5) { 7 } else { -7 };
+                let new_value = *pixel as i16 + change;
+                *pixel = new_value.clamp(0, 255) as u8;
+            }
+        }
+    }
+}
+
+/// Composite an image with an alpha channel over an opaque `background`,
+/// returning RGB pixels at the same bit depth.
+///
+/// Works on RGBA and grayscale+alpha at 8 or 16 bits per sample (16-bit
+/// samples are big-endian, as decoded). Color types without an alpha channel
+/// are rejected with `UnsupportedColorType`.
+pub fn flatten_alpha(
+    pixels: &[u8],
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    background: [u8; 3],
+) -> Result<Vec<u8>, UnpeelError> {
+    let channels = match color_type {
+        ColorType::Rgba => 4,
+        ColorType::GrayscaleAlpha => 2,
+        _ => return Err(UnpeelError::UnsupportedColorType(color_type)),
+    };
+    let sample_bytes = if bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+    let max: u32 = if sample_bytes == 2 { 0xFFFF } else { 0xFF };
+    // Scale the 8-bit background to the sample range; 0xFF * 257 = 0xFFFF
+    let background = background.map(|c| c as u32 * (max / 0xFF));
+
+    let read = |bytes: &[u8]| -> u32 {
+        if sample_bytes == 2 {
+            u16::from_be_bytes([bytes[0], bytes[1]]) as u32
+        } else {
+            bytes[0] as u32
+        }
+    };
+    let mut out = Vec::with_capacity(pixels.len() / channels * 3);
+    for pixel in pixels.chunks_exact(channels * sample_bytes) {
+        let samples: Vec<u32> = pixel.chunks_exact(sample_bytes).map(read).collect();
+        let alpha = samples[channels - 1];
+        let color = if channels == 4 { [samples[0], samples[1], samples[2]] } else { [samples[0]; 3] };
+        for (c, bg) in color.iter().zip(background) {
+            // Rounded integer blend: (c * a + bg * (max - a)) / max
+            let blended = (c * alpha + bg * (max - alpha) + max / 2) / max;
+            if sample_bytes == 2 {
+                out.extend_from_slice(&(blended as u16).to_be_bytes());
+            } else {
+                out.push(blended as u8);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Convert RGB or RGBA pixels to grayscale at the same bit depth, using the
+/// Rec. 709 luma weights (0.2126 R + 0.7152 G + 0.0722 B). Alpha is dropped.
+///
+/// Color types other than RGB(A) are rejected with `UnsupportedColorType`.
+pub fn to_grayscale(pixels: &[u8], color_type: ColorType, bit_depth: BitDepth) -> Result<Vec<u8>, UnpeelError> {
+    let channels = match color_type {
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        _ => return Err(UnpeelError::UnsupportedColorType(color_type)),
+    };
+    let sample_bytes = if bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+    let read = |bytes: &[u8]| -> f64 {
+        if sample_bytes == 2 {
+            u16::from_be_bytes([bytes[0], bytes[1]]) as f64
+        } else {
+            bytes[0] as f64
+        }
+    };
+    let mut out = Vec::with_capacity(pixels.len() / channels);
+    for pixel in pixels.chunks_exact(channels * sample_bytes) {
+        let r = read(&pixel[0..]);
+        let g = read(&pixel[sample_bytes..]);
+        let b = read(&pixel[2 * sample_bytes..]);
+        let luma = (0.2126 * r + 0.7152 * g + 0.0722 * b).round();
+        if sample_bytes == 2 {
+            out.extend_from_slice(&(luma as u16).to_be_bytes());
+        } else {
+            out.push(luma as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// Pixels converted by `convert_color_type`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorConversion {
+    pub pixels: Vec<u8>,
+    pub bit_depth: BitDepth,
+    /// PLTE and optional tRNS data to write with indexed output
+    pub palette: Option<(Vec<u8>, Option<Vec<u8>>)>,
+    /// What the conversion threw away, e.g. "color reduced to luma"; empty
+    /// when it was lossless
+    pub losses: Vec<String>,
+    /// For indexed output, how many distinct colors the palette was built from
+    pub source_colors: Option<usize>,
+}
+
+impl ColorConversion {
+    /// Number of palette entries, 0 unless the output is indexed.
+    pub fn palette_len(&self) -> usize {
+        self.palette.as_ref().map_or(0, |(plte, _)| plte.len() / 3)
+    }
+}
+
+/// Convert pixels of any color type and bit depth to `target`.
+///
+/// `palette` and `trns` are the source's raw PLTE and tRNS data, if any.
+/// 16-bit images stay 16-bit and everything else becomes 8-bit, except that
+/// indexed output is always 8-bit: its palette is built with median cut when
+/// the image has more than 256 distinct colors. Grayscale uses the Rec. 709
+/// luma weights, and dropping alpha makes transparent pixels opaque. With
+/// `dither`, indexed output uses Floyd-Steinberg dithering.
+#[allow(clippy::too_many_arguments)]
+pub fn convert_color_type(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+    target: ColorType,
+    dither: bool,
+) -> Result<ColorConversion, UnpeelError> {
+    let rgba = to_rgba(pixels, width, height, color_type, bit_depth, palette, trns)?;
+    let max = rgba.max;
+    let mut losses = Vec::new();
+    let keeps_alpha = matches!(target, ColorType::GrayscaleAlpha | ColorType::Rgba | ColorType::Indexed);
+    if !keeps_alpha && rgba.pixels.iter().any(|p| p[3] < max) {
+        losses.push("transparent pixels made opaque".to_string());
+    }
+
+    if target == ColorType::Indexed {
+        let mut converted = quantize_rgba(rgba, width, 256, dither, losses);
+        let (entries, source_colors) = (converted.palette_len(), converted.source_colors.unwrap_or_default());
+        if source_colors > entries {
+            converted.losses.push(format!("{} colors reduced to a {}-color palette", source_colors, entries));
+        }
+        return Ok(converted);
+    }
+
+    let gray = matches!(target, ColorType::Grayscale | ColorType::GrayscaleAlpha);
+    if gray && rgba.pixels.iter().any(|p| p[0] != p[1] || p[1] != p[2]) {
+        losses.push("color reduced to luma".to_string());
+    }
+    let sixteen = max == 0xFFFF;
+    let mut out = Vec::with_capacity(rgba.pixels.len() * target.samples() * if sixteen { 2 } else { 1 });
+    for [r, g, b, a] in rgba.pixels {
+        let luma = || (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64).round() as u16;
+        let samples = match target {
+            ColorType::Grayscale => vec![luma()],
+            ColorType::GrayscaleAlpha => vec![luma(), a],
+            ColorType::Rgb => vec![r, g, b],
+            _ => vec![r, g, b, a],
+        };
+        for sample in samples {
+            if sixteen {
+                out.extend_from_slice(&sample.to_be_bytes());
+            } else {
+                out.push(sample as u8);
+            }
+        }
+    }
+    let bit_depth = if sixteen { BitDepth::Sixteen } else { BitDepth::Eight };
+    Ok(ColorConversion { pixels: out, bit_depth, palette: None, losses, source_colors: None })
+}
+
+/// Reduce pixels of any color type and bit depth to an 8-bit indexed image
+/// of at most `max_colors` colors (1 to 256), building the palette by median
+/// cut and mapping each pixel to its nearest entry.
+///
+/// `palette` and `trns` are the source's raw PLTE and tRNS data, if any.
+/// Translucent colors keep their alpha through a tRNS chunk. With `dither`,
+/// pixels are mapped with Floyd-Steinberg dithering so gradients don't band.
+#[allow(clippy::too_many_arguments)]
+pub fn quantize(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+    max_colors: usize,
+    dither: bool,
+) -> Result<ColorConversion, UnpeelError> {
+    let rgba = to_rgba(pixels, width, height, color_type, bit_depth, palette, trns)?;
+    Ok(quantize_rgba(rgba, width, max_colors.clamp(1, 256), dither, Vec::new()))
+}
+
+fn quantize_rgba(rgba: Rgba, width: u32, max_colors: usize, dither: bool, mut losses: Vec<String>) -> ColorConversion {
+    let max = rgba.max;
+    if max == 0xFFFF {
+        losses.push("16-bit samples reduced to 8 bits".to_string());
+    }
+    let shrink = |sample: u16| if max == 0xFFFF { ((sample as u32 * 255 + 32767) / 65535) as u8 } else { sample as u8 };
+    let colors: Vec<[u8; 4]> = rgba.pixels.iter().map(|p| p.map(shrink)).collect();
+    let entries = median_cut(&colors, max_colors);
+    ColorConversion {
+        pixels: if dither {
+            dither_to_palette(&colors, width as usize, &entries)
+        } else {
+            map_to_palette(&colors, &entries)
+        },
+        bit_depth: BitDepth::Eight,
+        palette: Some(palette_chunks(&entries)),
+        losses,
+        source_colors: Some(colors.iter().collect::<HashSet<_>>().len()),
+    }
+}
+
+/// Rescale every sample between 8 and 16 bits.
+///
+/// 16 to 8 rounds to the nearest value rather than truncating; 8 to 16
+/// replicates the byte (`v * 257`) so full scale maps to full scale. Returns
+/// `None` unless converting between 8 and 16 bits.
+pub fn convert_bit_depth(pixels: &[u8], from: BitDepth, to: BitDepth) -> Option<Vec<u8>> {
+    match (from, to) {
+        (BitDepth::Sixteen, BitDepth::Eight) => Some(
+            pixels
+                .chunks_exact(2)
+                .map(|sample| {
+                    let value = u16::from_be_bytes([sample[0], sample[1]]) as u32;
+                    ((value * 255 + 32767) / 65535) as u8
+                })
+                .collect(),
+        ),
+        (BitDepth::Eight, BitDepth::Sixteen) => {
+            Some(pixels.iter().flat_map(|&value| (value as u16 * 257).to_be_bytes()).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Transfer function to re-encode samples to with `apply_gamma`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GammaTarget {
+    /// Linear light (gamma 1.0)
+    Linear,
+    /// The sRGB transfer curve
+    Srgb,
+}
+
+impl GammaTarget {
+    /// The gAMA value describing output encoded for this target, in units of 1/100000.
+    pub fn gama_value(self) -> u32 {
+        match self {
+            GammaTarget::Linear => 100_000,
+            GammaTarget::Srgb => 45_455,
+        }
+    }
+
+    fn encode(self, linear: f64) -> f64 {
+        match self {
+            GammaTarget::Linear => linear,
+            GammaTarget::Srgb if linear <= 0.0031308 => linear * 12.92,
+            GammaTarget::Srgb => 1.055 * linear.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+}
+
+/// Decode samples stored with `file_gamma` (the gAMA value, e.g. 0.45455) to
+/// linear light and re-encode them for `target`. Alpha samples are left as is.
+///
+/// Handles 8- and 16-bit samples of any color type except indexed, whose
+/// colors live in PLTE (see `apply_gamma_to_palette`). Returns `false` if the
+/// pixels were left unchanged because the format isn't supported.
+pub fn apply_gamma(
+    pixels: &mut [u8],
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    file_gamma: f64,
+    target: GammaTarget,
+) -> bool {
+    if color_type == ColorType::Indexed || !matches!(bit_depth, BitDepth::Eight | BitDepth::Sixteen) {
+        return false;
+    }
+    let channels = color_type.samples();
+    let has_alpha = matches!(color_type, ColorType::Rgba | ColorType::GrayscaleAlpha);
+    let sample_bytes = if bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+    let table = gamma_table(if sample_bytes == 2 { 0xFFFF } else { 0xFF }, file_gamma, target);
+
+    for (index, sample) in pixels.chunks_exact_mut(sample_bytes).enumerate() {
+        if has_alpha && index % channels == channels - 1 {
+            continue;
+        }
+        if sample_bytes == 2 {
+            let value = u16::from_be_bytes([sample[0], sample[1]]);
+            sample.copy_from_slice(&table[value as usize].to_be_bytes());
+        } else {
+            sample[0] = table[sample[0] as usize] as u8;
+        }
+    }
+    true
+}
+
+/// `apply_gamma` for the 8-bit RGB entries of a PLTE chunk.
+pub fn apply_gamma_to_palette(palette: &mut [u8], file_gamma: f64, target: GammaTarget) {
+    let table = gamma_table(0xFF, file_gamma, target);
+    for sample in palette.iter_mut() {
+        *sample = table[*sample as usize] as u8;
+    }
+}
+
+/// Lookup table mapping every sample value up to `max` through the gamma conversion.
+fn gamma_table(max: u32, file_gamma: f64, target: GammaTarget) -> Vec<u16> {
+    let max_f = max as f64;
+    (0..=max)
+        .map(|value| {
+            let linear = (value as f64 / max_f).powf(1.0 / file_gamma);
+            (target.encode(linear) * max_f).round().clamp(0.0, max_f) as u16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 image whose pixels are all different, so any wrong move shows:
+    ///
+    /// ```text
+    /// 1 2 3
+    /// 4 5 6
+    /// ```
+    fn asymmetric_gray() -> Vec<u8> {
+        vec![1, 2, 3, 4, 5, 6]
+    }
+
+    fn reorient_gray(pixels: &[u8], width: u32, height: u32, orientation: Orientation) -> (u32, u32, Vec<u8>) {
+        reorient(pixels, width, height, ColorType::Grayscale, BitDepth::Eight, orientation)
+    }
+
+    #[test]
+    fn rotations_move_pixels_clockwise_and_swap_dimensions() {
+        let pixels = asymmetric_gray();
+        assert_eq!(reorient_gray(&pixels, 3, 2, Orientation::Rotate90), (2, 3, vec![4, 1, 5, 2, 6, 3]));
+        assert_eq!(reorient_gray(&pixels, 3, 2, Orientation::Rotate180), (3, 2, vec![6, 5, 4, 3, 2, 1]));
+        assert_eq!(reorient_gray(&pixels, 3, 2, Orientation::Rotate270), (2, 3, vec![3, 6, 2, 5, 1, 4]));
+    }
+
+    #[test]
+    fn flips_mirror_rows_or_columns() {
+        let pixels = asymmetric_gray();
+        assert_eq!(reorient_gray(&pixels, 3, 2, Orientation::FlipHorizontal), (3, 2, vec![3, 2, 1, 6, 5, 4]));
+        assert_eq!(reorient_gray(&pixels, 3, 2, Orientation::FlipVertical), (3, 2, vec![4, 5, 6, 1, 2, 3]));
+    }
+
+    #[test]
+    fn transforms_compose_in_order() {
+        let pixels = asymmetric_gray();
+        let (w, h, rotated) = reorient_gray(&pixels, 3, 2, Orientation::Rotate90);
+        let (w, h, flipped) = reorient_gray(&rotated, w, h, Orientation::FlipHorizontal);
+        // A quarter turn then a mirror is a transpose
+        assert_eq!((w, h, flipped), (2, 3, vec![1, 4, 2, 5, 3, 6]));
+
+        let (w, h, back) = [Orientation::Rotate90; 4]
+            .iter()
+            .fold((3, 2, pixels.clone()), |(w, h, p), &o| reorient_gray(&p, w, h, o));
+        assert_eq!((w, h, back), (3, 2, pixels));
+    }
+
+    #[test]
+    fn crop_rect_cuts_out_whole_pixels() {
+        let pixels = asymmetric_gray();
+        let rect = CropRect { x: 1, y: 0, width: 2, height: 2 };
+        assert_eq!(crop_rect(&pixels, 3, 2, ColorType::Grayscale, BitDepth::Eight, rect).unwrap(), vec![2, 3, 5, 6]);
+
+        // 2-bit pixels 0..=3 in a row; cropping from pixel 1 shifts across the byte
+        let packed = vec![0b00_01_10_11, 0b01_00_00_00];
+        let rect = CropRect { x: 1, y: 0, width: 4, height: 1 };
+        let cropped = crop_rect(&packed, 5, 1, ColorType::Grayscale, BitDepth::Two, rect).unwrap();
+        assert_eq!(cropped, vec![0b01_10_11_01]);
+
+        let outside = CropRect { x: 2, y: 0, width: 2, height: 1 };
+        assert!(crop_rect(&pixels, 3, 2, ColorType::Grayscale, BitDepth::Eight, outside).is_err());
+    }
+
+    #[test]
+    fn whole_pixels_move_for_wide_and_packed_formats() {
+        // 16-bit RGB: each pixel is six bytes and stays intact
+        let rgb16: Vec<u8> = (0..2 * 6).collect();
+        let (_, _, flipped) = reorient(&rgb16, 2, 1, ColorType::Rgb, BitDepth::Sixteen, Orientation::FlipHorizontal);
+        assert_eq!(flipped, vec![6, 7, 8, 9, 10, 11, 0, 1, 2, 3, 4, 5]);
+
+        // 1-bit 3x2 with only the top-left pixel set; rows are padded to a byte
+        let bits = vec![0b1000_0000, 0b0000_0000];
+        let (w, h, rotated) = reorient(&bits, 3, 2, ColorType::Grayscale, BitDepth::One, Orientation::Rotate90);
+        assert_eq!((w, h), (2, 3));
+        assert_eq!(rotated, vec![0b0100_0000, 0, 0]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Checks on chunk ordering and multiplicity, and on the header's color type
+//! and bit depth, against the PNG specification.
+
+use png::ColorType;
+
+use crate::chunk::Chunk;
+
+/// Check the order and count of critical chunks (and tRNS placement),
+/// returning a description of every spec violation found.
+///
+/// Covers: a legal color type and bit depth pair in IHDR, IHDR first and unique, at most one PLTE and not after IDAT or in a
+/// grayscale image, tRNS after PLTE for indexed color, IDAT present and
+/// consecutive, and exactly one IEND as the last chunk.
+pub fn validate_chunk_order(chunks: &[Chunk], color_type: ColorType) -> Vec<String> {
+    let mut violations: Vec<String> = color_depth_violation(chunks).into_iter().collect();
+    let count = |code: &[u8; 4]| chunks.iter().filter(|c| &c.type_code == code).count();
+    let first_offset = |code: &[u8; 4]| chunks.iter().find(|c| &c.type_code == code).map(|c| c.offset);
+
+    match chunks.first() {
+        Some(first) if &first.type_code != b"IHDR" => {
+            violations.push(format!("IHDR is not the first chunk (found {} at offset {})", first.type_str(), first.offset))
+        }
+        _ => {}
+    }
+    for (code, limit) in [(b"IHDR", 1), (b"PLTE", 1)] {
+        let n = count(code);
+        if n > limit {
+            violations.push(format!("{} appears {} times", String::from_utf8_lossy(code), n));
+        }
+    }
+
+    let first_idat = first_offset(b"IDAT");
+    if let Some(plte) = first_offset(b"PLTE") {
+        if matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha) {
+            violations.push(format!("PLTE at offset {} is not allowed in a {:?} image", plte, color_type));
+        }
+    }
+    for chunk in chunks.iter().filter(|c| &c.type_code == b"PLTE") {
+        if first_idat.is_some_and(|idat| chunk.offset > idat) {
+            violations.push(format!("PLTE at offset {} appears after IDAT", chunk.offset));
+        }
+    }
+    if color_type == ColorType::Indexed {
+        if let Some(trns) = first_offset(b"tRNS") {
+            if first_offset(b"PLTE").is_none_or(|plte| trns < plte) {
+                violations.push(format!("tRNS at offset {} appears before PLTE", trns));
+            }
+        }
+    }
+
+    // All IDAT chunks must form one consecutive run
+    match chunks.iter().position(|c| &c.type_code == b"IDAT") {
+        None => violations.push("no IDAT chunk".to_string()),
+        Some(start) => {
+            let run_end = chunks[start..].iter().position(|c| &c.type_code != b"IDAT").map_or(chunks.len(), |n| start + n);
+            if let Some(stray) = chunks[run_end..].iter().find(|c| &c.type_code == b"IDAT") {
+                violations.push(format!(
+                    "IDAT at offset {} is separated from the other IDAT chunks by {}",
+                    stray.offset,
+                    chunks[run_end].type_str()
+                ));
+            }
+        }
+    }
+
+    match count(b"IEND") {
+        0 => violations.push("IEND is missing".to_string()),
+        1 => {}
+        n => violations.push(format!("IEND appears {} times", n)),
+    }
+    if let Some(last) = chunks.last() {
+        if &last.type_code != b"IEND" && count(b"IEND") > 0 {
+            violations.push(format!("IEND is not the last chunk (found {} after it)", last.type_str()));
+        }
+    }
+
+    violations
+}
+
+/// The bit depths the spec allows for a color type code, or `None` for an
+/// unknown color type.
+fn allowed_depths(color_type: u8) -> Option<&'static [u8]> {
+    match color_type {
+        0 => Some(&[1, 2, 4, 8, 16]),
+        3 => Some(&[1, 2, 4, 8]),
+        2 | 4 | 6 => Some(&[8, 16]),
+        _ => None,
+    }
+}
+
+/// Describe the IHDR's color type and bit depth pair if the spec forbids it,
+/// e.g. 4-bit truecolor, which some buggy encoders write. Returns `None` for
+/// a legal pair or when there is no readable IHDR.
+pub fn color_depth_violation(chunks: &[Chunk]) -> Option<String> {
+    let ihdr = chunks.iter().find(|c| &c.type_code == b"IHDR")?;
+    let (&bit_depth, &color_type) = (ihdr.data.get(8)?, ihdr.data.get(9)?);
+    let Some(allowed) = allowed_depths(color_type) else {
+        return Some(format!("IHDR declares unknown color type {}", color_type));
+    };
+    if allowed.contains(&bit_depth) {
+        return None;
+    }
+    let name = ColorType::from_u8(color_type).map_or_else(String::new, |c| format!(" ({:?})", c));
+    let (last, rest) = allowed.split_last()?;
+    let rest: Vec<String> = rest.iter().map(u8::to_string).collect();
+    Some(format!(
+        "IHDR declares bit depth {} for color type {}{}, which only allows {} or {}",
+        bit_depth,
+        color_type,
+        name,
+        rest.join(", "),
+        last
+    ))
+}
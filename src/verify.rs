@@ -0,0 +1,84 @@
+//! `--verify-only`: a quick integrity check of the chunk CRCs and the IDAT
+//! stream, with no report and no output image.
+
+use std::path::PathBuf;
+
+use unpeel::{color_depth_violation, Chunk, Png};
+
+use crate::progress;
+
+/// Chunks whose stored CRC doesn't match their contents.
+pub fn crc_failures(png: &Png) -> impl Iterator<Item = &Chunk> {
+    png.chunks.iter().filter(|chunk| !chunk.crc_is_valid())
+}
+
+/// How the decompressed IDAT stream differs from the size the header
+/// implies, or `None` if it matches. Images without IDAT are not checked.
+pub fn idat_problem(png: &Png) -> Option<String> {
+    let expected = png.expected_idat_size()?;
+    if png.idat_bytes() == 0 {
+        return None;
+    }
+    match png.inflated_idat_size(expected) {
+        Ok(actual) if actual == expected => None,
+        Ok(actual) if actual > expected => {
+            Some(format!("more than the {} bytes a {}x{} image needs", expected, png.width, png.height))
+        }
+        Ok(actual) => Some(format!("{} bytes, but a {}x{} image needs {}", actual, png.width, png.height, expected)),
+        Err(actual) => Some(format!("{} bytes before failing, but a {}x{} image needs {}", actual, png.width, png.height, expected)),
+    }
+}
+
+/// Check that `bytes` is a PNG with a legal color type and bit depth, whose
+/// chunks all pass their CRC and whose IDAT stream inflates to exactly the size the header implies. Pixels are
+/// never decoded, so this stays fast on large images. Returns the first
+/// problem found.
+pub fn verify(bytes: &[u8]) -> Result<(), String> {
+    let png = Png::metadata_from_bytes(bytes).map_err(|e| e.to_string())?;
+    if let Some(problem) = color_depth_violation(&png.chunks) {
+        return Err(problem);
+    }
+    let failures: Vec<&Chunk> = crc_failures(&png).collect();
+    if let Some(first) = failures.first() {
+        return Err(format!(
+            "{} chunk(s) failed CRC validation, first {} at offset {}",
+            failures.len(),
+            first.type_str(),
+            first.offset
+        ));
+    }
+    if png.idat_bytes() == 0 {
+        return Err("no image data (IDAT)".to_string());
+    }
+    match idat_problem(&png) {
+        Some(problem) => Err(format!("IDAT data decompresses to {}", problem)),
+        None => Ok(()),
+    }
+}
+
+/// Read and verify one file, or stdin for `-`.
+pub fn verify_file(file_path: &str) -> Result<(), String> {
+    let bytes = if file_path == "-" { crate::read_stdin() } else { std::fs::read(file_path).map_err(Into::into) };
+    verify(&bytes.map_err(|e| e.to_string())?)
+}
+
+/// Verify `files`, printing one `OK` or `FAILED` line per file and a final
+/// tally. Returns whether every file passed.
+pub fn run_files(files: &[PathBuf], show_progress: bool) -> bool {
+    let bar = progress::batch_bar(files.len(), show_progress);
+    let mut failed = 0;
+    for file in files {
+        let result = verify_file(&file.to_string_lossy());
+        bar.suspend(|| match result {
+            Ok(()) => println!("OK      {}", file.display()),
+            Err(reason) => {
+                println!("FAILED  {}: {}", file.display(), reason);
+                failed += 1;
+            }
+        });
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    println!("\nVerified {} file(s): {} OK, {} failed", files.len(), files.len() - failed, failed);
+    failed == 0
+}
@@ -0,0 +1,48 @@
+//! WebP output, built with the `webp` feature.
+
+use std::path::Path;
+
+use png::{BitDepth, ColorType};
+use webp::Encoder;
+
+use crate::chunk::Chunk;
+use crate::error::UnpeelError;
+use crate::output::write_output_file;
+use crate::pixels::to_rgb8;
+
+/// Encode decoded PNG pixels as WebP at `output_path`: lossless by default,
+/// lossy at `quality` (0 to 100) if given.
+///
+/// WebP only holds 8-bit RGB or RGBA, so grayscale is widened to RGB, 16-bit
+/// samples keep their high byte, and indexed pixels are looked up in the PLTE
+/// from `chunks`. Transparency in a tRNS chunk becomes an alpha channel. No
+/// other chunks are carried over. An existing file at `output_path` is only
+/// replaced when `overwrite` is set.
+#[allow(clippy::too_many_arguments)]
+pub fn write_webp_image(
+    output_path: &Path,
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    chunks: &[Chunk],
+    image_data: &[u8],
+    quality: Option<f32>,
+    overwrite: bool,
+) -> Result<(), UnpeelError> {
+    let find = |type_code: &[u8; 4]| chunks.iter().find(|c| &c.type_code == type_code).map(|c| c.data.as_slice());
+    let trns = find(b"tRNS");
+    let (pixels, has_alpha) =
+        to_rgb8(image_data, width, height, color_type, bit_depth, find(b"PLTE").unwrap_or_default(), trns)?;
+
+    let encoder = if has_alpha {
+        Encoder::from_rgba(&pixels, width, height)
+    } else {
+        Encoder::from_rgb(&pixels, width, height)
+    };
+    let encoded = encoder
+        .encode_simple(quality.is_none(), quality.unwrap_or(75.0))
+        .map_err(|e| UnpeelError::WebpEncodeError(format!("{:?}", e)))?;
+    write_output_file(output_path, &encoded, overwrite)?;
+    Ok(())
+}